@@ -40,17 +40,18 @@ async fn do_main() -> anyhow::Result<()> {
     ).await?;
 
     println!("* Uploading file.");
+    let checksum = b2::compute_sha1(b"abcd");
     let upload_request = b2::UploadFile::builder()
         .file_name("my-test-file.txt")?
         .content_type("text/plain")
-        .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+        .sha1_checksum(&checksum)
         .build()?;
 
     let file = b2::upload_file(&mut upload_auth, upload_request, b"abcd")
         .await?;
 
     println!("* Downloading file.");
-    let download_request = b2::DownloadFile::with_id(&file.file_id());
+    let download_request = b2::DownloadFile::with_id(file.file_id());
     let (downloaded, _headers) = b2::download_file(&mut auth, download_request)
         .await?;
 
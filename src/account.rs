@@ -5,12 +5,15 @@
 
 //! Account-related B2 API calls.
 
-use std::fmt;
+use std::{borrow::Cow, fmt, sync::Arc};
 
 use crate::{
     prelude::*,
     client::HttpClient,
+    clock::{self, Clock},
     error::{ValidationError, Error},
+    metrics::RetryAttempt,
+    retry::RetryPolicy,
     types::*,
 };
 
@@ -18,13 +21,13 @@ use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 
 
-const B2_AUTH_URL: &str = "https://api.backblazeb2.com/b2api/v2/";
+const B2_AUTH_URL: &str = "https://api.backblazeb2.com";
 
 /// Authorization token and related information obtained from
 /// [authorize_account].
 ///
 /// The token is valid for no more than 24 hours.
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct Authorization<C>
     where C: HttpClient,
 {
@@ -41,8 +44,116 @@ pub struct Authorization<C>
     pub(crate) download_url: String,
     recommended_part_size: u64,
     absolute_minimum_part_size: u64,
-    // The base URL to use for all API calls using the AWS S3-compatible API.j
-    _s3_api_url: String,
+    // The base URL to use for all API calls using the AWS S3-compatible API.
+    s3_api_url: Option<String>,
+    // When the application key used for the authorization expires.
+    key_expiration: Option<chrono::DateTime<chrono::Utc>>,
+    // When the authorization token was received.
+    obtained_at: chrono::DateTime<chrono::Utc>,
+    log_redaction: LogRedaction,
+    clock: Arc<dyn Clock>,
+    clock_skew: chrono::Duration,
+    // The application added to the client's User-Agent by with_user_agent.
+    user_agent_app: Option<String>,
+}
+
+impl<C> fmt::Debug for Authorization<C>
+    where C: HttpClient + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Authorization");
+
+        // The client may hold a pending request, including its file name
+        // header and body.
+        if self.log_redaction.file_names {
+            s.field("client", &"<redacted>");
+        } else {
+            s.field("client", &self.client);
+        }
+
+        s.field("account_id", &self.account_id)
+            .field("authorization_token", &"<redacted>")
+            .field("allowed", &self.allowed)
+            .field("api_url", &self.api_url)
+            .field("download_url", &self.download_url)
+            .field("recommended_part_size", &self.recommended_part_size)
+            .field("absolute_minimum_part_size",
+                &self.absolute_minimum_part_size)
+            .field("s3_api_url", &self.s3_api_url)
+            .field("key_expiration", &self.key_expiration)
+            .field("obtained_at", &self.obtained_at)
+            .field("log_redaction", &self.log_redaction)
+            .field("clock", &self.clock)
+            .field("clock_skew", &self.clock_skew)
+            .field("user_agent_app", &self.user_agent_app)
+            .finish()
+    }
+}
+
+/// Options to hide sensitive information from [Debug](fmt::Debug) output and
+/// error messages.
+///
+/// Set the options for an [Authorization] via
+/// [with_log_redaction](Authorization::with_log_redaction).
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct LogRedaction {
+    /// Replace file names and file name prefixes with a hash of the name.
+    ///
+    /// The HTTP client of an [Authorization] or
+    /// [DownloadAuthorization](crate::file::DownloadAuthorization) is omitted
+    /// from its `Debug` output, as a pending request may contain a file name.
+    ///
+    /// Names are replaced in the messages of [B2Error](crate::error::B2Error)s
+    /// and [ValidationError]s returned from uploading, downloading, hiding,
+    /// deleting, or renaming a file by name, and prefixes in those returned
+    /// from listing files or obtaining a download authorization. The prefix
+    /// in [Error::NamePrefixRestricted] is replaced as well.
+    ///
+    /// [Error::Client] and [Error::Transport] errors are returned as the HTTP
+    /// client produced them; they may include the request URL, which contains
+    /// the file name when downloading a file by name.
+    pub file_names: bool,
+}
+
+impl LogRedaction {
+    /// Return the file name to display.
+    ///
+    /// If file names are redacted, this is a short hash of the name, so that
+    /// log entries for the same file can still be correlated.
+    pub fn file_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        if self.file_names {
+            let hash = crate::file::compute_sha1(name.as_bytes());
+            Cow::Owned(format!("<file {}>", &hash[.. 12]))
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+
+    /// Redact the file name or prefix `name` from the message of a B2 or
+    /// validation error.
+    pub(crate) fn error<E>(&self, err: Error<E>, name: &str) -> Error<E>
+        where E: fmt::Debug + fmt::Display,
+    {
+        if ! self.file_names {
+            return err;
+        }
+
+        match err {
+            Error::B2(mut e) if ! name.is_empty() => {
+                e.redact(name, &self.file_name(name));
+                Error::B2(e)
+            },
+            Error::FeatureNotEnabled { feature, mut error }
+                if ! name.is_empty() =>
+            {
+                error.redact(name, &self.file_name(name));
+                Error::FeatureNotEnabled { feature, error }
+            },
+            Error::Validation(e) if ! name.is_empty() =>
+                Error::Validation(e.redact(name, &self.file_name(name))),
+            e => e,
+        }
+    }
 }
 
 impl<C> Authorization<C>
@@ -61,7 +172,7 @@ impl<C> Authorization<C>
         download_url: String,
         recommended_part_size: u64,
         absolute_minimum_part_size: u64,
-        _s3_api_url: String,
+        s3_api_url: String,
     ) -> Self {
         Self {
             client,
@@ -72,23 +183,341 @@ impl<C> Authorization<C>
             download_url,
             recommended_part_size,
             absolute_minimum_part_size,
-            _s3_api_url,
+            s3_api_url: Some(s3_api_url),
+            key_expiration: None,
+            obtained_at: chrono::Utc::now(),
+            log_redaction: LogRedaction::default(),
+            clock: clock::system(),
+            clock_skew: default_clock_skew(),
+            user_agent_app: None,
+        }
+    }
+
+    // Allow tests to drop the capabilities they don't need.
+    #[cfg(test)]
+    #[allow(dead_code)]
+    pub(crate) fn with_only_capabilities(mut self, caps: Vec<Capability>)
+    -> Self {
+        self.allowed.capabilities = caps;
+        self
+    }
+
+    /// Hide file names from `Debug` output and error messages; see
+    /// [LogRedaction].
+    pub fn with_log_redaction(mut self, redaction: LogRedaction) -> Self {
+        self.log_redaction = redaction;
+        self
+    }
+
+    /// The options for hiding sensitive information.
+    pub fn log_redaction(&self) -> LogRedaction { self.log_redaction }
+
+    /// Use the provided [Clock] for expiration checks.
+    ///
+    /// The clock is also used by the [DownloadAuthorization]s and
+    /// [UploadAuthorization]s obtained via this `Authorization`. The time at
+    /// which this `Authorization` was obtained is not changed; use
+    /// [authorize_account_with_clock] to set it from the clock as well.
+    ///
+    /// [DownloadAuthorization]: crate::file::DownloadAuthorization
+    /// [UploadAuthorization]: crate::file::UploadAuthorization
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Obtain a new authorization token, e.g. before the current one
+    /// [expires](Self::is_probably_expired).
+    ///
+    /// The token, capabilities, URLs, and part sizes are replaced with those
+    /// returned by B2, which may differ from the previous authorization;
+    /// uploads started afterward, such as via
+    /// [upload_path](crate::file::upload_path), plan their parts with the new
+    /// [part sizes](Self::part_size_bounds). The HTTP client, [Clock], clock
+    /// skew, and [LogRedaction] settings are kept.
+    ///
+    /// If the request fails, the `Authorization` is unchanged.
+    ///
+    /// See [authorize_account] for further information.
+    pub async fn reauthorize<E>(&mut self, key_id: &str, key: &str)
+    -> Result<(), Error<E>>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let proto = request_authorization(&mut self.client, key_id, key)
+            .await?;
+
+        self.account_id = proto.account_id;
+        self.authorization_token = proto.authorization_token;
+        self.allowed = proto.allowed;
+        self.api_url = proto.api_url;
+        self.download_url = proto.download_url;
+        self.recommended_part_size = proto.recommended_part_size;
+        self.absolute_minimum_part_size = proto.absolute_minimum_part_size;
+        self.s3_api_url = proto.s3_api_url;
+        self.key_expiration = proto.application_key_expiration_timestamp;
+        self.obtained_at = self.clock.now();
+
+        Ok(())
+    }
+
+    /// The current time according to the [Clock].
+    pub(crate) fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.clock.now()
+    }
+
+    /// The [Clock] used for expiration checks.
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> { self.clock.clone() }
+
+    /// Allow for the local clock to be off by up to `skew` in expiration
+    /// checks.
+    ///
+    /// The authorization token is treated as expiring `skew` earlier than its
+    /// [expiration](Self::expiration), so that a slightly wrong clock does not
+    /// lead to using a token that B2 has already expired. The default is five
+    /// minutes; a negative `skew` is treated as zero.
+    ///
+    /// The allowance only covers small errors; for correct expiration checks,
+    /// keep the local clock synchronized (e.g., via NTP).
+    pub fn with_clock_skew(mut self, skew: chrono::Duration) -> Self {
+        self.clock_skew = std::cmp::max(skew, chrono::Duration::zero());
+        self
+    }
+
+    /// The allowed clock skew for expiration checks.
+    ///
+    /// See [with_clock_skew](Self::with_clock_skew).
+    pub fn clock_skew(&self) -> chrono::Duration { self.clock_skew }
+
+    /// Identify your application in the User-Agent header of all further
+    /// requests.
+    ///
+    /// Backblaze recommends that clients identify themselves, as in
+    /// `my-app/1.0`. The value is appended to the client's current User-Agent
+    /// string, which for the built-in clients is
+    /// `rust-b2-client/<version>; <client>`; if the client does not report
+    /// its User-Agent string, `app` replaces it. Calling this again replaces
+    /// the application set by the previous call.
+    ///
+    /// Returns [ValidationError::MissingData] if `app` is empty.
+    pub fn with_user_agent(mut self, app: &str)
+    -> Result<Self, ValidationError> {
+        if app.is_empty() {
+            return Err(ValidationError::MissingData(
+                "The application's User-Agent is required".into()
+            ));
         }
+
+        let current = self.client.user_agent_string();
+
+        let base = match (current, self.user_agent_app.as_deref()) {
+            (Some(current), Some(prev)) if current == prev => None,
+            (Some(current), Some(prev)) => Some(
+                current.strip_suffix(prev)
+                    .and_then(|base| base.strip_suffix("; "))
+                    .unwrap_or(current)
+            ),
+            (current, _) => current,
+        };
+
+        let user_agent = match base {
+            Some(base) => format!("{}; {}", base, app),
+            None => app.to_owned(),
+        };
+
+        self.client.user_agent(user_agent)?;
+        self.user_agent_app = Some(app.to_owned());
+        Ok(self)
     }
 
     /// The authorization token used for Backblaze requests.
     pub fn authorization_token(&self) -> &str { &self.authorization_token }
 
+    /// Overwrite the authorization token in memory and drop the
+    /// `Authorization`.
+    ///
+    /// B2 cannot revoke an authorization token, so the token remains valid
+    /// until it expires; this only removes it from memory once you no longer
+    /// need it. Clones of this `Authorization` and the upload and download
+    /// authorizations obtained with it hold their own copies of their tokens,
+    /// which are not affected.
+    ///
+    /// This is only available with the `zeroize` feature.
+    #[cfg(feature = "zeroize")]
+    pub fn invalidate(mut self) {
+        use zeroize::Zeroize as _;
+
+        self.authorization_token.zeroize();
+    }
+
+    /// The value of the `Authorization` header for a request to a download
+    /// endpoint.
+    ///
+    /// If `dl` is provided, its token is used; otherwise the account's
+    /// authorization token is used. This allows building raw download
+    /// requests that the download functions do not cover.
+    pub fn download_header_value(
+        &self,
+        dl: Option<&crate::file::DownloadAuthorization<C>>
+    ) -> String {
+        // Only b2_authorize_account uses Basic authentication; download
+        // endpoints take the token as-is.
+        match dl {
+            Some(dl) => dl.authorization_token().to_owned(),
+            None => self.authorization_token.clone(),
+        }
+    }
+
     /// The ID for the account.
     pub fn account_id(&self) -> &str { &self.account_id }
     /// The capabilities granted to this auth token.
     pub fn capabilities(&self) -> &Capabilities { &self.allowed }
+
+    /// The base URL for API calls, as returned by B2 when the account was
+    /// authorized.
+    ///
+    /// The URL identifies the cluster serving the account, which is useful
+    /// when diagnosing region or endpoint problems.
+    pub fn api_url_base(&self) -> &str { &self.api_url }
+
+    /// The base URL for downloading files, as returned by B2 when the account
+    /// was authorized.
+    pub fn download_url_base(&self) -> &str { &self.download_url }
+
+    /// The base URL of the account's S3-compatible API endpoint.
+    ///
+    /// Returns `None` if B2 did not provide one, as for some older accounts.
+    pub fn s3_api_url(&self) -> Option<&str> { self.s3_api_url.as_deref() }
+
+    /// The region of the account's S3-compatible API endpoint, e.g.
+    /// `us-west-004`.
+    ///
+    /// The region is parsed from the endpoint's host name, which has the form
+    /// `s3.<region>.backblazeb2.com`, so that S3 SDKs can be configured for B2
+    /// without hard-coding the region. Returns `None` if the host name does not
+    /// have that form.
+    pub fn s3_region(&self) -> Option<String> {
+        self.s3_api_url.as_deref().and_then(s3_region_from_url)
+    }
+
+    /// The ID of the bucket to use for a request.
+    ///
+    /// Returns `bucket_id` if provided; otherwise, if the authorization is
+    /// restricted to a single bucket, returns that bucket's ID. Every function
+    /// that takes an optional bucket ID uses this: if `bucket_id` is `None`,
+    /// the bucket to which the [Authorization] is restricted is used, so keys
+    /// restricted to one bucket do not need to pass it.
+    ///
+    /// Returns [ValidationError::MissingData] if neither is available.
+    pub fn bucket_id_or_restricted(&self, bucket_id: Option<&str>)
+    -> Result<String, ValidationError> {
+        bucket_id.map(String::from)
+            .or_else(|| self.allowed.bucket_id().cloned())
+            .ok_or_else(|| ValidationError::MissingData(concat!(
+                "A bucket ID is required unless the authorization is ",
+                "restricted to a bucket"
+            ).into()))
+    }
+
+    /// The latest time at which the authorization token will expire.
+    ///
+    /// Authorization tokens are valid for no more than 24 hours from when they
+    /// are obtained.
+    pub fn expiration(&self) -> chrono::DateTime<chrono::Utc> {
+        self.obtained_at + chrono::Duration::hours(24)
+    }
+
+    /// When the application key used for this authorization expires.
+    ///
+    /// Once the key expires, the account cannot be
+    /// [reauthorized](Self::reauthorize) with it. Returns `None` if the key
+    /// does not expire or if B2 did not say when it does.
+    pub fn key_expiration(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.key_expiration
+    }
+
+    /// True if the [expiration](Self::expiration) has passed according to the
+    /// local clock, allowing for the [clock skew](Self::clock_skew).
+    ///
+    /// B2 may reject the token earlier, so a `false` result does not guarantee
+    /// that the token is still accepted.
+    pub fn is_probably_expired(&self) -> bool {
+        self.expires_within(chrono::Duration::zero())
+    }
+
+    /// True if the token will probably expire within `margin` according to
+    /// the local clock.
+    ///
+    /// The [clock skew](Self::clock_skew) is added to `margin`. Use this to
+    /// re-authorize before starting a long operation, such as a large file
+    /// upload, that may outlast the token.
+    pub fn expires_within(&self, margin: chrono::Duration) -> bool {
+        self.now() + margin + self.clock_skew >= self.expiration()
+    }
+
+    /// Describe what the authorization token can do.
+    ///
+    /// The summary lists the account ID, capabilities, bucket and file name
+    /// prefix restrictions, and [expiration](Self::expiration), one per line;
+    /// it is intended for display to users, e.g.:
+    ///
+    /// ```text
+    /// Account: 1a2b3c4d5e6f
+    /// Capabilities: listBuckets, listFiles, readFiles
+    /// Bucket: my-bucket (4a5b6c7d8e9f)
+    /// File name prefix: photos/
+    /// Expires: 2022-01-13 20:46:52 UTC
+    /// ```
+    pub fn capabilities_summary(&self) -> String {
+        let caps = self.allowed.capabilities().iter()
+            .filter_map(|cap| serde_json::to_value(cap).ok())
+            .filter_map(|cap| cap.as_str().map(String::from))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let allowed = &self.allowed;
+
+        let bucket = match (allowed.bucket_name(), allowed.bucket_id()) {
+            (Some(name), Some(id)) => format!("{} ({})", name, id),
+            (None, Some(id)) => id.to_owned(),
+            (Some(name), None) => name.to_owned(),
+            (None, None) => "all buckets".into(),
+        };
+
+        let prefix = self.allowed.name_prefix()
+            .map(|p| p.as_str())
+            .unwrap_or("none");
+
+        format!(
+            concat!(
+                "Account: {}\n",
+                "Capabilities: {}\n",
+                "Bucket: {}\n",
+                "File name prefix: {}\n",
+                "Expires: {}",
+            ),
+            self.account_id,
+            caps,
+            bucket,
+            prefix,
+            self.expiration().format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
     /// The recommended size in bytes for each part of a large file.
     pub fn recommended_part_size(&self) -> u64 { self.recommended_part_size }
     /// The smallest possible size in bytes of a part of a large file, except
     /// the final part.
     pub fn minimum_part_size(&self) -> u64 { self.absolute_minimum_part_size }
 
+    /// The minimum and recommended sizes in bytes for each part of a large
+    /// file, except the final part.
+    ///
+    /// The recommended size is never less than the minimum.
+    pub fn part_size_bounds(&self) -> (u64, u64) {
+        let minimum = self.absolute_minimum_part_size;
+        (minimum, self.recommended_part_size.max(minimum))
+    }
+
     pub fn has_capability(&self, cap: Capability) -> bool {
         self.allowed.has_capability(cap)
     }
@@ -97,7 +526,7 @@ impl<C> Authorization<C>
     ///
     /// This URL is used for all API calls except downloading files.
     pub(crate) fn api_url<S: AsRef<str>>(&self, endpoint: S) -> String {
-        format!("{}/b2api/v2/{}", self.api_url, endpoint.as_ref())
+        endpoint_url(&self.api_url, endpoint.as_ref())
     }
 
     /// Return the API url for GET requests to the specified service download
@@ -109,10 +538,63 @@ impl<C> Authorization<C>
     /// Return the API url for POST requests to the specified service download
     /// endpoint.
     pub(crate) fn download_url<S: AsRef<str>>(&self, endpoint: S) -> String {
-        format!("{}/b2api/v2/{}", self.download_url, endpoint.as_ref())
+        endpoint_url(&self.download_url, endpoint.as_ref())
+    }
+}
+
+/// Build the URL of a B2 API endpoint on the server at `base`.
+///
+/// The URL is joined via [url::Url::join], so any combination of slashes at
+/// the end of `base` and the start of `endpoint` produces a single separator.
+/// If `base` is not a valid URL, the strings are concatenated so that the
+/// HTTP client reports the bad URL.
+pub(crate) fn endpoint_url(base: &str, endpoint: &str) -> String {
+    let endpoint = endpoint.trim_start_matches('/');
+
+    let url = url::Url::parse(base).and_then(|mut url| {
+        if ! url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+
+        url.join("b2api/v2/")?.join(endpoint)
+    });
+
+    match url {
+        Ok(url) => url.into(),
+        Err(_) => format!("{}/b2api/v2/{}", base, endpoint),
+    }
+}
+
+/// Parse the region from an S3 API URL of the form
+/// `https://s3.<region>.backblazeb2.com`.
+fn s3_region_from_url(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+
+    let region = url.host_str()?
+        .strip_prefix("s3.")?
+        .strip_suffix(".backblazeb2.com")?;
+
+    if region.is_empty() || region.contains('.') {
+        None
+    } else {
+        Some(region.to_owned())
     }
 }
 
+/// Build the URL to download a file by name from the server at `base`.
+///
+/// `file_name` must already be percent-encoded. Unlike [endpoint_url], this
+/// does not use [url::Url::join], which would resolve `.` and `..` segments
+/// that are legitimate parts of B2 file names.
+pub(crate) fn file_url(base: &str, bucket_name: &str, file_name: &str)
+-> String {
+    format!("{}/file/{}/{}", base.trim_end_matches('/'), bucket_name, file_name)
+}
+
+/// The default clock skew allowance; see [Authorization::with_clock_skew].
+fn default_clock_skew() -> chrono::Duration { chrono::Duration::minutes(5) }
+
 /// The authorization information received from B2
 ///
 /// The public [Authorization] object contains everything here, plus private
@@ -127,11 +609,16 @@ struct ProtoAuthorization {
     download_url: String,
     recommended_part_size: u64,
     absolute_minimum_part_size: u64,
-    _s3_api_url: String,
+    // Not provided for some older accounts.
+    #[serde(default)]
+    s3_api_url: Option<String>,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
+    application_key_expiration_timestamp: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl ProtoAuthorization {
-    fn create_authorization<C: HttpClient>(self, c: C) -> Authorization<C> {
+    fn create_authorization<C: HttpClient>(self, c: C, clock: Arc<dyn Clock>)
+    -> Authorization<C> {
         Authorization {
             client: c,
             account_id: self.account_id,
@@ -141,7 +628,13 @@ impl ProtoAuthorization {
             download_url: self.download_url,
             recommended_part_size: self.recommended_part_size,
             absolute_minimum_part_size: self.absolute_minimum_part_size,
-            _s3_api_url: self._s3_api_url,
+            s3_api_url: self.s3_api_url,
+            key_expiration: self.application_key_expiration_timestamp,
+            obtained_at: clock.now(),
+            log_redaction: LogRedaction::default(),
+            clock,
+            clock_skew: default_clock_skew(),
+            user_agent_app: None,
         }
     }
 }
@@ -192,10 +685,74 @@ impl Capabilities {
     pub fn has_capability(&self, cap: Capability) -> bool {
         self.capabilities.iter().any(|&c| c == cap)
     }
+
+    /// The list of capabilities granted, sorted and without duplicates.
+    ///
+    /// B2 does not guarantee the order in which it lists capabilities; use
+    /// this to compare or persist capability sets regardless of order.
+    pub fn sorted_capabilities(&self) -> Vec<Capability> {
+        let mut capabilities = self.capabilities.clone();
+        capabilities.sort_unstable();
+        capabilities.dedup();
+        capabilities
+    }
+
+    /// Compare these capabilities with `other`.
+    ///
+    /// Capabilities present in `other` but not in `self` are considered added;
+    /// those in `self` but not in `other` are removed. The bucket and filename
+    /// prefix restrictions are compared as well; the bucket name is ignored
+    /// since it follows from the bucket ID.
+    pub fn diff(&self, other: &Capabilities) -> CapabilityDiff {
+        let changed = |old: &Option<String>, new: &Option<String>|
+            if old != new {
+                Some((old.clone(), new.clone()))
+            } else {
+                None
+            };
+
+        CapabilityDiff {
+            added: other.capabilities.iter()
+                .filter(|&&c| ! self.has_capability(c))
+                .copied()
+                .collect(),
+            removed: self.capabilities.iter()
+                .filter(|&&c| ! other.has_capability(c))
+                .copied()
+                .collect(),
+            bucket_id: changed(&self.bucket_id, &other.bucket_id),
+            name_prefix: changed(&self.name_prefix, &other.name_prefix),
+        }
+    }
+}
+
+/// The differences between two [Capabilities], as returned by
+/// [Capabilities::diff].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CapabilityDiff {
+    /// Capabilities granted by the other set but not by this one.
+    pub added: Vec<Capability>,
+    /// Capabilities granted by this set but not by the other one.
+    pub removed: Vec<Capability>,
+    /// The (old, new) bucket ID restriction, if it changed.
+    pub bucket_id: Option<(Option<String>, Option<String>)>,
+    /// The (old, new) filename prefix restriction, if it changed.
+    pub name_prefix: Option<(Option<String>, Option<String>)>,
+}
+
+impl CapabilityDiff {
+    /// Returns `true` if the two sets of capabilities are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+            && self.bucket_id.is_none() && self.name_prefix.is_none()
+    }
 }
 
 /// A capability potentially granted by an authorization token.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+///
+/// Capabilities are ordered by their declaration order below.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Capability {
     ListKeys,
@@ -253,10 +810,36 @@ pub enum Capability {
 /// let removed_key = delete_key_by_id(&mut auth, "OTHER KEY ID").await?;
 /// # Ok(()) }
 /// ```
-pub async fn authorize_account<C, E>(mut client: C, key_id: &str, key: &str)
+pub async fn authorize_account<C, E>(client: C, key_id: &str, key: &str)
 -> Result<Authorization<C>, Error<E>>
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
+{
+    authorize_account_with_clock(client, key_id, key, clock::SystemClock).await
+}
+
+/// Log onto the B2 API, using the provided [Clock] for the time at which the
+/// authorization was obtained and for expiration checks.
+///
+/// See [authorize_account] and [Authorization::with_clock] for further
+/// information.
+pub async fn authorize_account_with_clock<C, E>(
+    mut client: C,
+    key_id: &str,
+    key: &str,
+    clock: impl Clock + 'static,
+) -> Result<Authorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let auth = request_authorization(&mut client, key_id, key).await?;
+    Ok(auth.create_authorization(client, Arc::new(clock)))
+}
+
+async fn request_authorization<C, E>(client: &mut C, key_id: &str, key: &str)
+-> Result<ProtoAuthorization, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
 {
     let id_and_key = format!("{}:{}", key_id, key);
     let id_and_key = base64::encode(id_and_key.as_bytes());
@@ -265,14 +848,231 @@ pub async fn authorize_account<C, E>(mut client: C, key_id: &str, key: &str)
     auth.push_str(&id_and_key);
 
     let req = client.get(
-        format!("{}b2_authorize_account", B2_AUTH_URL)
+        endpoint_url(B2_AUTH_URL, "b2_authorize_account")
     ).expect("Invalid URL")
         .with_header("Authorization", &auth).unwrap();
 
-    let res = req.send().await?;
+    let res = req.send_keep_headers().await?;
+
+    let auth: B2Result<ProtoAuthorization> = B2Result::from_response(&res)?;
+    auth.into()
+}
+
+/// Log onto the B2 API, retrying transient failures according to `policy`.
+///
+/// Failures to reach the server, timeouts, and B2 errors that
+/// [are retriable](crate::error::B2Error::is_retriable), such as a
+/// `503 Service Unavailable`, are retried after the delays calculated by the
+/// [RetryPolicy]. Permanent failures such as `bad_auth_token` or
+/// `unauthorized` are returned immediately, as is the last error once
+/// `policy.max_attempts` requests have failed. If B2 sends a `Retry-After`
+/// header, its [delay](crate::error::B2Error::retry_after) is used instead of
+/// the one calculated by the policy, up to `policy.max_delay`. Each retry is
+/// reported to the client via [HttpClient::on_retry].
+///
+/// `sleep` waits between attempts; see
+/// [the retry module](crate::retry#sleeping).
+///
+/// See [authorize_account] for further information.
+pub async fn authorize_account_with_retry<C, E, S, F>(
+    client: C,
+    key_id: &str,
+    key: &str,
+    policy: &RetryPolicy,
+    sleep: S,
+) -> Result<Authorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          S: FnMut(std::time::Duration) -> F,
+          F: std::future::Future<Output = ()>,
+{
+    authorize_account_with_retry_with_clock(
+        client, key_id, key, policy, clock::SystemClock, sleep
+    ).await
+}
+
+/// Log onto the B2 API, retrying transient failures according to `policy` and
+/// using the provided [Clock].
+///
+/// `clock` seeds the policy's jitter, measures `Retry-After` dates, and is
+/// used by the returned [Authorization]; see [authorize_account_with_clock].
+///
+/// See [authorize_account_with_retry] for further information.
+pub async fn authorize_account_with_retry_with_clock<C, E, S, F>(
+    mut client: C,
+    key_id: &str,
+    key: &str,
+    policy: &RetryPolicy,
+    clock: impl Clock + 'static,
+    mut sleep: S,
+) -> Result<Authorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          S: FnMut(std::time::Duration) -> F,
+          F: std::future::Future<Output = ()>,
+{
+    let is_transient = |e: &Error<E>| match e {
+        Error::Transport(_) | Error::Timeout => true,
+        Error::B2(e) => e.is_retriable()
+            && e.code() != crate::error::ErrorCode::ExpiredAuthToken,
+        _ => false,
+    };
+
+    let clock: Arc<dyn Clock> = Arc::new(clock);
+
+    let seed = clock.now().timestamp_millis() as u64;
+    let mut delays = policy.delays(seed);
+
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        match request_authorization(&mut client, key_id, key).await {
+            Ok(auth) => return Ok(auth.create_authorization(client, clock)),
+            Err(e) if is_transient(&e) => match delays.next() {
+                Some(delay) => {
+                    let delay = match &e {
+                        Error::B2(e) => e.retry_after_with_clock(clock.as_ref())
+                            .map(|d| d.min(policy.max_delay))
+                            .unwrap_or(delay),
+                        _ => delay,
+                    };
+
+                    client.on_retry(&RetryAttempt::new(
+                        "b2_authorize_account", attempt, &e, delay
+                    ));
+
+                    sleep(delay).await
+                },
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Log onto the B2 API using credentials from the environment.
+///
+/// The application key ID and application key are read from the
+/// `B2_APPLICATION_KEY_ID` and `B2_APPLICATION_KEY` environment variables
+/// respectively, matching the variables used by the official B2 command-line
+/// tool; they are then passed to [authorize_account].
+///
+/// If either variable is unset or is not valid Unicode, a
+/// [ValidationError::MissingData] error naming the variable is returned.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "with_surf")]
+/// # use b2_client::{
+/// #     client::{HttpClient, SurfClient},
+/// #     account::authorize_from_env,
+/// # };
+/// # #[cfg(feature = "with_surf")]
+/// # async fn f() -> anyhow::Result<()> {
+/// let mut auth = authorize_from_env(SurfClient::default()).await?;
+/// # Ok(()) }
+/// ```
+pub async fn authorize_from_env<C, E>(client: C)
+-> Result<Authorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let (key_id, key) = credentials_from_env(|name| std::env::var(name))?;
+
+    authorize_account(client, &key_id, &key).await
+}
+
+/// Read the application key ID and key for [authorize_from_env] via `var`,
+/// which looks up an environment variable.
+fn credentials_from_env<F>(var: F) -> Result<(String, String), ValidationError>
+    where F: Fn(&str) -> Result<String, std::env::VarError>,
+{
+    let read_var = |name: &str| var(name).map_err(|e| match e {
+        std::env::VarError::NotPresent => ValidationError::MissingData(
+            format!("The {} environment variable is not set", name)
+        ),
+        std::env::VarError::NotUnicode(_) => ValidationError::MissingData(
+            format!("The {} environment variable is not valid Unicode", name)
+        ),
+    });
+
+    Ok((read_var("B2_APPLICATION_KEY_ID")?, read_var("B2_APPLICATION_KEY")?))
+}
+
+/// An application key ID and its secret, used to log onto the B2 API via
+/// [authorize_account_with].
+///
+/// The pair returned by [create_key] converts into `Credentials`, so a newly
+/// created key can be used right away:
+///
+/// ```no_run
+/// # #[cfg(feature = "with_surf")]
+/// # use b2_client::{
+/// #     client::SurfClient,
+/// #     account::{
+/// #         authorize_account_with, create_key, Authorization, Capability,
+/// #         CreateKey, Credentials,
+/// #     },
+/// # };
+/// # #[cfg(feature = "with_surf")]
+/// # async fn f(mut auth: Authorization<SurfClient>) -> anyhow::Result<()> {
+/// let req = CreateKey::builder()
+///     .name("my-key")?
+///     .capabilities([Capability::ListFiles])?
+///     .build()?;
+///
+/// let creds = Credentials::from(create_key(&mut auth, req).await?);
+/// let new_auth = authorize_account_with(SurfClient::default(), &creds).await?;
+/// # Ok(()) }
+/// ```
+///
+/// The secret is omitted from the [Debug](fmt::Debug) output.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Credentials {
+    /// The application key ID.
+    pub key_id: String,
+    /// The application key; i.e., the secret.
+    pub secret: String,
+}
+
+impl Credentials {
+    /// Pair an application key ID with its secret.
+    pub fn new(key_id: impl Into<String>, secret: impl Into<String>) -> Self {
+        Self {
+            key_id: key_id.into(),
+            secret: secret.into(),
+        }
+    }
+}
+
+impl From<(String, Key)> for Credentials {
+    /// Convert the secret and key returned by [create_key].
+    fn from((secret, key): (String, Key)) -> Self {
+        Self { key_id: key.application_key_id, secret }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("key_id", &self.key_id)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
 
-    let auth: B2Result<ProtoAuthorization> = serde_json::from_slice(&res)?;
-    auth.map(|v| v.create_authorization(client)).into()
+/// Log onto the B2 API with the given [Credentials].
+///
+/// See [authorize_account] for further information.
+pub async fn authorize_account_with<C, E>(client: C, creds: &Credentials)
+-> Result<Authorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    authorize_account(client, &creds.key_id, &creds.secret).await
 }
 
 /// A request to create a B2 API key with certain capabilities.
@@ -286,12 +1086,19 @@ pub struct CreateKey<'a> {
     account_id: Option<&'a str>,
     capabilities: Vec<Capability>,
     key_name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::types::serialize_optional_seconds"
+    )]
     valid_duration_in_seconds: Option<Duration>,
     #[serde(skip_serializing_if = "Option::is_none")]
     bucket_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     name_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<Vec<String>>,
+    #[serde(skip)]
+    check_capabilities: bool,
 }
 
 impl<'a> CreateKey<'a> {
@@ -314,6 +1121,8 @@ pub struct CreateKeyBuilder {
     valid_duration: Option<Duration>,
     bucket_id: Option<String>,
     name_prefix: Option<String>,
+    options: Option<Vec<String>>,
+    skip_capability_check: bool,
 }
 
 impl CreateKeyBuilder {
@@ -331,17 +1140,16 @@ impl CreateKeyBuilder {
                 "A key name must be present".into()
             ));
         } else if name.len() > 100 {
-            return Err(ValidationError::BadFormat(
-                "Name must be no more than 100 characters.".into()
-            ));
+            return Err(ValidationError::NameTooLong {
+                length: name.len(),
+                max: 100,
+            });
         }
 
         let invalid_char = |c: &char| !(c.is_alphanumeric() || *c == '-');
 
         if let Some(ch) = name.chars().find(invalid_char) {
-            return Err(
-                ValidationError::BadFormat(format!("Invalid character: {}", ch))
-            );
+            return Err(ValidationError::InvalidCharacter(ch));
         }
 
         self.name = Some(name);
@@ -356,9 +1164,7 @@ impl CreateKeyBuilder {
         let caps = caps.into();
 
         if caps.is_empty() {
-            return Err(ValidationError::MissingData(
-                "A key must have at least one capability.".into()
-            ));
+            return Err(ValidationError::MissingCapabilities);
         }
 
         self.capabilities = Some(caps);
@@ -368,8 +1174,10 @@ impl CreateKeyBuilder {
     /// Set an expiration duration for the key.
     ///
     /// If provided, the key must be positive and no more than 1,000 days.
-    pub fn expires_after(mut self, dur: chrono::Duration)
+    pub fn expires_after(mut self, dur: impl IntoDuration)
     -> Result<Self, ValidationError> {
+        let dur = dur.into_duration()?;
+
         if dur >= chrono::Duration::days(1000) {
             return Err(ValidationError::OutOfBounds(
                 "Expiration must be less than 1000 days".into()
@@ -385,20 +1193,57 @@ impl CreateKeyBuilder {
     }
 
     /// Limit the key's access to the specified bucket.
-    pub fn limit_to_bucket<S: Into<String>>(mut self, id: S)
-    -> Result<Self, ValidationError> {
+    pub fn limit_to_bucket<S: Into<String>>(mut self, id: S) -> Self {
         self.bucket_id = Some(id.into());
-        Ok(self)
+        self
+    }
+
+    /// Create a key that can list and download the files in a bucket, and
+    /// that expires after `duration`.
+    ///
+    /// This sets the key's [name](Self::name), grants
+    /// [Capability::ListFiles] and [Capability::ReadFiles],
+    /// [limits](Self::limit_to_bucket) the key to `bucket_id`, and sets its
+    /// [expiration](Self::expires_after), returning the same errors as those
+    /// methods.
+    pub fn read_only_bucket<S: Into<String>>(
+        self,
+        name: S,
+        bucket_id: S,
+        duration: impl IntoDuration,
+    ) -> Result<Self, ValidationError> {
+        self.name(name)?
+            .capabilities([Capability::ListFiles, Capability::ReadFiles])?
+            .limit_to_bucket(bucket_id)
+            .expires_after(duration)
     }
 
     /// Limit access to files to those that begin with the specified prefix.
-    pub fn name_prefix<S: Into<String>>(mut self, prefix: S)
-    -> Result<Self, ValidationError> {
-        let prefix = prefix.into();
-        // TODO: Validate prefix
+    pub fn name_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        // TODO: Validate prefix; this will need to return a Result.
+        self.name_prefix = Some(prefix.into());
+        self
+    }
 
-        self.name_prefix = Some(prefix);
-        Ok(self)
+    /// Set the key's options.
+    ///
+    /// B2 does not currently document any options that can be requested; the
+    /// values are sent as given. See [Key::options].
+    pub fn with_options(mut self, options: Vec<String>) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Don't check that the [Authorization] can delegate the key's
+    /// capabilities before sending the request to B2.
+    ///
+    /// By default, [create_key] returns a [ValidationError] if the new key
+    /// would have capabilities that the [Authorization] itself lacks, since B2
+    /// would reject the request. Use this if B2 allows delegating a capability
+    /// that b2-client doesn't know about.
+    pub fn skip_capability_check(mut self) -> Self {
+        self.skip_capability_check = true;
+        self
     }
 
     /// Create a new [CreateKey].
@@ -437,16 +1282,13 @@ impl CreateKeyBuilder {
                     | Capability::BypassGovernance
                     | Capability::ReadBucketReplications
                     | Capability::WriteBucketReplications => {},
-                    cap => return Err(ValidationError::Incompatible(format!(
-                        "Invalid capability when bucket_id is set: {:?}",
-                        cap
-                    ))),
+                    cap => return Err(
+                        ValidationError::CapabilityNotAllowedForBucket(*cap)
+                    ),
                 }
             }
         } else if self.name_prefix.is_some() {
-            return Err(ValidationError::MissingData(
-                "bucket_id must be set when name_prefix is given".into()
-            ));
+            return Err(ValidationError::PrefixWithoutBucket);
         }
 
         Ok(CreateKey {
@@ -456,6 +1298,8 @@ impl CreateKeyBuilder {
             valid_duration_in_seconds: self.valid_duration,
             bucket_id: self.bucket_id,
             name_prefix: self.name_prefix,
+            options: self.options,
+            check_capabilities: ! self.skip_capability_check,
         })
     }
 }
@@ -468,10 +1312,12 @@ pub struct Key {
     application_key_id: String,
     capabilities: Vec<Capability>,
     account_id: String,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
     expiration_timestamp: Option<DateTime<Utc>>,
     bucket_id: Option<String>,
     name_prefix: Option<String>,
-    // options: Option<Vec<String>>, // Currently unused by B2.
+    #[serde(default)]
+    options: Option<Vec<String>>,
 }
 
 impl Key {
@@ -479,7 +1325,7 @@ impl Key {
     pub fn key_name(&self) -> &str { &self.key_name }
     /// The application key ID. This, combined with the secret returned when the
     /// key was created, allows you to create an [Authorization] token to make
-    /// API calls.
+    /// API calls; see [Credentials].
     pub fn key_id(&self) -> &str { &self.application_key_id }
     /// The list of capabilities granted by this key.
     pub fn capabilities(&self) -> &[Capability] { &self.capabilities }
@@ -490,12 +1336,36 @@ impl Key {
     pub fn bucket_id(&self) -> Option<&String> { self.bucket_id.as_ref() }
     /// If set, access is limited to files whose names begin with this prefix.
     pub fn name_prefix(&self) -> Option<&String> { self.name_prefix.as_ref() }
+    /// The options set on this key, such as `s3` for keys usable with the
+    /// S3-compatible API.
+    ///
+    /// The values are returned as B2 reports them; the list is empty if B2
+    /// reported no options.
+    pub fn options(&self) -> &[String] {
+        self.options.as_deref().unwrap_or_default()
+    }
 
     /// If present, the expiration date and time of this key.
     pub fn expiration(&self) -> Option<DateTime<Utc>> {
         self.expiration_timestamp
     }
 
+    /// The time remaining until this key expires.
+    ///
+    /// The duration is negative if the key has already expired. Returns `None`
+    /// if the key does not expire.
+    pub fn expires_in(&self) -> Option<chrono::Duration> {
+        self.expires_in_with_clock(&clock::SystemClock)
+    }
+
+    /// The time remaining until this key expires, according to `clock`.
+    ///
+    /// See [expires_in](Self::expires_in).
+    pub fn expires_in_with_clock(&self, clock: &dyn Clock)
+    -> Option<chrono::Duration> {
+        self.expiration_timestamp.map(|expiration| expiration - clock.now())
+    }
+
     /// Check if the provided capability is granted by this key.
     pub fn has_capability(&self, cap: Capability) -> bool {
         self.capabilities.iter().any(|&c| c == cap)
@@ -514,10 +1384,12 @@ struct NewlyCreatedKey {
     application_key_id: String,
     capabilities: Vec<Capability>,
     account_id: String,
+    #[serde(default, with = "chrono::serde::ts_milliseconds_option")]
     expiration_timestamp: Option<DateTime<Utc>>,
     bucket_id: Option<String>,
     name_prefix: Option<String>,
-    // options: Option<Vec<String>>, Currently unused by B2.
+    #[serde(default)]
+    options: Option<Vec<String>>,
 }
 
 impl NewlyCreatedKey {
@@ -532,6 +1404,7 @@ impl NewlyCreatedKey {
             expiration_timestamp: self.expiration_timestamp,
             bucket_id: self.bucket_id,
             name_prefix: self.name_prefix,
+            options: self.options,
         };
 
         (secret, key)
@@ -544,6 +1417,12 @@ impl NewlyCreatedKey {
 /// secret is never obtainable except by this function, so must be stored in a
 /// secure location.
 ///
+/// B2 only allows creating keys with a subset of the [Authorization]'s own
+/// capabilities. If the new key requests any others, returns a
+/// [ValidationError::Incompatible] listing them without making a request,
+/// unless the check is disabled via
+/// [CreateKeyBuilder::skip_capability_check].
+///
 /// See <https://www.backblaze.com/b2/docs/b2_create_key.html> for further
 /// information.
 ///
@@ -580,6 +1459,19 @@ pub async fn create_key<C, E>(
 {
     require_capability!(auth, Capability::WriteKeys);
 
+    if new_key_info.check_capabilities {
+        let missing: Vec<_> = new_key_info.capabilities.iter()
+            .filter(|&&cap| ! auth.has_capability(cap))
+            .collect();
+
+        if ! missing.is_empty() {
+            return Err(ValidationError::Incompatible(format!(
+                "The authorization cannot delegate capabilities: {:?}",
+                missing
+            )).into());
+        }
+    }
+
     let mut new_key_info = new_key_info;
     new_key_info.account_id = Some(&auth.account_id);
 
@@ -587,9 +1479,9 @@ pub async fn create_key<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(new_key_info)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let new_key: B2Result<NewlyCreatedKey> = serde_json::from_slice(&res)?;
+    let new_key: B2Result<NewlyCreatedKey> = B2Result::from_response(&res)?;
     new_key.map(|key| key.create_public_key()).into()
 }
 
@@ -675,9 +1567,9 @@ pub async fn delete_key_by_id<C, E, S: AsRef<str>>(
         .with_body_json(serde_json::json!(
             {"applicationKeyId": key_id.as_ref()}
         ))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let key: B2Result<Key> = serde_json::from_slice(&res)?;
+    let key: B2Result<Key> = B2Result::from_response(&res)?;
     key.into()
 }
 
@@ -746,10 +1638,9 @@ impl ListKeysBuilder {
     }
 
     /// Set the key ID at which to begin listing.
-    pub fn start_at_key(mut self, id: impl Into<String>)
-    -> Result<Self, ValidationError> {
+    pub fn start_at_key(mut self, id: impl Into<String>) -> Self {
         self.start_key_id = Some(id.into());
-        Ok(self)
+        self
     }
 
     /// Create a [ListKeys].
@@ -830,9 +1721,9 @@ pub async fn list_keys<'a, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(list_req.clone())?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let keys: B2Result<KeyList> = serde_json::from_slice(&res)?;
+    let keys: B2Result<KeyList> = B2Result::from_response(&res)?;
 
     match keys {
         B2Result::Ok(keys) => {
@@ -853,6 +1744,82 @@ pub async fn list_keys<'a, C, E>(
     }
 }
 
+/// List the application keys whose names begin with `prefix`.
+///
+/// B2 cannot filter keys by name, so this requests every key associated with
+/// the account, 1,000 keys at a time, and keeps only those that match; it
+/// costs as many requests (and Class C transactions) as listing every key via
+/// [list_keys]. Only the matching keys are kept in memory.
+///
+/// The `Authorization` must have [Capability::ListKeys].
+pub async fn list_keys_by_name_prefix<C, E>(
+    auth: &mut Authorization<C>,
+    prefix: &str,
+) -> Result<Vec<Key>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let mut keys = vec![];
+    let mut start_key_id = None;
+
+    loop {
+        let req = ListKeys {
+            account_id: None,
+            max_key_count: 1000,
+            start_application_key_id: start_key_id.take(),
+        };
+
+        let (page, next_req) = list_keys(auth, req).await?;
+
+        keys.extend(
+            page.into_iter().filter(|k| k.key_name.starts_with(prefix))
+        );
+
+        match next_req.and_then(|r| r.start_application_key_id) {
+            Some(id) => start_key_id = Some(id),
+            None => break,
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Delete every key whose name starts with `prefix`.
+///
+/// Returns the keys that were deleted. If `dry_run` is `true`, the matching
+/// keys are listed and returned but not deleted.
+///
+/// The keys are listed via [list_keys_by_name_prefix], so the `Authorization`
+/// must have [Capability::ListKeys], and unless `dry_run` is set,
+/// [Capability::DeleteKeys]. If a deletion fails, the keys listed after it are
+/// not deleted.
+pub async fn delete_keys_by_name_prefix<C, E>(
+    auth: &mut Authorization<C>,
+    prefix: &str,
+    dry_run: bool,
+) -> Result<Vec<Key>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    if ! dry_run {
+        require_capability!(auth, Capability::DeleteKeys);
+    }
+
+    let keys = list_keys_by_name_prefix(auth, prefix).await?;
+
+    if dry_run {
+        return Ok(keys);
+    }
+
+    let mut deleted = Vec::with_capacity(keys.len());
+
+    for key in keys {
+        deleted.push(delete_key(auth, key).await?);
+    }
+
+    Ok(deleted)
+}
+
 
 // TODO: Find a good way to mock responses for any/all backends.
 #[cfg(feature = "with_surf")]
@@ -861,7 +1828,7 @@ mod tests {
     use super::*;
     use crate::{
         error::ErrorCode,
-        test_utils::{create_test_auth, create_test_client},
+        test_utils::{create_auth_with, create_test_auth, create_test_client},
     };
     use surf_vcr::VcrMode;
 
@@ -881,6 +1848,40 @@ mod tests {
         (id, key)
     }
 
+    #[test]
+    fn credentials_from_env_names_missing_variable() {
+        use std::env::VarError;
+
+        let var = |name: &str| match name {
+            "B2_APPLICATION_KEY" => Ok("B2_AUTH_KEY".to_owned()),
+            _ => Err(VarError::NotPresent),
+        };
+
+        match credentials_from_env(var).unwrap_err() {
+            ValidationError::MissingData(msg) =>
+                assert!(msg.contains("B2_APPLICATION_KEY_ID")),
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let var = |name: &str| match name {
+            "B2_APPLICATION_KEY_ID" => Ok("B2_KEY_ID".to_owned()),
+            _ => Err(VarError::NotUnicode("\u{fffd}".into())),
+        };
+
+        match credentials_from_env(var).unwrap_err() {
+            ValidationError::MissingData(msg) =>
+                assert!(msg.contains("B2_APPLICATION_KEY environment")),
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let var = |name: &str| Ok(name.to_lowercase());
+
+        assert_eq!(credentials_from_env(var).unwrap(), (
+            "b2_application_key_id".to_owned(),
+            "b2_application_key".to_owned(),
+        ));
+    }
+
     #[async_std::test]
     async fn test_authorize_account() -> Result<(), anyhow::Error> {
         let client = create_test_client(
@@ -897,6 +1898,123 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn authorize_account_with_credentials() -> Result<(), anyhow::Error> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let (id, key) = get_key();
+        let creds = Credentials::new(id, key);
+
+        assert!(! format!("{:?}", creds).contains(&creds.secret));
+
+        let auth = authorize_account_with(client, &creds).await?;
+        assert!(auth.allowed.capabilities.contains(&Capability::ListBuckets));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn authorization_expires_by_clock() -> Result<(), anyhow::Error> {
+        use crate::clock::FixedClock;
+        use chrono::TimeZone as _;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let (id, key) = get_key();
+        let start = Utc.timestamp_opt(1641038400, 0).unwrap();
+        let clock = FixedClock::new(start);
+
+        let auth = authorize_account_with_clock(
+            client, &id, &key, clock.clone()
+        ).await?;
+
+        assert_eq!(auth.expiration(), start + chrono::Duration::hours(24));
+        assert!(! auth.is_probably_expired());
+
+        clock.advance(chrono::Duration::hours(24));
+        assert!(auth.is_probably_expired());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn reauthorize_updates_part_sizes() -> Result<(), anyhow::Error> {
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new(|_, res| {
+                // Change the part sizes in every response after the first.
+                if res.call > 0 {
+                    res.body["recommendedPartSize"] = 50000000.into();
+                    res.body["absoluteMinimumPartSize"] = 6000000.into();
+                }
+            }))
+            .with_middleware(vcr);
+
+        let (id, key) = get_key();
+        let mut auth = authorize_account(client, &id, &key).await?;
+        assert_eq!(auth.part_size_bounds(), (5000000, 100000000));
+
+        auth.reauthorize(&id, &key).await?;
+        assert_eq!(auth.part_size_bounds(), (6000000, 50000000));
+        assert_eq!(auth.recommended_part_size(), 50000000);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn authorization_expiry_allows_clock_skew()
+    -> Result<(), anyhow::Error> {
+        use crate::clock::FixedClock;
+        use chrono::{Duration, TimeZone as _};
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let (id, key) = get_key();
+        let start = Utc.timestamp_opt(1641038400, 0).unwrap();
+        let clock = FixedClock::new(start);
+
+        let auth = authorize_account_with_clock(
+            client, &id, &key, clock.clone()
+        ).await?;
+
+        assert_eq!(auth.clock_skew(), Duration::minutes(5));
+
+        // The local clock runs three minutes behind B2's.
+        clock.advance(Duration::hours(24) - Duration::minutes(3));
+        assert!(auth.is_probably_expired());
+        assert!(! auth.clone().with_clock_skew(Duration::zero())
+            .is_probably_expired());
+
+        clock.advance(Duration::minutes(-3));
+        assert!(! auth.is_probably_expired());
+        assert!(! auth.expires_within(Duration::seconds(59)));
+        assert!(auth.expires_within(Duration::minutes(1)));
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn authorize_account_bad_key() -> Result<(), anyhow::Error> {
         let client = create_test_client(
@@ -918,6 +2036,188 @@ mod tests {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn authorize_account_retries_transient_errors()
+    -> Result<(), anyhow::Error> {
+        use std::time::Duration;
+        use chrono::TimeZone as _;
+        use crate::{
+            client::SurfClient,
+            retry::JitterMode,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        // Responds to the first request with a 503 error, sending the
+        // `Retry-After` value with it if one is set.
+        let unavailable = |retry_after: Option<&'static str>| {
+            RewriteResponses::new(move |_, res| {
+                if res.call > 0 {
+                    return;
+                }
+
+                res.error(503, "service_unavailable",
+                    "Service temporarily unavailable");
+
+                if let Some(retry_after) = retry_after {
+                    res.headers.push(("Retry-After", retry_after.into()));
+                }
+            })
+        };
+
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: JitterMode::None,
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(unavailable(None))
+            .with_middleware(vcr);
+
+        let (id, key) = get_key();
+        let mut sleeps = vec![];
+
+        let auth = authorize_account_with_retry(client, &id, &key, &policy,
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert!(auth.allowed.capabilities.contains(&Capability::ListBuckets));
+        assert_eq!(sleeps, [Duration::from_secs(1)]);
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(unavailable(Some("17")))
+            .with_middleware(vcr);
+
+        sleeps.clear();
+
+        authorize_account_with_retry(client, &id, &key, &policy,
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert_eq!(sleeps, [Duration::from_secs(17)]);
+
+        // A Retry-After longer than the policy allows is capped.
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(unavailable(Some("999999")))
+            .with_middleware(vcr);
+
+        sleeps.clear();
+
+        authorize_account_with_retry(client, &id, &key, &policy,
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert_eq!(sleeps, [policy.max_delay]);
+
+        // A Retry-After date is measured from the provided clock.
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(unavailable(Some("Tue, 01 Mar 2022 12:00:30 GMT")))
+            .with_middleware(vcr);
+
+        // Tue, 01 Mar 2022 12:00:00 GMT
+        let now = chrono::Utc.timestamp_opt(1646136000, 0).unwrap();
+
+        sleeps.clear();
+
+        let auth = authorize_account_with_retry_with_clock(
+            client, &id, &key, &policy, clock::FixedClock::new(now),
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert_eq!(sleeps, [Duration::from_secs(30)]);
+        assert_eq!(auth.now(), now);
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/bad_auth_account.yaml",
+            None, None
+        ).await?;
+
+        sleeps.clear();
+
+        let auth = authorize_account_with_retry(client, &id, "wrong-key",
+            &policy, |d| { sleeps.push(d); async {} }
+        ).await;
+
+        match auth.unwrap_err() {
+            Error::B2(e) => assert_eq!(e.code(), ErrorCode::BadAuthToken),
+            _ => panic!("Unexpected error type"),
+        }
+        assert!(sleeps.is_empty());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn authorize_account_retries_timeouts() -> Result<(), anyhow::Error> {
+        use std::time::Duration;
+        use crate::{
+            client::{SurfClient, TimeoutConfig, Timeouts},
+            retry::JitterMode,
+            test_utils::{create_test_vcr, Delay},
+        };
+
+        let timeouts = Timeouts {
+            connect: None,
+            idle: None,
+            total: Some(Duration::from_millis(50)),
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_timeouts(TimeoutConfig { api: timeouts, transfer: timeouts })
+            // The first request takes longer than the client allows.
+            .with_middleware(Delay::first(Duration::from_secs(5)))
+            .with_middleware(vcr);
+
+        let policy = RetryPolicy {
+            jitter: JitterMode::None,
+            ..Default::default()
+        };
+
+        let (id, key) = get_key();
+        let mut sleeps = vec![];
+
+        let auth = authorize_account_with_retry(client, &id, &key, &policy,
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert!(auth.allowed.capabilities.contains(&Capability::ListBuckets));
+        assert_eq!(sleeps, [policy.base_delay]);
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn authorize_account_bad_key_id() -> Result<(), anyhow::Error> {
         let client = create_test_client(
@@ -947,8 +2247,9 @@ mod tests {
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::WriteKeys])
-            .await;
+        let mut auth = create_test_auth(client, vec![
+            Capability::WriteKeys, Capability::ListFiles
+        ]).await;
 
         let new_key_info = CreateKey::builder()
             .name("my-special-key")
@@ -960,6 +2261,202 @@ mod tests {
         assert!(! secret.is_empty());
         assert_eq!(key.capabilities.len(), 1);
         assert_eq!(key.capabilities[0], Capability::ListFiles);
+        assert_eq!(key.options(), ["s3"]);
+
+        let creds = Credentials::from((secret, key));
+        assert_eq!(creds.key_id, "002d2e6b27577ea000000000c");
+        assert_eq!(creds.secret, "K0029r3hpMfUhncpS7uLYuIkg3FCMlo");
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_expires_in_by_clock() -> Result<(), anyhow::Error> {
+        use crate::clock::FixedClock;
+        use chrono::TimeZone as _;
+
+        let key = |expiration: &str| serde_json::from_str::<Key>(&format!(
+            r#"{{
+                "keyName": "my-key",
+                "applicationKeyId": "key-id",
+                "capabilities": ["listFiles"],
+                "accountId": "account-id",
+                "expirationTimestamp": {},
+                "bucketId": null,
+                "namePrefix": null
+            }}"#,
+            expiration
+        ));
+
+        let clock = FixedClock::new(Utc.timestamp_opt(1641038400, 0).unwrap());
+
+        // Three days after the clock's time.
+        let key_3d = key("1641297600000")?;
+        assert_eq!(
+            key_3d.expiration(),
+            Some(Utc.timestamp_opt(1641297600, 0).unwrap())
+        );
+        assert_eq!(
+            key_3d.expires_in_with_clock(&clock),
+            Some(chrono::Duration::days(3))
+        );
+
+        clock.advance(chrono::Duration::days(4));
+        assert_eq!(
+            key_3d.expires_in_with_clock(&clock),
+            Some(chrono::Duration::days(-1))
+        );
+
+        assert_eq!(key("null")?.expires_in_with_clock(&clock), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_key_sends_options() -> Result<(), anyhow::Error> {
+        let new_key_info = CreateKey::builder()
+            .name("my-special-key")?
+            .capabilities([Capability::ListFiles])?
+            .with_options(vec!["s3".into()])
+            .build()?;
+
+        let json = serde_json::to_value(new_key_info)?;
+        assert_eq!(json["options"], serde_json::json!(["s3"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_key_builder_errors_are_structured() {
+        let long_name = "a".repeat(101);
+
+        let err = CreateKey::builder().name(long_name).err().unwrap();
+        assert!(matches!(err,
+            ValidationError::NameTooLong { length: 101, max: 100 }));
+        assert_eq!(err.to_string(),
+            "Name must be no more than 100 characters.");
+
+        let err = CreateKey::builder().name("my_key").err().unwrap();
+        assert!(matches!(err, ValidationError::InvalidCharacter('_')));
+        assert_eq!(err.to_string(), "Invalid character: _");
+
+        assert!(matches!(
+            CreateKey::builder().capabilities(Vec::new()),
+            Err(ValidationError::MissingCapabilities)
+        ));
+
+        let builder = || CreateKey::builder()
+            .name("my-key").unwrap()
+            .capabilities([Capability::ListFiles]).unwrap();
+
+        assert!(matches!(
+            builder().name_prefix("photos/").build(),
+            Err(ValidationError::PrefixWithoutBucket)
+        ));
+
+        assert!(matches!(
+            builder().capabilities([Capability::WriteKeys]).unwrap()
+                .limit_to_bucket("some-bucket-id").build(),
+            Err(ValidationError::CapabilityNotAllowedForBucket(
+                Capability::WriteKeys
+            ))
+        ));
+    }
+
+    #[test]
+    fn create_key_duration_is_sent_in_seconds() -> Result<(), anyhow::Error> {
+        let new_key_info = CreateKey::builder()
+            .name("my-special-key")?
+            .capabilities([Capability::ListFiles])?
+            .expires_after(chrono::Duration::hours(1))?
+            .build()?;
+
+        let json = serde_json::to_value(new_key_info)?;
+        assert_eq!(json["validDurationInSeconds"], 3600);
+
+        Ok(())
+    }
+
+    #[test]
+    fn create_read_only_bucket_key() -> Result<(), anyhow::Error> {
+        let day = chrono::Duration::days(1);
+
+        let new_key_info = CreateKey::builder()
+            .read_only_bucket("share-photos", "8d625eb63be2775577c70e1a", day)?
+            .build()?;
+
+        let json = serde_json::to_value(new_key_info)?;
+
+        assert_eq!(json["keyName"], "share-photos");
+        assert_eq!(json["capabilities"],
+            serde_json::json!(["listFiles", "readFiles"]));
+        assert_eq!(json["bucketId"], "8d625eb63be2775577c70e1a");
+        assert_eq!(json["validDurationInSeconds"], 86400);
+        assert!(json.get("namePrefix").is_none());
+
+        assert!(matches!(
+            CreateKey::builder().read_only_bucket(
+                "share-photos",
+                "8d625eb63be2775577c70e1a",
+                chrono::Duration::days(1000)
+            ),
+            Err(ValidationError::OutOfBounds(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_options_deserialize_robustly() -> Result<(), anyhow::Error> {
+        let key = |options: &str| serde_json::from_str::<Key>(&format!(
+            r#"{{
+                "keyName": "my-key",
+                "applicationKeyId": "key-id",
+                "capabilities": ["listFiles"],
+                "accountId": "account-id",
+                "expirationTimestamp": null,
+                "bucketId": null,
+                "namePrefix": null
+                {}
+            }}"#,
+            options
+        ));
+
+        assert!(key("")?.options().is_empty());
+        assert!(key(r#", "options": null"#)?.options().is_empty());
+        assert_eq!(
+            key(r#", "options": ["s3", "future-option"]"#)?.options(),
+            ["s3", "future-option"]
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn create_key_rejects_undelegable_capabilities()
+    -> Result<(), anyhow::Error> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![
+            Capability::WriteKeys, Capability::ListFiles
+        ]).await;
+
+        let new_key_info = CreateKey::builder()
+            .name("my-special-key")?
+            .capabilities([Capability::ListFiles, Capability::DeleteFiles])?
+            .build()?;
+
+        match create_key(&mut auth, new_key_info).await.unwrap_err() {
+            Error::Validation(ValidationError::Incompatible(msg)) => {
+                assert!(msg.contains("DeleteFiles"));
+                assert!(! msg.contains("ListFiles"));
+            },
+            e => panic!("Unexpected error type: {:?}", e),
+        }
 
         Ok(())
     }
@@ -1006,4 +2503,444 @@ mod tests {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn test_list_keys_by_name_prefix() -> Result<(), anyhow::Error> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListKeys])
+            .await;
+
+        // The recorded session returns one key on each of two pages.
+        let keys = list_keys_by_name_prefix(&mut auth, "dev-").await?;
+        assert_eq!(keys.len(), 2);
+        assert!(keys.iter().all(|k| k.key_name() == "dev-b2-client-tester"));
+
+        let keys = list_keys_by_name_prefix(&mut auth, "prod-").await?;
+        assert!(keys.is_empty());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn delete_keys_by_name_prefix_dry_run() -> Result<(), anyhow::Error> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListKeys])
+            .await;
+
+        let keys = delete_keys_by_name_prefix(&mut auth, "dev-", true).await?;
+        assert_eq!(keys.len(), 2);
+
+        match delete_keys_by_name_prefix(&mut auth, "dev-", false).await
+            .unwrap_err()
+        {
+            Error::Unauthorized(Capability::DeleteKeys) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn capabilities_diff_added_only() {
+        let old = Capabilities::new(vec![Capability::ListFiles], None, None,
+            None);
+        let new = Capabilities::new(
+            vec![Capability::ListFiles, Capability::ReadFiles],
+            None, None, None
+        );
+
+        let diff = old.diff(&new);
+
+        assert_eq!(diff.added, [Capability::ReadFiles]);
+        assert!(diff.removed.is_empty());
+        assert!(! diff.is_empty());
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn capabilities_diff_removed_only() {
+        let old = Capabilities::new(
+            vec![Capability::ListFiles, Capability::DeleteFiles],
+            None, None, None
+        );
+        let new = Capabilities::new(vec![Capability::ListFiles], None, None,
+            None);
+
+        let diff = old.diff(&new);
+
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed, [Capability::DeleteFiles]);
+        assert_eq!(diff.bucket_id, None);
+        assert_eq!(diff.name_prefix, None);
+    }
+
+    #[test]
+    fn sorted_capabilities_ignores_order() {
+        let a = Capabilities::new(
+            vec![Capability::WriteFiles, Capability::ListKeys,
+                Capability::WriteFiles],
+            None, None, None
+        );
+        let b = Capabilities::new(
+            vec![Capability::ListKeys, Capability::WriteFiles],
+            None, None, None
+        );
+
+        assert_eq!(
+            a.sorted_capabilities(),
+            [Capability::ListKeys, Capability::WriteFiles]
+        );
+        assert_eq!(a.sorted_capabilities(), b.sorted_capabilities());
+    }
+
+    #[test]
+    fn capabilities_diff_restrictions() {
+        let old = Capabilities::new(
+            vec![Capability::ListFiles],
+            Some("bucket-a".into()),
+            Some("my-bucket".into()),
+            None
+        );
+        let new = Capabilities::new(
+            vec![Capability::ListFiles],
+            Some("bucket-b".into()),
+            None,
+            Some("logs/".into())
+        );
+
+        let diff = old.diff(&new);
+
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(
+            diff.bucket_id,
+            Some((Some("bucket-a".into()), Some("bucket-b".into())))
+        );
+        assert_eq!(diff.name_prefix, Some((None, Some("logs/".into()))));
+    }
+
+    #[test]
+    fn log_redaction_hides_file_names() {
+        let redaction = LogRedaction { file_names: true };
+        let name = redaction.file_name("secret/plans.txt");
+
+        assert!(name.starts_with("<file ") && ! name.contains("secret"));
+        assert_eq!(name, redaction.file_name("secret/plans.txt"));
+        assert_eq!(
+            LogRedaction::default().file_name("secret/plans.txt"),
+            "secret/plans.txt"
+        );
+
+        let err: B2Result<()> = serde_json::from_str(r#"{
+            "status": 400,
+            "code": "bad_request",
+            "message": "File secret/plans.txt is not allowed"
+        }"#).unwrap();
+
+        let err = redaction.error::<surf::Error>(
+            Result::from(err).unwrap_err(),
+            "secret/plans.txt"
+        );
+
+        assert!(! err.to_string().contains("secret"));
+
+        let err = redaction.error::<surf::Error>(
+            ValidationError::MissingData(
+                "No file named secret/plans.txt exists".into()
+            ).into(),
+            "secret/plans.txt"
+        );
+        assert!(! err.to_string().contains("secret"));
+    }
+
+    #[async_std::test]
+    async fn redacted_authorization_omits_client() {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await.unwrap();
+
+        let auth = create_test_auth(client, vec![]).await;
+        assert!(format!("{:?}", auth).contains("SurfClient"));
+
+        let auth = auth.with_log_redaction(LogRedaction { file_names: true });
+        assert!(! format!("{:?}", auth).contains("SurfClient"));
+    }
+
+    #[test]
+    fn authorization_debug_redacts_token() {
+        let auth = create_auth_with(
+            crate::client::SurfClient::default(),
+            Capabilities::new(vec![], None, None, None)
+        );
+
+        assert_eq!(auth.authorization_token, "some-key-id");
+        assert!(! format!("{:?}", auth).contains("some-key-id"));
+    }
+
+    #[test]
+    fn part_size_bounds_never_recommend_less_than_minimum() {
+        let auth = |recommended, minimum| {
+            let mut auth = create_auth_with(
+                crate::client::SurfClient::default(),
+                Capabilities::new(vec![], None, None, None)
+            );
+
+            auth.recommended_part_size = recommended;
+            auth.absolute_minimum_part_size = minimum;
+            auth
+        };
+
+        assert_eq!(auth(100_000_000, 5_000_000).part_size_bounds(),
+            (5_000_000, 100_000_000));
+        assert_eq!(auth(5_000_000, 5_000_000).part_size_bounds(),
+            (5_000_000, 5_000_000));
+        assert_eq!(auth(1_000, 5_000_000).part_size_bounds(),
+            (5_000_000, 5_000_000));
+    }
+
+    #[test]
+    fn capabilities_summary_describes_restrictions() {
+        let auth = create_auth_with(
+            crate::client::SurfClient::default(),
+            Capabilities::new(
+                vec![Capability::ListFiles, Capability::ReadFiles],
+                Some("8d625eb63be2775577c70e1a".into()),
+                Some("testing-b2-client".into()),
+                Some("photos/".into()),
+            )
+        );
+
+        let summary = auth.capabilities_summary();
+        let lines: Vec<_> = summary.lines().collect();
+
+        assert_eq!(lines[0], "Account: some-account-id");
+        assert_eq!(lines[1], "Capabilities: listFiles, readFiles");
+        assert_eq!(
+            lines[2],
+            "Bucket: testing-b2-client (8d625eb63be2775577c70e1a)"
+        );
+        assert_eq!(lines[3], "File name prefix: photos/");
+        assert!(lines[4].starts_with("Expires: "));
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn endpoint_url_joins_slashes() {
+        let expected = concat!(
+            "https://api002.backblazeb2.com",
+            "/b2api/v2/b2_list_buckets"
+        );
+
+        for (base, endpoint) in [
+            ("https://api002.backblazeb2.com", "b2_list_buckets"),
+            ("https://api002.backblazeb2.com/", "b2_list_buckets"),
+            ("https://api002.backblazeb2.com", "/b2_list_buckets"),
+            ("https://api002.backblazeb2.com/", "/b2_list_buckets"),
+        ] {
+            assert_eq!(endpoint_url(base, endpoint), expected, "{}", base);
+        }
+
+        let expected = "https://example.com/b2/b2api/v2/b2_list_buckets";
+
+        assert_eq!(endpoint_url("https://example.com/b2", "b2_list_buckets"),
+            expected);
+        assert_eq!(endpoint_url("https://example.com/b2/", "b2_list_buckets"),
+            expected);
+
+        assert_eq!(endpoint_url("not a url", "b2_list_buckets"),
+            "not a url/b2api/v2/b2_list_buckets");
+    }
+
+    #[test]
+    fn urls_match_b2_endpoints() {
+        assert_eq!(endpoint_url(B2_AUTH_URL, "b2_authorize_account"),
+            "https://api.backblazeb2.com/b2api/v2/b2_authorize_account");
+
+        let mut auth = create_auth_with(
+            crate::client::SurfClient::default(),
+            Capabilities::new(vec![], None, None, None)
+        );
+
+        auth.api_url = "https://api002.backblazeb2.com/".into();
+        auth.download_url = "https://f002.backblazeb2.com/".into();
+
+        assert_eq!(auth.api_url_base(), "https://api002.backblazeb2.com/");
+        assert_eq!(auth.download_url_base(), "https://f002.backblazeb2.com/");
+
+        assert_eq!(auth.api_url("b2_list_buckets"),
+            "https://api002.backblazeb2.com/b2api/v2/b2_list_buckets");
+        assert_eq!(auth.download_url("b2_download_file_by_id"),
+            "https://f002.backblazeb2.com/b2api/v2/b2_download_file_by_id");
+
+        let bases = [
+            "https://f002.backblazeb2.com",
+            "https://f002.backblazeb2.com/",
+        ];
+
+        for base in bases {
+            assert_eq!(file_url(base, "my-bucket", "a/../b%20c.txt"),
+                "https://f002.backblazeb2.com/file/my-bucket/a/../b%20c.txt");
+        }
+    }
+
+    #[test]
+    fn authorization_without_s3_api_url() -> Result<(), anyhow::Error> {
+        let proto: ProtoAuthorization = serde_json::from_value(
+            serde_json::json!({
+                "accountId": "some-account-id",
+                "authorizationToken": "some-token",
+                "allowed": {
+                    "capabilities": ["listBuckets"],
+                    "bucketId": null,
+                    "bucketName": null,
+                    "namePrefix": null,
+                },
+                "apiUrl": "https://api002.backblazeb2.com",
+                "downloadUrl": "https://f002.backblazeb2.com",
+                "recommendedPartSize": 100000000,
+                "absoluteMinimumPartSize": 5000000,
+            })
+        )?;
+
+        let auth = proto.create_authorization(
+            crate::client::SurfClient::default(),
+            crate::clock::system()
+        );
+
+        assert!(auth.s3_api_url().is_none());
+        assert!(auth.s3_region().is_none());
+        assert!(auth.key_expiration().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn authorization_key_expiration_is_parsed() -> Result<(), anyhow::Error> {
+        use chrono::TimeZone;
+
+        let proto: ProtoAuthorization = serde_json::from_value(
+            serde_json::json!({
+                "accountId": "some-account-id",
+                "authorizationToken": "some-token",
+                "allowed": {
+                    "capabilities": ["listBuckets"],
+                    "bucketId": null,
+                    "bucketName": null,
+                    "namePrefix": null,
+                },
+                "apiUrl": "https://api002.backblazeb2.com",
+                "downloadUrl": "https://f002.backblazeb2.com",
+                "recommendedPartSize": 100000000,
+                "absoluteMinimumPartSize": 5000000,
+                "s3ApiUrl": "https://s3.us-west-002.backblazeb2.com",
+                "applicationKeyExpirationTimestamp": 1700000000000u64,
+            })
+        )?;
+
+        let auth = proto.create_authorization(
+            crate::client::SurfClient::default(),
+            crate::clock::system()
+        );
+
+        assert_eq!(
+            auth.key_expiration(),
+            Some(chrono::Utc.timestamp_millis_opt(1700000000000).unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_deserializes_from_minimal_response() -> Result<(), anyhow::Error> {
+        let key: Key = serde_json::from_value(serde_json::json!({
+            "keyName": "my-key",
+            "applicationKeyId": "some-key-id",
+            "capabilities": ["listFiles"],
+            "accountId": "some-account-id",
+        }))?;
+
+        assert!(key.expiration().is_none());
+        assert!(key.bucket_id().is_none());
+        assert!(key.name_prefix().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn s3_region_is_parsed_from_endpoint() {
+        let region = s3_region_from_url;
+
+        assert_eq!(region("https://s3.us-west-002.backblazeb2.com").as_deref(),
+            Some("us-west-002"));
+        assert_eq!(region("https://s3.us-west-004.backblazeb2.com/")
+            .as_deref(), Some("us-west-004"));
+        assert_eq!(region("https://s3.eu-central-003.backblazeb2.com")
+            .as_deref(), Some("eu-central-003"));
+
+        assert_eq!(region("https://s3.backblazeb2.com"), None);
+        assert_eq!(region("https://s3.a.b.backblazeb2.com"), None);
+        assert_eq!(region("https://api002.backblazeb2.com"), None);
+        assert_eq!(region("https://s3.us-west-002.example.com"), None);
+        assert_eq!(region("not a url"), None);
+    }
+
+    #[test]
+    fn user_agent_identifies_app() {
+        let auth = create_auth_with(
+            crate::client::SurfClient::default(),
+            Capabilities::new(vec![], None, None, None)
+        );
+
+        let auth = auth.with_user_agent("my-app/1.0").unwrap();
+
+        assert_eq!(auth.client.user_agent_string().unwrap(), format!(
+            "rust-b2-client/{}; surf; my-app/1.0",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        // A second application replaces the first.
+        let auth = auth.with_user_agent("my-app/2.0").unwrap();
+
+        assert_eq!(auth.client.user_agent_string().unwrap(), format!(
+            "rust-b2-client/{}; surf; my-app/2.0",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        assert!(matches!(auth.with_user_agent(""),
+            Err(ValidationError::MissingData(_))
+        ));
+    }
+
+    #[test]
+    fn bucket_id_defaults_to_restricted_bucket() {
+        let auth = |bucket_id| create_auth_with(
+            crate::client::SurfClient::default(),
+            Capabilities::new(vec![], bucket_id, None, None)
+        );
+
+        let restricted = auth(Some("8d625eb63be2775577c70e1a".into()));
+        let unrestricted = auth(None);
+
+        assert_eq!(restricted.bucket_id_or_restricted(None).unwrap(),
+            "8d625eb63be2775577c70e1a");
+        assert_eq!(restricted.bucket_id_or_restricted(Some("other")).unwrap(),
+            "other");
+        assert_eq!(unrestricted.bucket_id_or_restricted(Some("other"))
+            .unwrap(), "other");
+
+        assert!(matches!(unrestricted.bucket_id_or_restricted(None),
+            Err(ValidationError::MissingData(_))
+        ));
+    }
 }
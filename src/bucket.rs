@@ -11,12 +11,14 @@
 //! A B2 account has a limit of 100 buckets. All bucket names must be globally
 //! unique (unique across all accounts).
 
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, collections::HashMap, fmt};
 
 use crate::{
     prelude::*,
     client::HttpClient,
     error::*,
+    file::{BypassGovernance, DeletedFile},
+    types::IntoDuration,
     validate::*,
 };
 
@@ -294,8 +296,10 @@ impl CorsRuleBuilder {
     /// preflight request.
     ///
     /// The age must be non-negative and no more than one day.
-    pub fn max_age(mut self, age: chrono::Duration)
+    pub fn max_age(mut self, age: impl IntoDuration)
     -> Result<Self, ValidationError> {
+        let age = age.into_duration()?;
+
         if age < chrono::Duration::zero() || age > chrono::Duration::days(1) {
             return Err(ValidationError::OutOfBounds(
                 "Age must be non-negative and no more than 1 day".into()
@@ -422,9 +426,9 @@ impl<'a> LifecycleRuleBuilder<'a> {
     /// number of days must be at least one.
     ///
     /// The maximum number of days supported is [u16::MAX].
-    pub fn hide_after_upload(mut self, days: chrono::Duration)
+    pub fn hide_after_upload(mut self, days: impl IntoDuration)
     -> Result<Self, ValidationError> {
-        let days = days.num_days();
+        let days = days.into_duration()?.num_days();
 
         if days < 1 {
             Err(ValidationError::OutOfBounds(
@@ -452,9 +456,9 @@ impl<'a> LifecycleRuleBuilder<'a> {
     /// The B2 service automatically hides files when a file with the same is
     /// uploaded (e.g., when a file changes). Files can also be explicitly
     /// hidden via [hide_file](crate::file::hide_file).
-    pub fn delete_after_hide(mut self, days: chrono::Duration)
+    pub fn delete_after_hide(mut self, days: impl IntoDuration)
     -> Result<Self, ValidationError> {
-        let days = days.num_days();
+        let days = days.into_duration()?.num_days();
 
         if days < 1 {
             Err(ValidationError::OutOfBounds(
@@ -844,35 +848,51 @@ impl CreateBucketBuilder {
     }
 }
 
-/// Information from B2 concerning a file's retention settings.
-#[derive(Debug, Deserialize)]
+/// Information from B2 concerning a bucket's file lock settings.
+///
+/// Reading the configuration requires [Capability::ReadBucketRetentions]; if
+/// the [Authorization] used to obtain the bucket lacks it, the configuration is
+/// unavailable.
+#[derive(Debug, Default, Deserialize)]
 pub struct FileLockConfiguration {
     #[serde(rename = "isClientAuthorizedToRead")]
     can_read: bool,
-    #[serde(rename = "isFileLockEnabled")]
-    file_lock_enabled: bool,
-    #[serde(rename = "value")]
-    retention: FileRetentionPolicy,
+    // B2 returns `null` when the client is not authorized to read the value.
+    #[serde(default)]
+    value: Option<FileLockSettings>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileLockSettings {
+    default_retention: FileRetentionPolicy,
+    is_file_lock_enabled: bool,
 }
 
 impl FileLockConfiguration {
+    /// True if the authorization token allows access to the file lock
+    /// configuration.
+    ///
+    /// If this is `false`, all other methods will return `None`.
+    pub fn can_read(&self) -> bool { self.can_read }
+
     /// Check whether a file lock is enabled.
     ///
     /// If not authorized to read the file lock configuration, returns `None`.
     pub fn lock_is_enabled(&self) -> Option<bool> {
-        if self.can_read {
-            Some(self.file_lock_enabled)
-        } else {
-            None
-        }
+        self.settings().map(|v| v.is_file_lock_enabled)
     }
 
-    /// Get the file lock's retention policy.
+    /// Get the file lock's default retention policy.
     ///
     /// If not authorized to read the file lock configuration, returns `None`.
     pub fn retention_policy(&self) -> Option<FileRetentionPolicy> {
+        self.settings().map(|v| v.default_retention)
+    }
+
+    fn settings(&self) -> Option<&FileLockSettings> {
         if self.can_read {
-            Some(self.retention)
+            self.value.as_ref()
         } else {
             None
         }
@@ -897,6 +917,7 @@ impl fmt::Display for FileRetentionMode {
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum PeriodUnit { Days, Years }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -945,7 +966,7 @@ impl FileRetentionPolicy {
 }
 
 /// Response from B2 with the configured bucket encryption settings.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BucketEncryptionInfo {
     is_client_authorized_to_read: bool,
@@ -973,10 +994,17 @@ pub struct Bucket {
     pub(crate) bucket_id: String,
     bucket_name: String,
     bucket_type: BucketType,
+    // A missing setting is treated as empty, or for settings that require a
+    // capability to read, as unreadable.
+    #[serde(default)]
     bucket_info: serde_json::Value,
+    #[serde(default)]
     cors_rules: Vec<CorsRule>,
-    file_lock_configuration: FileRetentionPolicy,
+    #[serde(default)]
+    file_lock_configuration: FileLockConfiguration,
+    #[serde(default)]
     default_server_side_encryption: BucketEncryptionInfo,
+    #[serde(default)]
     lifecycle_rules: Vec<LifecycleRule>,
     revision: u16,
     options: Option<Vec<String>>,
@@ -990,14 +1018,38 @@ impl Bucket {
     pub fn info(&self) -> &serde_json::Value { &self.bucket_info }
     pub fn cors_rules(&self) -> &[CorsRule] { &self.cors_rules }
 
-    pub fn retention_policy(&self) -> FileRetentionPolicy {
-        self.file_lock_configuration
+    /// The bucket's file lock configuration.
+    ///
+    /// The [Authorization] must have [Capability::ReadBucketRetentions] to
+    /// read the configuration; see [FileLockConfiguration::can_read].
+    pub fn file_lock_configuration(&self) -> &FileLockConfiguration {
+        &self.file_lock_configuration
+    }
+
+    /// The bucket's default file retention policy.
+    ///
+    /// Returns `None` if not authorized to read the file lock configuration.
+    pub fn retention_policy(&self) -> Option<FileRetentionPolicy> {
+        self.file_lock_configuration.retention_policy()
     }
 
     pub fn encryption_info(&self) -> &BucketEncryptionInfo {
         &self.default_server_side_encryption
     }
 
+    /// The bucket's default server-side encryption settings.
+    ///
+    /// Returns `None` if the [Authorization] lacks
+    /// [Capability::ReadBucketEncryption].
+    pub fn default_server_side_encryption(&self)
+    -> Option<&ServerSideEncryption> {
+        if self.default_server_side_encryption.can_read() {
+            self.default_server_side_encryption.settings()
+        } else {
+            None
+        }
+    }
+
     pub fn lifecycle_rules(&self) -> &[LifecycleRule] { &self.lifecycle_rules }
     pub fn revision(&self) -> u16 { self.revision }
     pub fn options(&self) -> Option<&Vec<String>> { self.options.as_ref() }
@@ -1026,9 +1078,9 @@ pub async fn create_bucket<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(new_bucket_info)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let new_bucket: B2Result<Bucket> = serde_json::from_slice(&res)?;
+    let new_bucket: B2Result<Bucket> = B2Result::from_response(&res)?;
     new_bucket.into()
 }
 
@@ -1054,12 +1106,239 @@ pub async fn delete_bucket<C, E>(
             "accountId": &auth.account_id,
             "bucketId": bucket_id.as_ref(),
         }))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let new_bucket: B2Result<Bucket> = serde_json::from_slice(&res)?;
+    let new_bucket: B2Result<Bucket> = B2Result::from_response(&res)?;
     new_bucket.into()
 }
 
+/// Delete every file version in a bucket, then delete the bucket.
+///
+/// B2 only deletes empty buckets; this cancels all unfinished large files via
+/// [cancel_large_file_by_id](crate::file::cancel_large_file_by_id), then
+/// deletes all file versions and hide markers. Returns the unfinished large
+/// files and file versions that were removed. If `dry_run` is `true`, they are
+/// listed and returned but neither they nor the bucket are deleted.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::ListFiles], and unless `dry_run`
+/// is set, [Capability::DeleteFiles] and [Capability::DeleteBuckets];
+/// cancelling unfinished large files also requires [Capability::WriteFiles],
+/// and bypassing governance rules requires [Capability::BypassGovernance].
+///
+/// If a cancellation or deletion fails, its error is returned, and the files
+/// listed after it and the bucket are not deleted. New files uploaded to the
+/// bucket during the deletion will cause the bucket deletion to fail.
+pub async fn delete_bucket_recursive<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    bypass_governance: BypassGovernance,
+    dry_run: bool,
+) -> Result<Vec<DeletedFile>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    use crate::file::{
+        cancel_large_file_by_id,
+        delete_file_versions,
+        list_file_versions,
+        require_delete_capabilities,
+        DeletedFile,
+        FileAction,
+        ListFileVersions,
+    };
+
+    require_delete_capabilities(auth, bypass_governance, dry_run)?;
+
+    if ! dry_run {
+        require_capability!(auth, Capability::DeleteBuckets);
+    }
+
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+    let bucket_id = bucket_id.as_str();
+
+    let mut versions = vec![];
+    let mut request = Some(
+        ListFileVersions::builder()
+            .bucket_id(bucket_id)
+            .max_file_count(1000)
+            .build()
+            .expect("The bucket ID is set")
+    );
+
+    while let Some(req) = request {
+        let (mut files, next_req) = list_file_versions(auth, req).await?;
+        versions.append(&mut files);
+        request = next_req;
+    }
+
+    if dry_run {
+        return delete_file_versions(auth, versions, bypass_governance, true)
+            .await;
+    }
+
+    let (unfinished, versions): (Vec<_>, Vec<_>) = versions.into_iter()
+        .partition(|f| f.action() == FileAction::Start);
+
+    if ! unfinished.is_empty() {
+        require_capability!(auth, Capability::WriteFiles);
+    }
+
+    let mut deleted = Vec::with_capacity(unfinished.len() + versions.len());
+
+    for file in unfinished {
+        let file = cancel_large_file_by_id(auth, file.file_id()).await?;
+
+        deleted.push(DeletedFile {
+            file_id: file.file_id,
+            file_name: file.file_name,
+        });
+    }
+
+    deleted.extend(
+        delete_file_versions(auth, versions, bypass_governance, false).await?
+    );
+
+    delete_bucket(auth, bucket_id).await?;
+
+    Ok(deleted)
+}
+
+/// The storage used by a bucket, as computed by [bucket_usage].
+///
+/// The current version of a file is its most recent upload or copy, unless a
+/// hide marker is newer; every other version is an old version. Hide markers
+/// and unfinished large files do not count as files.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct BucketUsage {
+    current_files: u64,
+    current_bytes: u64,
+    old_files: u64,
+    old_bytes: u64,
+    hide_markers: u64,
+}
+
+impl BucketUsage {
+    /// The total number of file versions stored in the bucket.
+    pub fn files(&self) -> u64 { self.current_files + self.old_files }
+
+    /// The total number of bytes stored in the bucket.
+    pub fn bytes(&self) -> u64 { self.current_bytes + self.old_bytes }
+
+    /// The number of files whose current version is visible.
+    pub fn current_files(&self) -> u64 { self.current_files }
+
+    /// The number of bytes stored in the current versions of files.
+    pub fn current_bytes(&self) -> u64 { self.current_bytes }
+
+    /// The number of old or hidden file versions.
+    pub fn old_files(&self) -> u64 { self.old_files }
+
+    /// The number of bytes stored in old or hidden file versions.
+    pub fn old_bytes(&self) -> u64 { self.old_bytes }
+
+    /// The number of hide markers in the bucket.
+    pub fn hide_markers(&self) -> u64 { self.hide_markers }
+
+    // Versions must be added in the order B2 lists them: by name, then newest
+    // first. `last_name` is the name of the previously-added version.
+    fn add(&mut self, file: &crate::file::File, last_name: &mut Option<String>)
+    {
+        use crate::file::FileAction;
+
+        let is_first = last_name.as_deref() != Some(file.file_name());
+
+        match file.action() {
+            FileAction::Upload | FileAction::Copy => {
+                let bytes = file.content_length().unwrap_or(0);
+
+                if is_first {
+                    self.current_files += 1;
+                    self.current_bytes += bytes;
+                } else {
+                    self.old_files += 1;
+                    self.old_bytes += bytes;
+                }
+            },
+            FileAction::Hide => self.hide_markers += 1,
+            // An unfinished large file does not affect which version is
+            // current.
+            FileAction::Start | FileAction::Folder => return,
+        }
+
+        if is_first {
+            *last_name = Some(file.file_name().to_owned());
+        }
+    }
+}
+
+/// Compute the number of files and bytes stored in a bucket.
+///
+/// There is no B2 API call to obtain a bucket's size; this lists every file
+/// version in the bucket, 1,000 versions per request, so it takes time and
+/// transactions proportional to the number of files in the bucket. See
+/// [bucket_usage_with_progress] to report progress during the scan.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::ListFiles].
+pub async fn bucket_usage<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+) -> Result<BucketUsage, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    bucket_usage_with_progress(auth, bucket_id, |_| {}).await
+}
+
+/// Compute the number of files and bytes stored in a bucket, reporting the
+/// running totals as the bucket is scanned.
+///
+/// `progress` is called with the usage counted so far after each page of file
+/// versions is listed.
+///
+/// See [bucket_usage] for details.
+pub async fn bucket_usage_with_progress<C, E, P>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    mut progress: P,
+) -> Result<BucketUsage, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          P: FnMut(&BucketUsage),
+{
+    use crate::file::{list_file_versions, ListFileVersions};
+
+    require_capability!(auth, Capability::ListFiles);
+
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+
+    let mut usage = BucketUsage::default();
+    let mut last_name = None;
+    let mut request = Some(
+        ListFileVersions::builder()
+            .bucket_id(&bucket_id)
+            .max_file_count(1000)
+            .build()
+            .expect("The bucket ID is set")
+    );
+
+    while let Some(req) = request {
+        let (files, next_req) = list_file_versions(auth, req).await?;
+
+        for file in files.iter() {
+            usage.add(file, &mut last_name);
+        }
+
+        progress(&usage);
+        request = next_req;
+    }
+
+    Ok(usage)
+}
+
 // The B2 API intention is that only an ID or name is supplied when listing
 // buckets.
 #[derive(Debug, Clone, Serialize)]
@@ -1171,9 +1450,11 @@ struct BucketList {
 /// List buckets accessible by the [Authorization] according to the filter
 /// provided by a [ListBuckets] object.
 ///
-/// If your `Authorization` only has access to one bucket, then attempting to
-/// list all buckets will result in an error with
-/// [ErrorCode::Unauthorized](crate::error::ErrorCode::Unauthorized).
+/// If your `Authorization` only has access to one bucket and `list_info` does
+/// not name a bucket, that bucket is listed; B2 rejects a request from such an
+/// `Authorization` to list all buckets.
+///
+/// The [Authorization] must have [Capability::ListBuckets].
 pub async fn list_buckets<C, E>(
     auth: &mut Authorization<C>,
     list_info: ListBuckets<'_>
@@ -1186,16 +1467,297 @@ pub async fn list_buckets<C, E>(
     let mut list_info = list_info;
     list_info.account_id = Some(&auth.account_id);
 
+    if list_info.bucket.is_none() {
+        list_info.bucket = auth.capabilities().bucket_id()
+            .map(|id| BucketRef::Id(id.to_owned()));
+    }
+
     let res = auth.client.post(auth.api_url("b2_list_buckets"))
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(list_info)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let buckets: B2Result<BucketList> = serde_json::from_slice(&res)?;
+    let buckets: B2Result<BucketList> = B2Result::from_response(&res)?;
     buckets.map(|b| b.buckets).into()
 }
 
+/// Get all buckets accessible by the [Authorization], keyed by bucket name.
+///
+/// If the `Authorization` is restricted to a single bucket, only that bucket
+/// is returned.
+///
+/// The [Authorization] must have [Capability::ListBuckets].
+pub async fn bucket_index<C, E>(auth: &mut Authorization<C>)
+-> Result<HashMap<String, Bucket>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let buckets = list_buckets(auth, ListBuckets::builder().build()).await?;
+
+    Ok(buckets.into_iter()
+        .map(|b| (b.bucket_name.clone(), b))
+        .collect())
+}
+
+/// Log onto the B2 API and list the buckets accessible by the new
+/// [Authorization].
+///
+/// This is a quick way to check connectivity and credentials. If the key is
+/// restricted to a single bucket, only that bucket is returned.
+///
+/// The key must have [Capability::ListBuckets]. See [authorize_account] and
+/// [list_buckets] for further information.
+pub async fn authorize_and_list_buckets<C, E>(
+    client: C,
+    key_id: &str,
+    key: &str,
+) -> Result<(Authorization<C>, Vec<Bucket>), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let mut auth = authorize_account(client, key_id, key).await?;
+    let buckets = list_buckets(&mut auth, ListBuckets::builder().build())
+        .await?;
+
+    Ok((auth, buckets))
+}
+
+/// The result of looking up a bucket with [get_bucket_by_name] or
+/// [get_bucket_by_id].
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)] // Boxing would only add an allocation.
+pub enum BucketLookup {
+    /// The bucket is visible to the [Authorization].
+    Found(Bucket),
+    /// No bucket with the name or ID exists in the account.
+    ///
+    /// B2 does not distinguish a bucket that doesn't exist from one that
+    /// belongs to another account; both are reported as `NotFound`.
+    NotFound,
+    /// The [Authorization] is not permitted to see the bucket, whether or not
+    /// it exists; e.g., its key is restricted to a different bucket.
+    Forbidden,
+}
+
+/// Look up the bucket named `name`.
+///
+/// Listing a bucket by name returns nothing both when the bucket does not
+/// exist and when the [Authorization] cannot see it. Where possible, this
+/// distinguishes the two:
+///
+/// * If the `Authorization` is restricted to a bucket with a different name,
+///   returns [BucketLookup::Forbidden] without making a request.
+/// * If B2 denies the request as `unauthorized` or `access_denied`, returns
+///   [BucketLookup::Forbidden].
+/// * Otherwise, if no bucket is listed, returns [BucketLookup::NotFound].
+///
+/// If the `Authorization` is restricted to a bucket that has since been
+/// deleted, its name is unknown; the result depends on B2's response to the
+/// request.
+///
+/// The [Authorization] must have [Capability::ListBuckets].
+pub async fn get_bucket_by_name<C, E>(auth: &mut Authorization<C>, name: &str)
+-> Result<BucketLookup, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    use crate::error::ErrorCode;
+
+    require_capability!(auth, Capability::ListBuckets);
+
+    if let Some(restricted) = auth.capabilities().bucket_name() {
+        if restricted != name {
+            return Ok(BucketLookup::Forbidden);
+        }
+    }
+
+    let list_info = ListBuckets::builder()
+        .bucket_name(name)
+        .map_err(|e| ValidationError::BadFormat(e.to_string()))?
+        .build();
+
+    match list_buckets(auth, list_info).await {
+        Ok(buckets) => Ok(buckets.into_iter()
+            .find(|b| b.bucket_name == name)
+            .map(BucketLookup::Found)
+            .unwrap_or(BucketLookup::NotFound)),
+        Err(Error::B2(e)) if matches!(e.code(),
+            ErrorCode::Unauthorized | ErrorCode::AccessDenied
+        ) => Ok(BucketLookup::Forbidden),
+        Err(e) => Err(e),
+    }
+}
+
+/// Look up the bucket with the ID `id`.
+///
+/// This lists only the specified bucket, so it is the cheapest way to get the
+/// current information for a bucket whose ID is known. As with
+/// [get_bucket_by_name]:
+///
+/// * If the [Authorization] is restricted to a different bucket, returns
+///   [BucketLookup::Forbidden] without making a request.
+/// * If B2 denies the request as `unauthorized` or `access_denied`, returns
+///   [BucketLookup::Forbidden].
+/// * Otherwise, if no bucket is listed, returns [BucketLookup::NotFound].
+///
+/// The [Authorization] must have [Capability::ListBuckets].
+pub async fn get_bucket_by_id<C, E>(auth: &mut Authorization<C>, id: &str)
+-> Result<BucketLookup, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    use crate::error::ErrorCode;
+
+    require_capability!(auth, Capability::ListBuckets);
+
+    if let Some(restricted) = auth.capabilities().bucket_id() {
+        if restricted != id {
+            return Ok(BucketLookup::Forbidden);
+        }
+    }
+
+    let list_info = ListBuckets::builder()
+        .bucket_id(id)
+        .build();
+
+    match list_buckets(auth, list_info).await {
+        Ok(buckets) => Ok(buckets.into_iter()
+            .find(|b| b.bucket_id == id)
+            .map(BucketLookup::Found)
+            .unwrap_or(BucketLookup::NotFound)),
+        Err(Error::B2(e)) if matches!(e.code(),
+            ErrorCode::Unauthorized | ErrorCode::AccessDenied
+        ) => Ok(BucketLookup::Forbidden),
+        Err(e) => Err(e),
+    }
+}
+
+/// Wait until the bucket named `name` is visible, polling [list_buckets] with
+/// exponential backoff.
+///
+/// B2 is eventually consistent; a bucket that was just created may not be
+/// listed right away. The first poll is made immediately; the delay before
+/// each later poll starts at `interval` and doubles each time, but never
+/// extends beyond `timeout`.
+///
+/// `sleep` waits between polls; see [the retry module](crate::retry#sleeping).
+/// The timeout counts only the time spent sleeping, not the time spent waiting
+/// for B2 to respond.
+///
+/// The [Authorization] must have [Capability::ListBuckets].
+///
+/// # Errors
+///
+/// Returns [Error::Timeout] if the bucket is not visible after waiting
+/// `timeout`, or [ValidationError::OutOfBounds] if `interval` is zero.
+pub async fn wait_for_bucket<C, E, S, F>(
+    auth: &mut Authorization<C>,
+    name: &str,
+    interval: std::time::Duration,
+    timeout: std::time::Duration,
+    mut sleep: S,
+) -> Result<Bucket, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          S: FnMut(std::time::Duration) -> F,
+          F: std::future::Future<Output = ()>,
+{
+    // The delay would never grow, so we would poll B2 without pause forever.
+    if interval.is_zero() {
+        return Err(ValidationError::OutOfBounds(
+            "The polling interval must be greater than zero".into()
+        ).into());
+    }
+
+    let mut waited = std::time::Duration::ZERO;
+    let mut delay = interval;
+
+    loop {
+        let list_info = ListBuckets::builder()
+            .bucket_name(name)
+            .map_err(|e| ValidationError::BadFormat(e.to_string()))?
+            .build();
+
+        let bucket = list_buckets(auth, list_info).await?.into_iter()
+            .find(|b| b.bucket_name == name);
+
+        if let Some(bucket) = bucket {
+            return Ok(bucket);
+        }
+
+        let remaining = timeout.saturating_sub(waited);
+
+        if remaining.is_zero() {
+            return Err(Error::Timeout);
+        }
+
+        let next = delay.min(remaining);
+        sleep(next).await;
+
+        waited += next;
+        delay = delay.saturating_mul(2);
+    }
+}
+
+/// A change to a bucket setting in an [UpdateBucket] request.
+///
+/// B2 distinguishes between a setting that is omitted from the request and
+/// one that is sent empty: an omitted setting is left as it is, while an empty
+/// object or list removes everything currently stored in it. `Unchanged` omits
+/// the setting, `Set` replaces the current value, and `Clear` sends an empty
+/// value to remove it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum FieldUpdate<T> {
+    /// Leave the bucket's current setting as it is.
+    #[default]
+    Unchanged,
+    /// Replace the bucket's current setting with the provided value.
+    Set(T),
+    /// Remove the bucket's current setting.
+    Clear,
+}
+
+impl<T> FieldUpdate<T> {
+    fn is_unchanged(&self) -> bool {
+        matches!(self, Self::Unchanged)
+    }
+}
+
+impl<T> From<Option<T>> for FieldUpdate<T> {
+    /// Convert `None` to [FieldUpdate::Unchanged] and `Some` to
+    /// [FieldUpdate::Set].
+    fn from(val: Option<T>) -> Self {
+        match val {
+            Some(val) => Self::Set(val),
+            None => Self::Unchanged,
+        }
+    }
+}
+
+fn serialize_object_update<S>(
+    update: &FieldUpdate<serde_json::Value>,
+    serializer: S
+) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer,
+{
+    match update {
+        FieldUpdate::Set(val) => val.serialize(serializer),
+        _ => serde_json::Map::new().serialize(serializer),
+    }
+}
+
+fn serialize_list_update<S, T>(update: &FieldUpdate<Vec<T>>, serializer: S)
+-> Result<S::Ok, S::Error>
+    where S: serde::Serializer,
+          T: Serialize,
+{
+    match update {
+        FieldUpdate::Set(val) => val.serialize(serializer),
+        _ => Vec::<T>::new().serialize(serializer),
+    }
+}
+
 /// A request to update one or more settings on a [Bucket].
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -1204,16 +1766,25 @@ pub struct UpdateBucket<'a> {
     bucket_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     bucket_type: Option<BucketType>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    bucket_info: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    cors_rules: Option<Vec<CorsRule>>,
+    #[serde(
+        skip_serializing_if = "FieldUpdate::is_unchanged",
+        serialize_with = "serialize_object_update"
+    )]
+    bucket_info: FieldUpdate<serde_json::Value>,
+    #[serde(
+        skip_serializing_if = "FieldUpdate::is_unchanged",
+        serialize_with = "serialize_list_update"
+    )]
+    cors_rules: FieldUpdate<Vec<CorsRule>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     default_retention: Option<FileRetentionPolicy>,
     #[serde(skip_serializing_if = "Option::is_none")]
     default_server_side_encryption: Option<ServerSideEncryption>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    lifecycle_rules: Option<Vec<LifecycleRule>>,
+    #[serde(
+        skip_serializing_if = "FieldUpdate::is_unchanged",
+        serialize_with = "serialize_list_update"
+    )]
+    lifecycle_rules: FieldUpdate<Vec<LifecycleRule>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     if_revision_is: Option<u16>,
 }
@@ -1229,12 +1800,12 @@ impl<'a> UpdateBucket<'a> {
 pub struct UpdateBucketBuilder {
     bucket_id: Option<String>,
     bucket_type: Option<BucketType>,
-    bucket_info: Option<serde_json::Value>,
+    bucket_info: FieldUpdate<serde_json::Value>,
     cache_control: Option<String>,
-    cors_rules: Option<Vec<CorsRule>>,
+    cors_rules: FieldUpdate<Vec<CorsRule>>,
     default_retention: Option<FileRetentionPolicy>,
     default_server_side_encryption: Option<ServerSideEncryption>,
-    lifecycle_rules: Option<Vec<LifecycleRule>>,
+    lifecycle_rules: FieldUpdate<Vec<LifecycleRule>>,
     if_revision_is: Option<u16>,
 }
 
@@ -1268,7 +1839,16 @@ impl UpdateBucketBuilder {
     /// via the `cache-control` method, the latter will override this value.
     pub fn bucket_info(mut self, info: serde_json::Value)
     -> Self {
-        self.bucket_info = Some(info);
+        self.bucket_info = FieldUpdate::Set(info);
+        self
+    }
+
+    /// Remove all of the bucket's current bucket information.
+    ///
+    /// If [cache_control](Self::cache_control) is also used, the bucket
+    /// information is replaced with only the Cache-Control setting.
+    pub fn clear_bucket_info(mut self) -> Self {
+        self.bucket_info = FieldUpdate::Clear;
         self
     }
 
@@ -1282,6 +1862,9 @@ impl UpdateBucketBuilder {
     /// Replace the bucket's current provided CORS rules with the provided
     /// rules.
     ///
+    /// An empty list leaves the current rules unchanged; use
+    /// [clear_cors_rules](Self::clear_cors_rules) to remove them.
+    ///
     /// See <https://www.backblaze.com/b2/docs/cors_rules.html> for further
     /// information.
     pub fn cors_rules(mut self, rules: impl Into<Vec<CorsRule>>)
@@ -1293,12 +1876,18 @@ impl UpdateBucketBuilder {
                 "A bucket can have no more than 100 CORS rules".into()
             ));
         } else if ! rules.is_empty() {
-            self.cors_rules = Some(rules);
+            self.cors_rules = FieldUpdate::Set(rules);
         }
 
         Ok(self)
     }
 
+    /// Remove all of the bucket's CORS rules.
+    pub fn clear_cors_rules(mut self) -> Self {
+        self.cors_rules = FieldUpdate::Clear;
+        self
+    }
+
     /// Replace the bucket's default retention policy.
     ///
     /// The [Authorization] must have
@@ -1325,11 +1914,20 @@ impl UpdateBucketBuilder {
     pub fn lifecycle_rules(mut self, rules: impl Into<Vec<LifecycleRule>>)
     -> Result<Self, LifecycleRuleValidationError> {
         let rules = validated_lifecycle_rules(rules)?;
-        self.lifecycle_rules = Some(rules);
+        self.lifecycle_rules = FieldUpdate::Set(rules);
 
         Ok(self)
     }
 
+    /// Remove all of the bucket's lifecycle rules.
+    ///
+    /// This is equivalent to passing an empty list to
+    /// [lifecycle_rules](Self::lifecycle_rules).
+    pub fn clear_lifecycle_rules(mut self) -> Self {
+        self.lifecycle_rules = FieldUpdate::Clear;
+        self
+    }
+
     /// Only perform the update if the bucket's current revision is the provided
     /// version.
     pub fn if_revision_is(mut self, revision: u16) -> Self {
@@ -1345,9 +1943,10 @@ impl UpdateBucketBuilder {
         )?;
 
         let bucket_info = if let Some(cache_control) = self.cache_control {
-            let mut info = self.bucket_info.unwrap_or_else(||
-                serde_json::Value::Object(serde_json::Map::new())
-            );
+            let mut info = match self.bucket_info {
+                FieldUpdate::Set(info) => info,
+                _ => serde_json::Value::Object(serde_json::Map::new()),
+            };
 
             info.as_object_mut()
                 .map(|map| map.insert(
@@ -1355,7 +1954,7 @@ impl UpdateBucketBuilder {
                     serde_json::Value::String(cache_control)
                 ));
 
-            Some(info)
+            FieldUpdate::Set(info)
         } else {
             self.bucket_info
         };
@@ -1400,9 +1999,9 @@ pub async fn update_bucket<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(bucket_info)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let bucket: B2Result<Bucket> = serde_json::from_slice(&res)?;
+    let bucket: B2Result<Bucket> = B2Result::from_response(&res)?;
     bucket.into()
 }
 
@@ -1581,8 +2180,209 @@ mod tests_mocked {
     };
     use surf_vcr::VcrMode;
 
-    use crate::test_utils::{create_test_auth, create_test_client};
+    use crate::test_utils::{
+        create_auth_with, create_test_auth, create_test_client,
+    };
+
 
+    #[async_std::test]
+    async fn bucket_usage_splits_current_and_old_versions()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
+
+        let mut pages = 0;
+        let usage = bucket_usage_with_progress(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            |_| pages += 1
+        ).await?;
+
+        assert_eq!(pages, 1);
+
+        // test-file.txt is hidden, so its only upload is an old version.
+        assert_eq!(usage.current_files(), 2);
+        assert_eq!(usage.current_bytes(), 14);
+        assert_eq!(usage.old_files(), 1);
+        assert_eq!(usage.old_bytes(), 10);
+        assert_eq!(usage.hide_markers(), 1);
+        assert_eq!(usage.files(), 3);
+        assert_eq!(usage.bytes(), 24);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn delete_bucket_recursive_dry_run() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
+
+        let files = delete_bucket_recursive(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            BypassGovernance::No,
+            true
+        ).await?;
+
+        assert_eq!(files.len(), 4);
+
+        match delete_bucket_recursive(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            BypassGovernance::No,
+            false
+        ).await.unwrap_err() {
+            Error::Unauthorized(Capability::DeleteFiles) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn delete_bucket_recursive_deletes_files_and_bucket()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/delete_bucket_recursive.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![
+            Capability::ListFiles, Capability::DeleteFiles,
+            Capability::DeleteBuckets,
+        ]).await;
+
+        // Cancelling the unfinished large file requires WriteFiles.
+        match delete_bucket_recursive(
+            &mut auth,
+            Some("1df2dee6ab62f7f577c70e1a"),
+            BypassGovernance::No,
+            false
+        ).await.unwrap_err() {
+            Error::Unauthorized(Capability::WriteFiles) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/delete_bucket_recursive.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![
+            Capability::ListFiles, Capability::DeleteFiles,
+            Capability::DeleteBuckets, Capability::WriteFiles,
+        ]).await;
+
+        let files = delete_bucket_recursive(
+            &mut auth,
+            Some("1df2dee6ab62f7f577c70e1a"),
+            BypassGovernance::No,
+            false
+        ).await?;
+
+        let names: Vec<_> = files.iter().map(|f| f.file_name.as_str())
+            .collect();
+        assert_eq!(names, ["big.bin", "notes.txt", "notes.txt"]);
+
+        // The second deletion fails, so the bucket is not deleted; the
+        // recording has no request to delete it.
+        match delete_bucket_recursive(
+            &mut auth,
+            Some("3ac4b1f6c7e8d9a0b1c2d3e4"),
+            BypassGovernance::No,
+            false
+        ).await.unwrap_err() {
+            Error::B2(e) => assert_eq!(e.code_str(), "access_denied"),
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn wait_for_bucket_backs_off_until_timeout() -> anyhow::Result<()> {
+        use std::time::Duration;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListBuckets])
+            .await;
+
+        let mut sleeps = vec![];
+
+        let bucket = wait_for_bucket(
+            &mut auth,
+            "testing-b2-client",
+            Duration::from_secs(1),
+            Duration::from_secs(10),
+            |d| { sleeps.push(d); async {} }
+        ).await?;
+
+        assert_eq!(bucket.name(), "testing-b2-client");
+        assert!(sleeps.is_empty());
+
+        let res = wait_for_bucket(
+            &mut auth,
+            "missing-b2-client",
+            Duration::from_secs(1),
+            Duration::from_secs(10),
+            |d| { sleeps.push(d); async {} }
+        ).await;
+
+        assert!(matches!(res, Err(Error::Timeout)));
+        assert_eq!(sleeps, [1, 2, 4, 3].map(Duration::from_secs));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn wait_for_bucket_rejects_zero_interval() -> anyhow::Result<()> {
+        use std::time::Duration;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListBuckets])
+            .await;
+
+        let mut sleeps = vec![];
+
+        let res = wait_for_bucket(
+            &mut auth,
+            "missing-b2-client",
+            Duration::ZERO,
+            Duration::from_secs(10),
+            |d| { sleeps.push(d); async {} }
+        ).await;
+
+        assert!(matches!(res,
+            Err(Error::Validation(ValidationError::OutOfBounds(_)))
+        ));
+        assert!(sleeps.is_empty());
+
+        Ok(())
+    }
 
     #[async_std::test]
     async fn create_bucket_success() -> anyhow::Result<()> {
@@ -1714,6 +2514,152 @@ mod tests_mocked {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn list_buckets_defaults_to_restricted_bucket() -> anyhow::Result<()>
+    {
+        use crate::account::Capabilities;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        // A key restricted to a bucket with only the ListBuckets capability,
+        // as given to read-only tools.
+        let mut auth = create_auth_with(
+            client,
+            Capabilities::new(
+                vec![Capability::ListBuckets],
+                Some("8d625eb63be2775577c70e1a".into()),
+                None,
+                None
+            )
+        );
+
+        let buckets = list_buckets(&mut auth, ListBuckets::builder().build())
+            .await?;
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].bucket_id(), "8d625eb63be2775577c70e1a");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn authorize_and_list_buckets_with_restricted_key()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/authorize_list_buckets.yaml",
+            None, None
+        ).await?;
+
+        let (auth, buckets) = authorize_and_list_buckets(
+            client,
+            "some-key-id",
+            "some-key"
+        ).await?;
+
+        assert_eq!(
+            auth.capabilities().bucket_id().map(String::as_str),
+            Some("8d625eb63be2775577c70e1a")
+        );
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].name(), "testing-b2-client");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn get_bucket_by_name_distinguishes_lookups() -> anyhow::Result<()> {
+        use crate::account::Capabilities;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client.clone(),
+            vec![Capability::ListBuckets]
+        ).await;
+
+        match get_bucket_by_name(&mut auth, "testing-b2-client").await? {
+            BucketLookup::Found(b) => assert_eq!(b.name(), "testing-b2-client"),
+            l => panic!("Unexpected lookup: {:?}", l),
+        }
+
+        assert!(matches!(
+            get_bucket_by_name(&mut auth, "missing-b2-client").await?,
+            BucketLookup::NotFound
+        ));
+
+        let mut auth = create_auth_with(
+            client,
+            Capabilities::new(
+                vec![Capability::ListBuckets],
+                Some("8d625eb63be2775577c70e1a".into()),
+                Some("testing-b2-client".into()),
+                None
+            )
+        );
+
+        assert!(matches!(
+            get_bucket_by_name(&mut auth, "missing-b2-client").await?,
+            BucketLookup::Forbidden
+        ));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn get_bucket_by_id_distinguishes_lookups() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListBuckets])
+            .await;
+
+        match get_bucket_by_id(&mut auth, "8d625eb63be2775577c70e1a").await? {
+            BucketLookup::Found(b) => assert_eq!(b.name(), "testing-b2-client"),
+            l => panic!("Unexpected lookup: {:?}", l),
+        }
+
+        assert!(matches!(
+            get_bucket_by_id(&mut auth, "0d625eb63be2775577c70e1a").await?,
+            BucketLookup::NotFound
+        ));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_bucket_index() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListBuckets])
+            .await;
+
+        let buckets = bucket_index(&mut auth).await?;
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(
+            buckets["testing-b2-client"].bucket_id(),
+            "8d625eb63be2775577c70e1a"
+        );
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn update_bucket_success() -> anyhow::Result<()> {
         // To run this against the B2 API the bucket_id below needs to be
@@ -1898,7 +2844,83 @@ mod tests {
             "revision": 2,
         });
 
-        let _: Bucket = from_value(info).unwrap();
+        let bucket: Bucket = from_value(info).unwrap();
+
+        assert_eq!(bucket.file_lock_configuration().lock_is_enabled(),
+            Some(false));
+        assert_eq!(
+            bucket.default_server_side_encryption(),
+            Some(&ServerSideEncryption::NoEncryption)
+        );
+    }
+
+    #[test]
+    fn deserialize_bucket_without_read_permissions() {
+        let info = json!({
+            "accountId": "abcdefg",
+            "bucketId": "hijklmno",
+            "bucketInfo": {},
+            "bucketName": "some-bucket-name",
+            "bucketType": "allPrivate",
+            "corsRules": [],
+            "defaultServerSideEncryption": {
+                "isClientAuthorizedToRead": false,
+            },
+            "fileLockConfiguration": {
+                "isClientAuthorizedToRead": false,
+                "value": null,
+            },
+            "lifecycleRules": [],
+            "options": ["s3"],
+            "revision": 2,
+        });
+
+        let bucket: Bucket = from_value(info).unwrap();
+
+        assert!(! bucket.file_lock_configuration().can_read());
+        assert!(bucket.file_lock_configuration().lock_is_enabled().is_none());
+        assert!(bucket.retention_policy().is_none());
+        assert!(bucket.default_server_side_encryption().is_none());
+    }
+
+    #[test]
+    fn deserialize_bucket_with_missing_settings() {
+        let info = json!({
+            "accountId": "abcdefg",
+            "bucketId": "hijklmno",
+            "bucketName": "some-bucket-name",
+            "bucketType": "allPrivate",
+            "revision": 2,
+        });
+
+        let bucket: Bucket = from_value(info).unwrap();
+
+        assert!(bucket.cors_rules().is_empty());
+        assert!(bucket.lifecycle_rules().is_empty());
+        assert!(! bucket.file_lock_configuration().can_read());
+        assert!(bucket.default_server_side_encryption().is_none());
+    }
+
+    #[test]
+    fn deserialize_file_lock_configuration_with_retention() {
+        let config: FileLockConfiguration = from_value(json!({
+            "isClientAuthorizedToRead": true,
+            "value": {
+                "defaultRetention": {
+                    "mode": "governance",
+                    "period": {
+                        "duration": 7,
+                        "unit": "days",
+                    },
+                },
+                "isFileLockEnabled": true,
+            },
+        })).unwrap();
+
+        let policy = config.retention_policy().unwrap();
+
+        assert_eq!(config.lock_is_enabled(), Some(true));
+        assert_eq!(policy.period(), Some(chrono::Duration::days(7)));
     }
 
     #[test]
@@ -1945,5 +2967,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn update_bucket_omits_unchanged_fields() -> anyhow::Result<()> {
+        let req = UpdateBucket::builder()
+            .bucket_id("some-bucket")
+            .build()?;
+
+        assert_eq!(to_value(req)?, json!({
+            "accountId": null,
+            "bucketId": "some-bucket",
+        }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_bucket_clears_fields() -> anyhow::Result<()> {
+        let req = UpdateBucket::builder()
+            .bucket_id("some-bucket")
+            .clear_bucket_info()
+            .clear_cors_rules()
+            .clear_lifecycle_rules()
+            .build()?;
+
+        assert_eq!(to_value(req)?, json!({
+            "accountId": null,
+            "bucketId": "some-bucket",
+            "bucketInfo": {},
+            "corsRules": [],
+            "lifecycleRules": [],
+        }));
+
+        let req = UpdateBucket::builder()
+            .bucket_id("some-bucket")
+            .clear_bucket_info()
+            .cache_control(CacheControl::new())
+            .build()?;
+
+        assert_eq!(to_value(req)?, json!({
+            "accountId": null,
+            "bucketId": "some-bucket",
+            "bucketInfo": { "Cache-Control": "" },
+        }));
+
+        Ok(())
+    }
+
     // TODO: Test CorsRuleBuilder with allowed headers, etc.
 }
@@ -15,9 +15,10 @@
 use std::{
     collections::HashMap,
     path::PathBuf,
+    time::Duration,
 };
 
-use crate::error::ValidationError;
+use crate::{error::ValidationError, metrics::RetryAttempt};
 
 #[cfg(feature = "with_surf")]
 pub use surf_client::SurfClient;
@@ -28,10 +29,22 @@ pub use hyper_client::HyperClient;
 #[cfg(feature = "with_isahc")]
 pub use isahc_client::IsahcClient;
 
+#[cfg(all(feature = "with_fetch", target_arch = "wasm32"))]
+pub use fetch_client::{FetchClient, FetchError};
+
 /// A trait that wraps an HTTP client to send HTTP requests.
-#[async_trait::async_trait]
+///
+/// Responses are returned as raw bytes; API functions that expect JSON
+/// deserialize the response themselves, while download functions return the
+/// bytes unchanged.
+///
+/// On `wasm32` targets the futures returned by the trait are not required to
+/// be `Send`; implement the trait with `#[async_trait::async_trait(?Send)]`
+/// there.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 pub trait HttpClient
-    where Self: Default + Clone + Sized,
+    where Self: Default + Clone + Sized + MaybeSend,
 {
     /// The HTTP client's Error type.
     type Error;
@@ -56,12 +69,90 @@ pub trait HttpClient
     /// Use the given [serde_json::Value] as the request's body.
     fn with_body_json(&mut self, body: serde_json::Value) -> &mut Self;
     /// Read the provided path as the request's body.
+    ///
+    /// Implementations should stream the file's contents rather than read the
+    /// entire file into memory when possible. The surf, hyper, and isahc
+    /// clients do so; the fetch client has no filesystem access.
     fn read_body_from_file(&mut self, path: impl Into<PathBuf>) -> &mut Self;
 
     /// Set the User-Agent header value to send with requests.
     fn user_agent(&mut self, user_agent_string: impl Into<String>)
     -> Result<&mut Self, ValidationError>;
 
+    /// The User-Agent header value sent with requests.
+    ///
+    /// The default implementation returns `None`, meaning the value is
+    /// unknown.
+    fn user_agent_string(&self) -> Option<&str> {
+        None
+    }
+
+    /// Limit the size of the response body to `limit` bytes.
+    ///
+    /// If the response body is larger than the limit, sending the request
+    /// should fail with [Error::ResponseTooLarge](crate::error::Error) rather
+    /// than reading the full body. The limit only applies to the current
+    /// request.
+    ///
+    /// The default implementation ignores the limit.
+    fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+        let _ = limit;
+        self
+    }
+
+    /// Read the response body of the current request into `buf`.
+    ///
+    /// The buffer is cleared and returned as the response body by
+    /// [send](Self::send), [send_keep_headers](Self::send_keep_headers), or
+    /// [send_keep_status](Self::send_keep_status), so that reusing one buffer
+    /// for many requests avoids allocating a new one for each response. The
+    /// buffer only applies to the current request.
+    ///
+    /// The default implementation ignores the buffer.
+    fn with_response_buffer(&mut self, buf: Vec<u8>) -> &mut Self {
+        let _ = buf;
+        self
+    }
+
+    /// Set the kind of the current request, which determines the
+    /// [Timeouts] to use for it.
+    ///
+    /// Requests are [RequestKind::Api] requests unless set otherwise. The kind
+    /// only applies to the current request.
+    ///
+    /// The default implementation ignores the request kind.
+    fn with_request_kind(&mut self, kind: RequestKind) -> &mut Self {
+        let _ = kind;
+        self
+    }
+
+    /// Send an `Expect: 100-continue` header with the current request.
+    ///
+    /// The server may then reject the request, e.g. for an expired upload
+    /// authorization, before the client sends the body. If the server responds
+    /// with `417 Expectation Failed`, implementations should send the request
+    /// again without the header. The expectation only applies to the current
+    /// request.
+    ///
+    /// The default implementation ignores the expectation and sends the body
+    /// immediately.
+    fn with_expect_continue(&mut self) -> &mut Self {
+        self
+    }
+
+    /// Report that a failed request will be retried after a delay.
+    ///
+    /// This is called by the functions that retry requests, such as
+    /// [authorize_account_with_retry](crate::authorize_account_with_retry),
+    /// before waiting to send the request again.
+    ///
+    /// The default implementation does nothing; a
+    /// [MeteredClient](crate::metrics::MeteredClient) reports the retry to
+    /// its [Metrics](crate::metrics::Metrics).
+    fn on_retry(&self, retry: &RetryAttempt<'_>) {
+        let _ = retry;
+    }
+
     /// Send the previously-constructed request and return a response.
     async fn send(&mut self) -> Result<Vec<u8>, Self::Error>;
 
@@ -69,11 +160,231 @@ pub trait HttpClient
     /// returned HTTP headers.
     async fn send_keep_headers(&mut self)
     -> Result<(Vec<u8>, HeaderMap), Self::Error>;
+
+    /// Send the previously-constructed request and return a response with its
+    /// HTTP status code and headers.
+    ///
+    /// An implementation that cannot determine the status code may return
+    /// `None` for it.
+    ///
+    /// The default implementation calls [send_keep_headers] and returns `None`
+    /// for the status code.
+    ///
+    /// [send_keep_headers]: HttpClient::send_keep_headers
+    async fn send_keep_status(&mut self)
+    -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+        let (body, headers) = self.send_keep_headers().await?;
+        Ok((None, body, headers))
+    }
 }
 
+/// A `Send` bound on targets where [HttpClient]'s futures must be `Send`.
+///
+/// This is implemented for every `Send` type, and on `wasm32` targets for
+/// every type.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+
+/// A `Send` bound on targets where [HttpClient]'s futures must be `Send`.
+///
+/// This is implemented for every `Send` type, and on `wasm32` targets for
+/// every type.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
 // TODO: Use http_types::{HeaderName, HeaderValue} instead of Strings?
 pub type HeaderMap = HashMap<String, String>;
 
+/// The status code, body, and (if requested) headers of a response, as
+/// returned by the backends' `send_impl` functions.
+#[allow(dead_code)]
+type RawResponse = (u16, Vec<u8>, Option<HeaderMap>);
+
+/// The kind of request being sent.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum RequestKind {
+    /// A B2 API call with a small request and response.
+    #[default]
+    Api,
+    /// A request that sends or receives file data, such as an upload,
+    /// download, or server-side copy.
+    Transfer,
+}
+
+/// Timeouts for sending a request and receiving its response.
+///
+/// A value of `None` disables the timeout.
+///
+/// The `SurfClient` and `HyperClient` consider a connection established once
+/// they begin to send the request's body. They cannot tell when a request
+/// without a body has been sent, so they allow the `connect` and `idle`
+/// timeouts combined for its response's headers to arrive.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Timeouts {
+    /// The maximum time to spend establishing a connection to the server.
+    pub connect: Option<Duration>,
+    /// The maximum time to wait for more of the request body to be sent, for
+    /// the response's headers to arrive after the request was sent, or for
+    /// more of the response body to arrive.
+    pub idle: Option<Duration>,
+    /// The maximum time to spend on the request, from connecting to
+    /// receiving the full response.
+    pub total: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Timeouts suitable for B2 API calls: a ten second connect timeout, a
+    /// 30 second idle timeout, and a two minute total timeout.
+    pub const fn api() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(10)),
+            idle: Some(Duration::from_secs(30)),
+            total: Some(Duration::from_secs(120)),
+        }
+    }
+
+    /// Timeouts suitable for uploads and downloads: a ten second connect
+    /// timeout, a one minute idle timeout, and no total timeout, so that slow
+    /// transfers may continue as long as data is moving.
+    pub const fn transfer() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(10)),
+            idle: Some(Duration::from_secs(60)),
+            total: None,
+        }
+    }
+}
+
+/// The [Timeouts] a client uses for each [RequestKind].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TimeoutConfig {
+    /// The timeouts for [RequestKind::Api] requests.
+    pub api: Timeouts,
+    /// The timeouts for [RequestKind::Transfer] requests.
+    pub transfer: Timeouts,
+}
+
+impl Default for TimeoutConfig {
+    /// Use [Timeouts::api] and [Timeouts::transfer].
+    fn default() -> Self {
+        Self {
+            api: Timeouts::api(),
+            transfer: Timeouts::transfer(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    /// Get the timeouts to use for the given request kind.
+    pub fn for_kind(&self, kind: RequestKind) -> Timeouts {
+        match kind {
+            RequestKind::Api => self.api,
+            RequestKind::Transfer => self.transfer,
+        }
+    }
+}
+
+/// When a request last made progress, used by the backends that apply the
+/// connect and idle timeouts themselves until the response's headers arrive.
+///
+/// Clients only read a request's body once they have connected to the server,
+/// so the first read marks the end of the connect phase. Each later read, and
+/// the last one until the response arrives, must happen within the idle
+/// timeout.
+#[cfg(any(feature = "with_surf", feature = "with_hyper"))]
+#[derive(Debug, Clone)]
+struct Progress {
+    started: std::time::Instant,
+    last: std::sync::Arc<std::sync::Mutex<Option<std::time::Instant>>>,
+    has_body: bool,
+    timeouts: Timeouts,
+}
+
+#[cfg(any(feature = "with_surf", feature = "with_hyper"))]
+impl Progress {
+    fn new(timeouts: Timeouts, has_body: bool) -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            last: Default::default(),
+            has_body,
+            timeouts,
+        }
+    }
+
+    /// Record that more of the request's body was read to be sent.
+    fn record(&self) {
+        *self.last.lock().unwrap() = Some(std::time::Instant::now());
+    }
+
+    /// The time by which the request must next make progress, if any.
+    fn deadline(&self) -> Option<std::time::Instant> {
+        let Timeouts { connect, idle, .. } = self.timeouts;
+
+        match *self.last.lock().unwrap() {
+            Some(last) => idle.map(|idle| last + idle),
+            None if self.has_body => connect.map(|c| self.started + c),
+            // Nothing marks the end of the connect phase, so we wait for both
+            // the connection and the response.
+            None => match (connect, idle) {
+                (None, None) => None,
+                (connect, idle) => Some(self.started
+                    + connect.unwrap_or_default()
+                    + idle.unwrap_or_default()),
+            },
+        }
+    }
+
+    /// Wait for `fut`, or return `None` if the request stops making progress.
+    ///
+    /// `sleep` is the runtime's function to wait for a duration.
+    async fn watch<F, S, SF>(&self, fut: F, mut sleep: S) -> Option<F::Output>
+        where F: std::future::Future,
+              S: FnMut(Duration) -> SF,
+              SF: std::future::Future<Output = ()>,
+    {
+        use std::task::Poll;
+
+        let mut fut = Box::pin(fut);
+
+        loop {
+            let now = std::time::Instant::now();
+
+            // Before the body is first read there may be no deadline yet; we
+            // check again once the idle timeout could have started.
+            let wait = match self.deadline() {
+                Some(deadline) if deadline <= now => return None,
+                Some(deadline) => deadline - now,
+                None if self.has_body && self.last.lock().unwrap().is_none() =>
+                    match self.timeouts.idle {
+                        Some(idle) => idle,
+                        None => return Some(fut.await),
+                    },
+                None => return Some(fut.await),
+            };
+
+            let mut sleep = Box::pin(sleep(wait));
+
+            let res = std::future::poll_fn(|cx| {
+                if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                    return Poll::Ready(Some(res));
+                }
+
+                sleep.as_mut().poll(cx).map(|()| None)
+            }).await;
+
+            if res.is_some() {
+                return res;
+            }
+        }
+    }
+}
+
 /// Generate a standard User-Agent string for HTTP client backends.
 ///
 /// This is only useful if you either:
@@ -118,6 +429,52 @@ macro_rules! default_user_agent {
 }
 pub use default_user_agent;
 
+/// An [HttpClient] that fails every request, for unit tests that need a client
+/// type but make no requests; unlike the backends, it is always available.
+#[cfg(test)]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct NoClient;
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl HttpClient for NoClient {
+    type Error = crate::error::Error<&'static str>;
+
+    fn get(&mut self, _url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> { Ok(self) }
+    fn head(&mut self, _url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> { Ok(self) }
+    fn post(&mut self, _url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> { Ok(self) }
+
+    fn with_header<S: AsRef<str>>(&mut self, _name: S, _value: S)
+    -> Result<&mut Self, ValidationError> { Ok(self) }
+    fn with_body(&mut self, _data: impl Into<Vec<u8>>) -> &mut Self { self }
+    fn with_body_json(&mut self, _body: serde_json::Value) -> &mut Self {
+        self
+    }
+    fn read_body_from_file(&mut self, _path: impl Into<PathBuf>) -> &mut Self {
+        self
+    }
+
+    fn user_agent(&mut self, _user_agent_string: impl Into<String>)
+    -> Result<&mut Self, ValidationError> { Ok(self) }
+
+    async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
+        Err(crate::error::Error::Client("NoClient sends no requests"))
+    }
+
+    async fn send_keep_headers(&mut self)
+    -> Result<(Vec<u8>, HeaderMap), Self::Error> {
+        Err(crate::error::Error::Client("NoClient sends no requests"))
+    }
+
+    async fn send_keep_status(&mut self)
+    -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+        Err(crate::error::Error::Client("NoClient sends no requests"))
+    }
+}
+
 #[cfg(feature = "with_surf")]
 mod surf_client {
     use std::path::PathBuf;
@@ -135,6 +492,11 @@ mod surf_client {
         req: Option<Request>,
         body: Option<Body>,
         user_agent: String,
+        response_limit: Option<u64>,
+        timeouts: TimeoutConfig,
+        request_kind: RequestKind,
+        expect_continue: bool,
+        response_buffer: Option<Vec<u8>>,
     }
 
     impl Default for SurfClient {
@@ -145,6 +507,11 @@ mod surf_client {
                 req: None,
                 body: None,
                 user_agent: default_user_agent!("surf"),
+                response_limit: None,
+                timeouts: TimeoutConfig::default(),
+                request_kind: RequestKind::default(),
+                expect_continue: false,
+                response_buffer: None,
             }
         }
     }
@@ -169,29 +536,142 @@ mod surf_client {
             self
         }
 
+        /// Add a [surf::middleware::Middleware] to the client, such as for
+        /// logging or metrics.
+        ///
+        /// Middleware runs in the order in which it is added, before the
+        /// request is sent.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the client has been cloned or used to send a request;
+        /// add all middleware when creating the client.
+        pub fn with_middleware(
+            mut self,
+            middleware: impl surf::middleware::Middleware
+        ) -> Self {
+            self.client = self.client.with(middleware);
+            self
+        }
+
+        /// Use the provided timeouts instead of the defaults.
+        pub fn with_timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+            self.timeouts = timeouts;
+            self
+        }
+
         async fn send_impl(&mut self, keep_headers: bool)
-        -> Result<(Vec<u8>, Option<HeaderMap>), <Self as HttpClient>::Error> {
-            if let Some(mut req) = self.req.to_owned() {
-                if let Some(body) = &self.body {
-                    match body {
-                        Body::Json(val) => req.body_json(val)?,
-                        Body::Bytes(data) => req.body_bytes(data),
-                        Body::File(path) =>
-                            req.set_body(surf::Body::from_file(path).await?),
+        -> Result<RawResponse, <Self as HttpClient>::Error> {
+            let timeouts = self.timeouts.for_kind(self.request_kind);
+
+            let res = match timeouts.total {
+                Some(total) => async_std::future::timeout(
+                    total,
+                    self.send_with_idle_timeout(keep_headers, timeouts.idle)
+                ).await.unwrap_or(Err(Error::Timeout)),
+                None => self.send_with_idle_timeout(keep_headers, timeouts.idle)
+                    .await,
+            };
+
+            self.clear_request();
+            res
+        }
+
+        /// Reset the state of the current request, whether or not it was sent
+        /// successfully, so that none of it applies to the next request.
+        fn clear_request(&mut self) {
+            self.req = None;
+            self.body = None;
+            self.response_limit = None;
+            self.request_kind = RequestKind::default();
+            self.expect_continue = false;
+            self.response_buffer = None;
+        }
+
+        async fn send_with_idle_timeout(
+            &mut self,
+            keep_headers: bool,
+            idle: Option<std::time::Duration>,
+        ) -> Result<RawResponse, <Self as HttpClient>::Error>
+        {
+            use async_std::io::ReadExt as _;
+
+            let expect_continue = std::mem::take(&mut self.expect_continue);
+            let buffer = self.response_buffer.take();
+
+            if self.req.is_some() {
+                let mut res = self.send_request(expect_continue).await?;
+
+                if expect_continue
+                    && res.status() == surf::StatusCode::ExpectationFailed
+                {
+                    // The server or a proxy doesn't support the expectation,
+                    // so we send the body without waiting for it.
+                    res = self.send_request(false).await?;
+                }
+
+                let status = res.status() as u16;
+                let limit = self.response_limit.take();
+
+                if let Some(limit) = limit {
+                    if res.len().map(|l| l as u64 > limit).unwrap_or(false) {
+                        return Err(Error::ResponseTooLarge(limit));
                     }
                 }
 
-                req.insert_header("User-Agent", &self.user_agent);
+                let mut reader = res.take_body().into_reader();
+                let reused = buffer.is_some();
+
+                let mut body = buffer.unwrap_or_default();
+                body.clear();
+
+                loop {
+                    // We read directly into the body so that a reused buffer
+                    // doesn't need to allocate.
+                    let start = body.len();
+                    body.resize(start + 64 * 1024, 0);
 
-                let mut res = self.client.send(req).await?;
-                let body = res.body_bytes().await?;
+                    let read = reader.read(&mut body[start..]);
+
+                    let len = match idle {
+                        Some(idle) => async_std::io::timeout(idle, read).await
+                            .map_err(|e| match e.kind() {
+                                std::io::ErrorKind::TimedOut => Error::Timeout,
+                                _ => Error::IO(e),
+                            })?,
+                        None => read.await?,
+                    };
+
+                    body.truncate(start + len);
+
+                    if len == 0 {
+                        break;
+                    }
+
+                    if let Some(limit) = limit {
+                        if body.len() as u64 > limit {
+                            return Err(Error::ResponseTooLarge(limit));
+                        }
+                    }
+                }
+
+                if ! reused {
+                    body.shrink_to_fit();
+                }
 
                 let headers = if keep_headers {
                     let headers: &surf::http::Headers = res.as_ref();
                     let mut ret = HeaderMap::new();
 
                     for (k, v) in headers.iter() {
-                        ret.insert(k.to_string(), v.to_string());
+                        // HeaderValues' Display implementation formats the
+                        // values as a list, so we join them ourselves.
+                        let v = v.iter()
+                            .map(|v| v.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+
+                        ret.insert(k.to_string(), v);
                     }
 
                     Some(ret)
@@ -199,13 +679,105 @@ mod surf_client {
                     None
                 };
 
-                self.req = None;
-
-                Ok((body, headers))
+                Ok((status, body, headers))
             } else {
                 Err(Error::NoRequest)
             }
         }
+
+        /// Send the current request with its body.
+        ///
+        /// The request is not cleared, so it may be sent again.
+        async fn send_request(&self, expect_continue: bool)
+        -> Result<surf::Response, <Self as HttpClient>::Error> {
+            use async_std::io::Cursor;
+
+            let mut req = self.req.to_owned().ok_or(Error::NoRequest)?;
+            let timeouts = self.timeouts.for_kind(self.request_kind);
+
+            // We read the body to the client ourselves so that we can see
+            // whether the request is still being sent.
+            let (body, len) = match &self.body {
+                Some(Body::Json(val)) => {
+                    let data = serde_json::to_vec(val)?;
+                    let len = data.len();
+                    (Some(ProgressBody::Bytes(Cursor::new(data))), len)
+                },
+                Some(Body::Bytes(data)) => (
+                    Some(ProgressBody::Bytes(Cursor::new(data.clone()))),
+                    data.len()
+                ),
+                Some(Body::File(path)) => {
+                    let file = async_std::fs::File::open(path).await?;
+                    let len = file.metadata().await?.len() as usize;
+                    (Some(ProgressBody::File(file)), len)
+                },
+                None => (None, 0),
+            };
+
+            let progress = Progress::new(timeouts, len > 0);
+
+            if let Some(body) = body {
+                let reader = ProgressReader {
+                    body,
+                    progress: progress.clone(),
+                };
+
+                let mut body = surf::Body::from_reader(
+                    async_std::io::BufReader::new(reader),
+                    Some(len)
+                );
+
+                if let Some(Body::Json(_)) = &self.body {
+                    body.set_mime(surf::http::mime::JSON);
+                }
+
+                req.set_body(body);
+            }
+
+            req.insert_header("User-Agent", &self.user_agent);
+
+            if expect_continue {
+                req.insert_header("Expect", "100-continue");
+            }
+
+            progress.watch(self.client.send(req), async_std::task::sleep).await
+                .ok_or(Error::Timeout)?
+                .map_err(Error::from)
+        }
+    }
+
+    /// The source of a request body for a [ProgressReader].
+    enum ProgressBody {
+        Bytes(async_std::io::Cursor<Vec<u8>>),
+        File(async_std::fs::File),
+    }
+
+    /// Reads a request body to the client, recording its [Progress].
+    struct ProgressReader {
+        body: ProgressBody,
+        progress: Progress,
+    }
+
+    impl async_std::io::Read for ProgressReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            use std::pin::Pin;
+
+            let res = match &mut self.body {
+                ProgressBody::Bytes(data) => Pin::new(data).poll_read(cx, buf),
+                ProgressBody::File(file) => Pin::new(file).poll_read(cx, buf),
+            };
+
+            if let std::task::Poll::Ready(Ok(_)) = res {
+                self.progress.record();
+            }
+
+            res
+        }
     }
 
     macro_rules! gen_method_func {
@@ -284,14 +856,45 @@ mod surf_client {
             }
         }
 
+        fn user_agent_string(&self) -> Option<&str> {
+            Some(&self.user_agent)
+        }
+
+        fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+            self.response_limit = Some(limit);
+            self
+        }
+
+        fn with_response_buffer(&mut self, buf: Vec<u8>) -> &mut Self {
+            self.response_buffer = Some(buf);
+            self
+        }
+
+        fn with_request_kind(&mut self, kind: RequestKind) -> &mut Self {
+            self.request_kind = kind;
+            self
+        }
+
+        /// Send an `Expect: 100-continue` header with the current request.
+        ///
+        /// If the server responds with `417 Expectation Failed`, the request
+        /// is sent again without the header.
+        fn with_expect_continue(&mut self) -> &mut Self {
+            self.expect_continue = true;
+            self
+        }
+
         /// Send the previously-constructed request and return a response.
         ///
         /// # Errors
         ///
         /// * If a request has not been created, returns [Error::NoRequest].
-        /// * Returns any underlying HTTP client errors in [Error::Client].
+        /// * If the request exceeds its timeouts, returns [Error::Timeout].
+        /// * If the server cannot be reached, returns [Error::Transport]; see
+        ///   its documentation for the failures that are recognized.
+        /// * Returns any other HTTP client errors in [Error::Client].
         async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
-            self.send_impl(false).await.map(|v| v.0)
+            self.send_impl(false).await.map(|v| v.1)
         }
 
         /// Send the previously-constructed request and return a response with
@@ -300,10 +903,23 @@ mod surf_client {
         /// # Errors
         ///
         /// * If a request has not been created, returns [Error::NoRequest].
-        /// * Returns any underlying HTTP client errors in [Error::Client].
+        /// * If the request exceeds its timeouts, returns [Error::Timeout].
+        /// * If the server cannot be reached, returns [Error::Transport]; see
+        ///   its documentation for the failures that are recognized.
+        /// * Returns any other HTTP client errors in [Error::Client].
         async fn send_keep_headers(&mut self)
         -> Result<(Vec<u8>, HeaderMap), Self::Error> {
-            self.send_impl(true).await.map(|(r, m)| (r, m.unwrap()))
+            self.send_impl(true).await.map(|(_, r, m)| (r, m.unwrap()))
+        }
+
+        /// Send the previously-constructed request and return the response's
+        /// HTTP status code, body, and headers.
+        ///
+        /// See [send](Self::send) for the errors returned.
+        async fn send_keep_status(&mut self)
+        -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+            self.send_impl(true).await
+                .map(|(s, r, m)| (Some(s), r, m.unwrap()))
         }
     }
 }
@@ -330,6 +946,9 @@ mod hyper_client {
         headers: Vec<(HeaderName, HeaderValue)>,
         body: Option<Body>,
         user_agent: String,
+        response_limit: Option<u64>,
+        timeouts: TimeoutConfig,
+        request_kind: RequestKind,
     }
 
     impl Default for HyperClient {
@@ -346,6 +965,9 @@ mod hyper_client {
                 headers: vec![],
                 body: None,
                 user_agent: default_user_agent!("hyper"),
+                response_limit: None,
+                timeouts: TimeoutConfig::default(),
+                request_kind: RequestKind::default(),
             }
         }
     }
@@ -379,6 +1001,12 @@ mod hyper_client {
             self
         }
 
+        /// Use the provided timeouts instead of the defaults.
+        pub fn with_timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+            self.timeouts = timeouts;
+            self
+        }
+
         /// Use the provided [Bytes](hyper::body::Bytes) as the request's body.
         ///
         /// The `Bytes` type is cheaply cloneable, so this method should be
@@ -391,7 +1019,40 @@ mod hyper_client {
         }
 
         async fn send_impl(&mut self, keep_headers: bool)
-        -> Result<(Vec<u8>, Option<HeaderMap>), <Self as HttpClient>::Error> {
+        -> Result<RawResponse, <Self as HttpClient>::Error> {
+            let timeouts = self.timeouts.for_kind(self.request_kind);
+
+            let res = match timeouts.total {
+                Some(total) => tokio::time::timeout(
+                    total,
+                    self.send_with_timeouts(keep_headers, timeouts)
+                ).await.unwrap_or(Err(Error::Timeout)),
+                None => self.send_with_timeouts(keep_headers, timeouts).await,
+            };
+
+            self.clear_request();
+            res
+        }
+
+        /// Reset the state of the current request, whether or not it was sent
+        /// successfully, so that none of it applies to the next request.
+        fn clear_request(&mut self) {
+            self.method = None;
+            self.url = String::default();
+            self.headers.clear();
+            self.body = None;
+            self.response_limit = None;
+            self.request_kind = RequestKind::default();
+        }
+
+        async fn send_with_timeouts(
+            &mut self,
+            keep_headers: bool,
+            timeouts: Timeouts,
+        ) -> Result<RawResponse, <Self as HttpClient>::Error>
+        {
+            use hyper::body::HttpBody as _;
+
             if self.method.is_none() {
                 return Err(Error::NoRequest);
             }
@@ -406,26 +1067,77 @@ mod hyper_client {
 
             req = req.header("User-Agent", &self.user_agent);
 
-            let body = match &self.body {
-                Some(body) => match body {
-                    Body::Json(val) => hyper::Body::from(val.to_string()),
-                    Body::Bytes(data) => hyper::Body::from(data.clone()),
-                    Body::File(path) => {
-                        use tokio::{
-                            fs::File,
-                            io::AsyncReadExt as _,
-                        };
-
-                        // TODO: We might do better to create a stream over the
-                        // file and pass it to the hyper::Body.
-                        let mut file = File::open(path).await?;
-                        let mut buf = vec![];
-                        file.read_to_end(&mut buf).await?;
-
-                        hyper::Body::from(buf)
-                    },
+            let data = match &self.body {
+                Some(Body::Json(val)) =>
+                    Some(hyper::body::Bytes::from(val.to_string())),
+                Some(Body::Bytes(data)) => Some(data.clone()),
+                _ => None,
+            };
+
+            let has_body = match (&self.body, &data) {
+                (_, Some(data)) => ! data.is_empty(),
+                (Some(Body::File(path)), None) =>
+                    tokio::fs::metadata(path).await?.len() > 0,
+                _ => false,
+            };
+
+            let progress = Progress::new(timeouts, has_body);
+
+            // We pass the body to hyper in chunks so that we can see whether
+            // the request is still being sent.
+            let body = match (&self.body, data) {
+                (_, Some(data)) => {
+                    use futures_util::StreamExt as _;
+
+                    const CHUNK: usize = 64 * 1024;
+
+                    let has_length = self.headers.iter()
+                        .any(|(name, _)| name == hyper::header::CONTENT_LENGTH);
+
+                    if ! has_length {
+                        req = req.header(hyper::header::CONTENT_LENGTH,
+                            data.len());
+                    }
+
+                    let progress = progress.clone();
+                    let chunks = (0..data.len()).step_by(CHUNK)
+                        .map(move |start| Ok::<_, std::io::Error>(
+                            data.slice(start..(start + CHUNK).min(data.len()))
+                        ));
+
+                    hyper::Body::wrap_stream(futures_util::stream::iter(chunks)
+                        .inspect(move |_| progress.record()))
+                },
+                (Some(Body::File(path)), None) => {
+                    use futures_util::StreamExt as _;
+                    use tokio::{
+                        fs::File,
+                        io::AsyncReadExt as _,
+                    };
+
+                    let file = File::open(path).await?;
+                    let progress = progress.clone();
+
+                    let stream = futures_util::stream::unfold(file,
+                        |mut file| async move {
+                            let mut buf = vec![0; 64 * 1024];
+
+                            match file.read(&mut buf).await {
+                                Ok(0) => None,
+                                Ok(len) => {
+                                    buf.truncate(len);
+                                    Some((Ok(buf), file))
+                                },
+                                Err(e) => Some((Err(e), file)),
+                            }
+                        }
+                    );
+
+                    hyper::Body::wrap_stream(
+                        stream.inspect(move |_| progress.record())
+                    )
                 },
-                None => hyper::Body::empty(),
+                _ => hyper::Body::empty(),
             };
 
             let req = req.body(body).expect(concat!(
@@ -433,10 +1145,49 @@ mod hyper_client {
                 "improper validation"
             ));
 
-            let (mut parts, body) = self.client.request(req).await?
-                .into_parts();
+            let res = progress.watch(
+                self.client.request(req),
+                tokio::time::sleep
+            ).await.ok_or(Error::Timeout)?;
+
+            let (mut parts, body) = res?.into_parts();
+
+            let limit = self.response_limit.take();
+
+            if let Some(limit) = limit {
+                let content_length = parts.headers
+                    .get(hyper::header::CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok());
+
+                if content_length.map(|l| l > limit).unwrap_or(false) {
+                    return Err(Error::ResponseTooLarge(limit));
+                }
+            }
+
+            let mut body_stream = body;
+            let mut body = vec![];
+
+            loop {
+                let chunk = match timeouts.idle {
+                    Some(idle) => tokio::time::timeout(idle, body_stream.data())
+                        .await.map_err(|_| Error::Timeout)?,
+                    None => body_stream.data().await,
+                };
+
+                let chunk = match chunk {
+                    Some(chunk) => chunk?,
+                    None => break,
+                };
+
+                if let Some(limit) = limit {
+                    if (body.len() + chunk.len()) as u64 > limit {
+                        return Err(Error::ResponseTooLarge(limit));
+                    }
+                }
 
-            let body = hyper::body::to_bytes(body).await?.to_vec();
+                body.extend_from_slice(&chunk);
+            }
 
             let headers = if keep_headers {
                 let mut headers = HeaderMap::new();
@@ -460,12 +1211,7 @@ mod hyper_client {
                 None
             };
 
-            self.method = None;
-            self.url = String::default();
-            self.headers.clear();
-            self.body = None;
-
-            Ok((body, headers))
+            Ok((parts.status.as_u16(), body, headers))
         }
     }
 
@@ -523,19 +1269,45 @@ mod hyper_client {
             }
         }
 
+        fn user_agent_string(&self) -> Option<&str> {
+            Some(&self.user_agent)
+        }
+
+        fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+            self.response_limit = Some(limit);
+            self
+        }
+
+        fn with_request_kind(&mut self, kind: RequestKind) -> &mut Self {
+            self.request_kind = kind;
+            self
+        }
+
         /// Send the previously-constructed request and return a response.
         ///
         /// # Errors
         ///
         /// * If a request has not been created, returns [Error::NoRequest].
-        /// * Returns any underlying HTTP client errors in [Error::Client].
+        /// * If the request exceeds its timeouts, returns [Error::Timeout].
+        /// * If the server cannot be reached, returns [Error::Transport].
+        /// * Returns any other HTTP client errors in [Error::Client].
         async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
-            self.send_impl(false).await.map(|v| v.0)
+            self.send_impl(false).await.map(|v| v.1)
         }
 
         async fn send_keep_headers(&mut self)
         -> Result<(Vec<u8>, HeaderMap), Self::Error> {
-            self.send_impl(true).await.map(|(r, m)| (r, m.unwrap()))
+            self.send_impl(true).await.map(|(_, r, m)| (r, m.unwrap()))
+        }
+
+        /// Send the previously-constructed request and return the response's
+        /// HTTP status code, body, and headers.
+        ///
+        /// See [send](Self::send) for the errors returned.
+        async fn send_keep_status(&mut self)
+        -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+            self.send_impl(true).await
+                .map(|(s, r, m)| (Some(s), r, m.unwrap()))
         }
     }
 }
@@ -564,6 +1336,9 @@ mod isahc_client {
         user_agent: String,
         body: Option<Body>,
         headers: Vec<(HeaderName, HeaderValue)>,
+        response_limit: Option<u64>,
+        timeouts: TimeoutConfig,
+        request_kind: RequestKind,
     }
 
     impl Default for IsahcClient {
@@ -575,6 +1350,9 @@ mod isahc_client {
                 user_agent: default_user_agent!("isahc"),
                 body: None,
                 headers: Vec::new(),
+                response_limit: None,
+                timeouts: TimeoutConfig::default(),
+                request_kind: RequestKind::default(),
             }
         }
     }
@@ -582,8 +1360,8 @@ mod isahc_client {
     impl Clone for IsahcClient {
         /// Clone an `IsahcClient` object.
         ///
-        /// The client itself and the user-agent string are cloned. The current
-        /// request is not.
+        /// The client itself, the user-agent string, and the timeouts are
+        /// cloned. The current request is not.
         fn clone(&self) -> Self {
             Self {
                 client: self.client.clone(),
@@ -591,66 +1369,103 @@ mod isahc_client {
                 user_agent: self.user_agent.clone(),
                 body: None,
                 headers: Vec::new(),
+                response_limit: None,
+                timeouts: self.timeouts,
+                request_kind: RequestKind::default(),
             }
         }
     }
 
     impl IsahcClient {
+        /// Use the provided timeouts instead of the defaults.
+        ///
+        /// The idle timeout of [RequestKind::Api] requests aborts the request
+        /// if less than one byte per second is sent or received for its
+        /// duration. Isahc measures this over the whole request, including
+        /// while B2 processes an upload before responding, so the idle timeout
+        /// of [RequestKind::Transfer] requests is not supported.
+        pub fn with_timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+            self.timeouts = timeouts;
+            self
+        }
+
         async fn send_impl(&mut self, keep_headers: bool)
-        -> Result<(
-            Vec<u8>, Option<HeaderMap>),
-            <Self as super::HttpClient>::Error
-        > {
-            use futures_lite::AsyncReadExt as _;
+        -> Result<RawResponse, <Self as super::HttpClient>::Error> {
+            let res = self.send_request(keep_headers).await;
 
-            if let Some(mut req) = self.req.take() {
-                for (name, value) in &self.headers {
-                    req = req.header(name, value);
-                }
+            self.clear_request();
+            res
+        }
 
-                req = req.header("User-Agent", &self.user_agent);
+        /// Reset the state of the current request, whether or not it was sent
+        /// successfully, so that none of it applies to the next request.
+        fn clear_request(&mut self) {
+            self.req = None;
+            self.body = None;
+            self.headers.clear();
+            self.response_limit = None;
+            self.request_kind = RequestKind::default();
+        }
 
-                let body = if let Some(body) = self.body.take() {
-                    match body {
-                        Body::Bytes(bytes) => Some(bytes),
-                        Body::Json(json) => Some(serde_json::to_vec(&json)?),
-                        Body::File(path) => {
-                            // TODO: Use async_std?
-                            use std::{fs::File, io::Read as _};
+        async fn send_request(&mut self, keep_headers: bool)
+        -> Result<RawResponse, <Self as super::HttpClient>::Error> {
+            use futures_lite::AsyncReadExt as _;
+            use isahc::config::Configurable as _;
 
-                            let mut file = File::open(path)?;
-                            let mut buf: Vec<u8> = vec![];
-                            file.read_to_end(&mut buf)?;
+            if let Some(mut req) = self.req.take() {
+                for (name, value) in &self.headers {
+                    req = req.header(name, value);
+                }
 
-                            Some(buf)
-                        },
-                    }
-                } else {
-                    None
-                };
+                let timeouts = self.timeouts.for_kind(self.request_kind);
 
-                let (mut parts, body) = match body {
-                    Some(body) => self.client.send_async(req.body(body)?)
-                        .await?.into_parts(),
-                    None => self.client.send_async(
-                        req.body(isahc::AsyncBody::empty())?
-                    ).await?.into_parts(),
+                if let Some(timeout) = timeouts.connect {
+                    req = req.connect_timeout(timeout);
+                }
+                if let (Some(timeout), RequestKind::Api) =
+                    (timeouts.idle, self.request_kind)
+                {
+                    req = req.low_speed_timeout(1, timeout);
+                }
+                if let Some(timeout) = timeouts.total {
+                    req = req.timeout(timeout);
+                }
+
+                req = req.header("User-Agent", &self.user_agent);
+
+                let body = match self.body.take() {
+                    Some(Body::Bytes(bytes)) => isahc::AsyncBody::from(bytes),
+                    Some(Body::Json(json)) =>
+                        isahc::AsyncBody::from(serde_json::to_vec(&json)?),
+                    Some(Body::File(path)) => {
+                        use futures_lite::io::AssertAsync;
+
+                        // isahc reads the body from its own agent thread, so
+                        // the blocking reads do not stall the caller.
+                        let file = std::fs::File::open(path)?;
+                        let len = file.metadata()?.len();
+
+                        isahc::AsyncBody::from_reader_sized(
+                            AssertAsync::new(file),
+                            len
+                        )
+                    },
+                    None => isahc::AsyncBody::empty(),
                 };
 
+                let (parts, mut body) = self.client.send_async(req.body(body)?)
+                    .await?.into_parts();
+
                 let headers = if keep_headers {
                     let mut headers = HeaderMap::new();
 
                     headers.extend(
-                        parts.headers.drain()
-                        .filter(|(k, _)| k.is_some())
+                        parts.headers.iter()
                         .map(|(k, v)|
                             // TODO: Ensure that all possible header values from
                             // B2 are required to be valid strings on their
                             // side.
-                            (
-                                k.unwrap().to_string(),
-                                v.to_str().unwrap().to_owned()
-                            )
+                            (k.to_string(), v.to_str().unwrap().to_owned())
                         )
                     );
 
@@ -660,14 +1475,38 @@ mod isahc_client {
                 };
 
                 let mut buf = Vec::new();
-                body.bytes().read_to_end(&mut buf).await?;
 
-                // self.req and self.body had their values reset already; we
-                // only need to clear the list of headers and we're ready for
-                // the next request.
-                self.headers.clear();
+                // isahc reports a timeout while reading the body as an I/O
+                // error.
+                let timed_out = |e: std::io::Error| match e.kind() {
+                    std::io::ErrorKind::TimedOut => Error::Timeout,
+                    _ => Error::IO(e),
+                };
+
+                match self.response_limit {
+                    Some(limit) => {
+                        let content_length = parts.headers
+                            .get(isahc::http::header::CONTENT_LENGTH)
+                            .and_then(|v| v.to_str().ok())
+                            .and_then(|v| v.parse::<u64>().ok());
+
+                        if content_length.map(|l| l > limit).unwrap_or(false) {
+                            return Err(Error::ResponseTooLarge(limit));
+                        }
+
+                        body.take(limit.saturating_add(1))
+                            .read_to_end(&mut buf).await.map_err(timed_out)?;
+
+                        if buf.len() as u64 > limit {
+                            return Err(Error::ResponseTooLarge(limit));
+                        }
+                    },
+                    None => {
+                        body.read_to_end(&mut buf).await.map_err(timed_out)?;
+                    },
+                }
 
-                Ok((buf, headers))
+                Ok((parts.status.as_u16(), buf, headers))
             } else {
                 Err(Error::NoRequest)
             }
@@ -740,13 +1579,771 @@ mod isahc_client {
             }
         }
 
+        fn user_agent_string(&self) -> Option<&str> {
+            Some(&self.user_agent)
+        }
+
+        fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+            self.response_limit = Some(limit);
+            self
+        }
+
+        fn with_request_kind(&mut self, kind: RequestKind) -> &mut Self {
+            self.request_kind = kind;
+            self
+        }
+
+        async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
+            self.send_impl(false).await.map(|v| v.1)
+        }
+
+        async fn send_keep_headers(&mut self)
+        -> Result<(Vec<u8>, HeaderMap), Self::Error> {
+            self.send_impl(true).await.map(|(_, r, m)| (r, m.unwrap()))
+        }
+
+        /// Send the previously-constructed request and return the response's
+        /// HTTP status code, body, and headers.
+        ///
+        /// See [send](Self::send) for the errors returned.
+        async fn send_keep_status(&mut self)
+        -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+            self.send_impl(true).await
+                .map(|(s, r, m)| (Some(s), r, m.unwrap()))
+        }
+    }
+}
+
+#[cfg(all(feature = "with_fetch", target_arch = "wasm32"))]
+mod fetch_client {
+    use super::*;
+    use crate::error::Error;
+    use std::fmt;
+    use url::Url;
+    use wasm_bindgen::{JsCast as _, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+
+    /// An error from the browser's Fetch API.
+    #[derive(Debug, Clone)]
+    pub struct FetchError(String);
+
+    impl std::error::Error for FetchError {}
+
+    impl fmt::Display for FetchError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl From<JsValue> for FetchError {
+        fn from(v: JsValue) -> Self {
+            match v.as_string() {
+                Some(s) => Self(s),
+                None => Self(format!("{:?}", v)),
+            }
+        }
+    }
+
+    /// An [HttpClient] that uses the Fetch API of a web browser or web worker.
+    ///
+    /// Browsers do not permit setting the User-Agent header, so the value set
+    /// via [user_agent](HttpClient::user_agent) is not sent. There is no
+    /// filesystem access, so
+    /// [read_body_from_file](HttpClient::read_body_from_file) requests fail
+    /// with [Error::IO].
+    #[derive(Debug, Clone)]
+    pub struct FetchClient {
+        method: Option<&'static str>,
+        url: String,
+        headers: Vec<(String, String)>,
+        body: Option<Body>,
+        user_agent: String,
+        response_limit: Option<u64>,
+    }
+
+    impl Default for FetchClient {
+        /// Create a new `FetchClient`.
+        fn default() -> Self {
+            Self {
+                method: None,
+                url: String::default(),
+                headers: vec![],
+                body: None,
+                user_agent: default_user_agent!("fetch"),
+                response_limit: None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    enum Body {
+        Json(serde_json::Value),
+        Bytes(Vec<u8>),
+        // There is no filesystem to read from; sending the request will fail.
+        File,
+    }
+
+    macro_rules! gen_method_func {
+        ($func:ident, $method:literal) => {
+            fn $func(&mut self, url: impl AsRef<str>)
+            -> Result<&mut Self, ValidationError> {
+                let _url = Url::parse(url.as_ref())?;
+
+                self.method = Some($method);
+                self.url = String::from(url.as_ref());
+                Ok(self)
+            }
+        }
+    }
+
+    impl FetchClient {
+        async fn send_impl(&mut self, keep_headers: bool)
+        -> Result<RawResponse, <Self as HttpClient>::Error> {
+            let res = self.send_request(keep_headers).await;
+
+            self.clear_request();
+            res
+        }
+
+        /// Reset the state of the current request, whether or not it was sent
+        /// successfully, so that none of it applies to the next request.
+        fn clear_request(&mut self) {
+            self.method = None;
+            self.url = String::default();
+            self.headers.clear();
+            self.body = None;
+            self.response_limit = None;
+        }
+
+        async fn send_request(&mut self, keep_headers: bool)
+        -> Result<RawResponse, <Self as HttpClient>::Error> {
+            let method = self.method.take().ok_or(Error::NoRequest)?;
+            let limit = self.response_limit.take();
+
+            let init = web_sys::RequestInit::new();
+            init.set_method(method);
+
+            match self.body.take() {
+                Some(Body::Json(val)) =>
+                    init.set_body(&JsValue::from_str(&val.to_string())),
+                Some(Body::Bytes(data)) =>
+                    init.set_body(&js_sys::Uint8Array::from(&data[..])),
+                Some(Body::File) => {
+                    return Err(Error::IO(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "Files cannot be read by the fetch client"
+                    )));
+                },
+                None => {},
+            }
+
+            let req = web_sys::Request::new_with_str_and_init(&self.url, &init)
+                .map_err(FetchError::from)?;
+
+            for (name, value) in self.headers.drain(..) {
+                req.headers().set(&name, &value).map_err(FetchError::from)?;
+            }
+
+            let global = js_sys::global();
+
+            let promise = if let Some(window) =
+                global.dyn_ref::<web_sys::Window>()
+            {
+                window.fetch_with_request(&req)
+            } else if let Some(worker) =
+                global.dyn_ref::<web_sys::WorkerGlobalScope>()
+            {
+                worker.fetch_with_request(&req)
+            } else {
+                return Err(Error::Client(FetchError(
+                    "The Fetch API is not available".into()
+                )));
+            };
+
+            // The Fetch API only rejects the promise on network failures.
+            let res = JsFuture::from(promise).await
+                .map_err(|e| Error::Transport(FetchError::from(e)))?;
+            let res: web_sys::Response = res.dyn_into()
+                .map_err(FetchError::from)?;
+
+            if let Some(limit) = limit {
+                let content_length = res.headers().get("Content-Length")
+                    .map_err(FetchError::from)?
+                    .and_then(|v| v.parse::<u64>().ok());
+
+                if content_length.map(|l| l > limit).unwrap_or(false) {
+                    return Err(Error::ResponseTooLarge(limit));
+                }
+            }
+
+            let headers = if keep_headers {
+                let mut headers = HeaderMap::new();
+
+                let entries = js_sys::try_iter(&res.headers())
+                    .map_err(FetchError::from)?;
+
+                for entry in entries.into_iter().flatten() {
+                    let entry: js_sys::Array = entry.map_err(FetchError::from)?
+                        .unchecked_into();
+
+                    if let (Some(name), Some(value)) =
+                        (entry.get(0).as_string(), entry.get(1).as_string())
+                    {
+                        headers.insert(name, value);
+                    }
+                }
+
+                Some(headers)
+            } else {
+                None
+            };
+
+            let buf = res.array_buffer().map_err(FetchError::from)?;
+            let buf = JsFuture::from(buf).await.map_err(FetchError::from)?;
+            let mut body = js_sys::Uint8Array::new(&buf).to_vec();
+
+            if let Some(limit) = limit {
+                if body.len() as u64 > limit {
+                    return Err(Error::ResponseTooLarge(limit));
+                }
+            }
+
+            Ok((res.status(), body, headers))
+        }
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl HttpClient for FetchClient
+        where Self: Clone + Sized,
+    {
+        type Error = Error<FetchError>;
+
+        gen_method_func!(get, "GET");
+        gen_method_func!(head, "HEAD");
+        gen_method_func!(post, "POST");
+
+        fn with_header<S: AsRef<str>>(&mut self, name: S, value: S)
+        -> Result<&mut Self, ValidationError> {
+            self.headers.push(
+                (name.as_ref().to_owned(), value.as_ref().to_owned())
+            );
+
+            Ok(self)
+        }
+
+        fn with_body(&mut self, data: impl Into<Vec<u8>>) -> &mut Self {
+            self.body = Some(Body::Bytes(data.into()));
+            self
+        }
+
+        fn with_body_json(&mut self, body: serde_json::Value) -> &mut Self {
+            self.body = Some(Body::Json(body));
+            self
+        }
+
+        fn read_body_from_file(&mut self, _path: impl Into<PathBuf>)
+        -> &mut Self {
+            self.body = Some(Body::File);
+            self
+        }
+
+        /// Set the User-Agent header value to send with requests.
+        ///
+        /// Browsers do not allow setting the User-Agent header, so this value
+        /// is stored but not sent.
+        ///
+        /// # Errors
+        ///
+        /// Returns [ValidationError] if the `user_agent_string` is empty.
+        fn user_agent(&mut self, user_agent_string: impl Into<String>)
+        -> Result<&mut Self, ValidationError> {
+            let user_agent = user_agent_string.into();
+
+            if user_agent.is_empty() {
+                Err(ValidationError::MissingData(
+                    "User-Agent is required".into()
+                ))
+            } else {
+                self.user_agent = user_agent;
+                Ok(self)
+            }
+        }
+
+        fn user_agent_string(&self) -> Option<&str> {
+            Some(&self.user_agent)
+        }
+
+        fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+            self.response_limit = Some(limit);
+            self
+        }
+
+        /// Send the previously-constructed request and return a response.
+        ///
+        /// # Errors
+        ///
+        /// * If a request has not been created, returns [Error::NoRequest].
+        /// * Returns any underlying Fetch API errors in [Error::Client].
         async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
-            self.send_impl(false).await.map(|v| v.0)
+            self.send_impl(false).await.map(|v| v.1)
         }
 
         async fn send_keep_headers(&mut self)
         -> Result<(Vec<u8>, HeaderMap), Self::Error> {
-            self.send_impl(true).await.map(|v| (v.0, v.1.unwrap()))
+            self.send_impl(true).await.map(|(_, r, m)| (r, m.unwrap()))
+        }
+
+        /// Send the previously-constructed request and return the response's
+        /// HTTP status code, body, and headers.
+        ///
+        /// See [send](Self::send) for the errors returned.
+        async fn send_keep_status(&mut self)
+        -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+            self.send_impl(true).await
+                .map(|(s, r, m)| (Some(s), r, m.unwrap()))
+        }
+    }
+}
+
+#[cfg(all(test, any(
+    feature = "with_surf", feature = "with_hyper", feature = "with_isahc"
+)))]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    use std::{
+        fmt,
+        io::{Read as _, Write as _},
+        net::TcpListener,
+        thread,
+    };
+
+    /// A response for [serve] to send: the bytes to send immediately, how
+    /// long to pause afterward, and the bytes to send after the pause.
+    type Response = (&'static str, Option<Duration>, &'static str);
+
+    /// Serve each response on its own connection, in order, and return the
+    /// URL of the server.
+    fn serve(responses: Vec<Response>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for (head, pause, tail) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+
+                // A stalled response must not delay the next one.
+                thread::spawn(move || {
+                    let mut req = vec![];
+                    let mut buf = [0; 1024];
+
+                    while ! req.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => break,
+                            Ok(len) => req.extend_from_slice(&buf[..len]),
+                        }
+                    }
+
+                    let _ = stream.write_all(head.as_bytes());
+                    let _ = stream.flush();
+
+                    if let Some(pause) = pause {
+                        thread::sleep(pause);
+                    }
+
+                    let _ = stream.write_all(tail.as_bytes());
+                });
+            }
+        });
+
+        url
+    }
+
+    /// Serve one connection, responding with the body of its request, and
+    /// return the URL of the server.
+    fn echo() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut req = vec![];
+            let mut buf = [0; 1024];
+
+            let end = loop {
+                if let Some(pos) = req.windows(4).position(|w| w == b"\r\n\r\n")
+                {
+                    break pos + 4;
+                }
+
+                let len = stream.read(&mut buf).unwrap();
+                req.extend_from_slice(&buf[..len]);
+            };
+
+            let head = String::from_utf8_lossy(&req[..end]).to_lowercase();
+
+            let len: usize = head.lines()
+                .filter_map(|l| l.split_once(':'))
+                .find(|(name, _)| *name == "content-length")
+                .expect("No Content-Length header")
+                .1.trim().parse().unwrap();
+
+            // curl waits for permission to send larger bodies.
+            if head.contains("100-continue") {
+                stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+            }
+
+            let mut body = req.split_off(end);
+            let received = body.len();
+            body.resize(len, 0);
+            stream.read_exact(&mut body[received..]).unwrap();
+
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\
+                Connection: close\r\n\r\n",
+                len
+            );
+
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+
+        url
+    }
+
+    fn idle_timeout(idle: Duration) -> TimeoutConfig {
+        let timeouts = Timeouts {
+            connect: None,
+            idle: Some(idle),
+            total: None,
+        };
+
+        TimeoutConfig {
+            api: timeouts,
+            transfer: timeouts,
+        }
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\
+        Connection: close\r\n\r\n0123456789";
+
+    const CHUNKED: &str = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\
+        Connection: close\r\n\r\n5\r\n01234\r\n5\r\n56789\r\n0\r\n\r\n";
+
+    /// The server stops sending the body after its first five bytes.
+    const STALLED: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\
+        Connection: close\r\n\r\n01234";
+
+    async fn idle_timeout_fires<C, E>(mut client: C, pause: Duration)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = serve(vec![
+            (STALLED, Some(pause), "56789"),
+            (OK, None, ""),
+        ]);
+
+        let res = client.get(&url).unwrap().send().await;
+        assert!(matches!(res, Err(Error::Timeout)), "{:?}", res);
+
+        let res = client.get(&url).unwrap().send().await.unwrap();
+        assert_eq!(res, b"0123456789");
+    }
+
+    async fn response_limit_is_enforced<C, E>(mut client: C)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = serve(vec![
+            (OK, None, ""),
+            (CHUNKED, None, ""),
+            (OK, None, ""),
+            (CHUNKED, None, ""),
+        ]);
+
+        for _ in 0..2 {
+            let res = client.get(&url).unwrap()
+                .with_response_limit(9)
+                .send().await;
+
+            assert!(
+                matches!(res, Err(Error::ResponseTooLarge(9))), "{:?}", res
+            );
+        }
+
+        // The limit only applied to the requests above.
+        for _ in 0..2 {
+            let res = client.get(&url).unwrap().send().await.unwrap();
+            assert_eq!(res, b"0123456789");
+        }
+    }
+
+    async fn request_kind_is_reset<C, E>(mut client: C, pause: Duration)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = serve(vec![
+            (STALLED, Some(pause), "56789"),
+            (STALLED, Some(pause), "56789"),
+        ]);
+
+        let res = client.get(&url).unwrap()
+            .with_request_kind(RequestKind::Transfer)
+            .send().await.unwrap();
+        assert_eq!(res, b"0123456789");
+
+        let res = client.get(&url).unwrap().send().await;
+        assert!(matches!(res, Err(Error::Timeout)), "{:?}", res);
+    }
+
+    /// The server reads the request but does not respond until after `pause`.
+    async fn unanswered_upload_times_out<C, E>(mut client: C, pause: Duration)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = serve(vec![("", Some(pause), OK)]);
+
+        let res = client.post(&url).unwrap()
+            .with_body(vec![0; 1000])
+            .with_request_kind(RequestKind::Transfer)
+            .send().await;
+
+        assert!(matches!(res, Err(Error::Timeout)), "{:?}", res);
+    }
+
+    /// The server reads the request but does not respond until after `pause`.
+    async fn unanswered_download_times_out<C, E>(mut client: C, pause: Duration)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = serve(vec![("", Some(pause), OK)]);
+
+        let res = client.get(&url).unwrap()
+            .with_request_kind(RequestKind::Transfer)
+            .send().await;
+
+        assert!(matches!(res, Err(Error::Timeout)), "{:?}", res);
+    }
+
+    fn connect_timeout(connect: Duration) -> TimeoutConfig {
+        let timeouts = Timeouts {
+            connect: Some(connect),
+            idle: None,
+            total: None,
+        };
+
+        TimeoutConfig {
+            api: timeouts,
+            transfer: timeouts,
+        }
+    }
+
+    async fn file_body_is_sent<C, E>(mut client: C)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let url = echo();
+
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let path = std::env::temp_dir()
+            .join(format!("b2-client-file-body-{}", std::process::id()))
+            .join(std::any::type_name::<C>().replace("::", "-"));
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &data).unwrap();
+
+        let res = client.post(&url).unwrap()
+            .with_header("Content-Length", &data.len().to_string()).unwrap()
+            .read_body_from_file(&path)
+            .send().await;
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(res.unwrap() == data);
+    }
+
+    async fn refused_connection_is_transport_error<C, E>(mut client: C)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        // Nothing listens on port 1, so the connection is refused.
+        let res = client.get("http://127.0.0.1:1/").unwrap().send().await;
+        assert!(matches!(res, Err(Error::Transport(_))), "{:?}", res);
+    }
+
+    fn kind_timeouts(idle: Duration) -> TimeoutConfig {
+        TimeoutConfig {
+            transfer: Timeouts {
+                connect: None,
+                idle: None,
+                total: None,
+            },
+            ..idle_timeout(idle)
+        }
+    }
+
+    #[cfg(feature = "with_surf")]
+    mod surf {
+        use super::*;
+
+        const IDLE: Duration = Duration::from_millis(100);
+
+        #[async_std::test]
+        async fn idle_timeout_fires() {
+            let client = SurfClient::default()
+                .with_timeouts(idle_timeout(IDLE));
+
+            super::idle_timeout_fires(client, IDLE * 5).await;
+        }
+
+        #[async_std::test]
+        async fn response_limit_is_enforced() {
+            super::response_limit_is_enforced(SurfClient::default()).await;
+        }
+
+        #[async_std::test]
+        async fn request_kind_is_reset() {
+            let client = SurfClient::default()
+                .with_timeouts(kind_timeouts(IDLE));
+
+            super::request_kind_is_reset(client, IDLE * 5).await;
+        }
+
+        #[async_std::test]
+        async fn file_body_is_sent() {
+            super::file_body_is_sent(SurfClient::default()).await;
+        }
+
+        #[async_std::test]
+        async fn unanswered_upload_times_out() {
+            let client = SurfClient::default()
+                .with_timeouts(idle_timeout(IDLE));
+
+            super::unanswered_upload_times_out(client, IDLE * 5).await;
+        }
+
+        #[async_std::test]
+        async fn unanswered_download_times_out() {
+            let client = SurfClient::default()
+                .with_timeouts(connect_timeout(IDLE));
+
+            super::unanswered_download_times_out(client, IDLE * 5).await;
+        }
+
+        #[async_std::test]
+        async fn refused_connection_is_transport_error() {
+            super::refused_connection_is_transport_error(SurfClient::default())
+                .await;
+        }
+    }
+
+    #[cfg(feature = "with_hyper")]
+    mod hyper {
+        use super::*;
+
+        const IDLE: Duration = Duration::from_millis(100);
+
+        fn block_on<F: std::future::Future>(f: F) -> F::Output {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(f)
+        }
+
+        #[test]
+        fn idle_timeout_fires() {
+            let client = HyperClient::default()
+                .with_timeouts(idle_timeout(IDLE));
+
+            block_on(super::idle_timeout_fires(client, IDLE * 5));
+        }
+
+        #[test]
+        fn response_limit_is_enforced() {
+            block_on(super::response_limit_is_enforced(HyperClient::default()));
+        }
+
+        #[test]
+        fn request_kind_is_reset() {
+            let client = HyperClient::default()
+                .with_timeouts(kind_timeouts(IDLE));
+
+            block_on(super::request_kind_is_reset(client, IDLE * 5));
+        }
+
+        #[test]
+        fn file_body_is_sent() {
+            block_on(super::file_body_is_sent(HyperClient::default()));
+        }
+
+        #[test]
+        fn unanswered_upload_times_out() {
+            let client = HyperClient::default()
+                .with_timeouts(idle_timeout(IDLE));
+
+            block_on(super::unanswered_upload_times_out(client, IDLE * 5));
+        }
+
+        #[test]
+        fn unanswered_download_times_out() {
+            let client = HyperClient::default()
+                .with_timeouts(connect_timeout(IDLE));
+
+            block_on(super::unanswered_download_times_out(client, IDLE * 5));
+        }
+
+        #[test]
+        fn refused_connection_is_transport_error() {
+            block_on(super::refused_connection_is_transport_error(
+                HyperClient::default()
+            ));
+        }
+    }
+
+    #[cfg(feature = "with_isahc")]
+    mod isahc {
+        use super::*;
+
+        use futures_lite::future::block_on;
+
+        // curl measures transfer speed in whole seconds, averaged over the
+        // last several seconds, so a stall must last well past the timeout.
+        const IDLE: Duration = Duration::from_secs(1);
+        const PAUSE: Duration = Duration::from_secs(9);
+
+        #[test]
+        fn idle_timeout_fires() {
+            let client = IsahcClient::default()
+                .with_timeouts(idle_timeout(IDLE));
+
+            block_on(super::idle_timeout_fires(client, PAUSE));
+        }
+
+        #[test]
+        fn response_limit_is_enforced() {
+            block_on(super::response_limit_is_enforced(IsahcClient::default()));
+        }
+
+        #[test]
+        fn request_kind_is_reset() {
+            let client = IsahcClient::default()
+                .with_timeouts(kind_timeouts(IDLE));
+
+            block_on(super::request_kind_is_reset(client, PAUSE));
+        }
+
+        #[test]
+        fn file_body_is_sent() {
+            block_on(super::file_body_is_sent(IsahcClient::default()));
+        }
+
+        #[test]
+        fn refused_connection_is_transport_error() {
+            block_on(super::refused_connection_is_transport_error(
+                IsahcClient::default()
+            ));
         }
     }
 }
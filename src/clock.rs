@@ -0,0 +1,70 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+   License, v. 2.0. If a copy of the MPL was not distributed with this
+   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Sources of the current time.
+//!
+//! b2-client reads the time when it receives an authorization and when it
+//! checks whether one has expired. By default it uses the [SystemClock]; pass
+//! another [Clock] to
+//! [authorize_account_with_clock](crate::account::authorize_account_with_clock)
+//! or [Authorization::with_clock](crate::account::Authorization::with_clock)
+//! to control the time, e.g. to test expiration deterministically.
+
+use std::{fmt, sync::Arc};
+
+use chrono::{DateTime, Utc};
+
+
+/// A source of the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current date and time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A [Clock] that reads the system time.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> { Utc::now() }
+}
+
+/// The [SystemClock], shared by all objects that don't use a custom [Clock].
+pub(crate) fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A [Clock] that returns a fixed time until it is changed.
+///
+/// Clones of a `FixedClock` share their time, so a test can keep a clone and
+/// advance the time seen by the objects using the clock.
+///
+/// This is only available with the `test-util` feature.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct FixedClock(Arc<std::sync::Mutex<DateTime<Utc>>>);
+
+#[cfg(any(test, feature = "test-util"))]
+impl FixedClock {
+    /// Create a clock that returns `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self(Arc::new(std::sync::Mutex::new(now)))
+    }
+
+    /// Change the time returned by the clock.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    /// Move the clock forward by `duration`.
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> { *self.0.lock().unwrap() }
+}
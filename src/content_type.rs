@@ -0,0 +1,103 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+   License, v. 2.0. If a copy of the MPL was not distributed with this
+   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Common content types.
+//!
+//! The functions here return the [Mime] type accepted by the `content_type`
+//! methods of upload requests, download requests, and download authorization
+//! requests, so that common types can be set without parsing a string.
+//!
+//! See <https://www.backblaze.com/b2/docs/content-types.html> for the content
+//! types that B2 recognizes.
+
+use http_types::mime;
+
+pub use http_types::mime::Mime;
+
+
+/// `b2/x-auto`: B2 will determine the content type from the file extension.
+pub fn auto() -> Mime { Mime::from("b2/x-auto") }
+
+/// `application/json`.
+pub fn json() -> Mime { mime::JSON }
+
+/// `application/octet-stream`, for arbitrary binary data.
+pub fn octet_stream() -> Mime { mime::BYTE_STREAM }
+
+/// `text/plain;charset=utf-8`.
+pub fn plain_text() -> Mime { mime::PLAIN }
+
+/// `text/html;charset=utf-8`.
+pub fn html() -> Mime { mime::HTML }
+
+/// `image/png`.
+pub fn png() -> Mime { mime::PNG }
+
+/// `image/jpeg`.
+pub fn jpeg() -> Mime { mime::JPEG }
+
+/// Guess the content type from a file extension.
+///
+/// The extension is matched case-insensitively and may include its leading
+/// `.`. Returns `None` for unrecognized extensions; to let B2 choose the type
+/// instead, use [auto].
+pub fn from_extension(extension: &str) -> Option<Mime> {
+    let extension = extension.strip_prefix('.').unwrap_or(extension)
+        .to_ascii_lowercase();
+
+    let mime = match extension.as_str() {
+        "json" => mime::JSON,
+        "bin" => mime::BYTE_STREAM,
+        "txt" => mime::PLAIN,
+        "htm" | "html" => mime::HTML,
+        "css" => mime::CSS,
+        "js" | "mjs" => mime::JAVASCRIPT,
+        "xml" => mime::XML,
+        "svg" => mime::SVG,
+        "png" => mime::PNG,
+        "jpg" | "jpeg" => mime::JPEG,
+        "ico" => mime::ICO,
+        "wasm" => mime::WASM,
+        "csv" => Mime::from("text/csv"),
+        "md" => Mime::from("text/markdown"),
+        "gif" => Mime::from("image/gif"),
+        "webp" => Mime::from("image/webp"),
+        "pdf" => Mime::from("application/pdf"),
+        "zip" => Mime::from("application/zip"),
+        "gz" => Mime::from("application/gzip"),
+        "tar" => Mime::from("application/x-tar"),
+        "mp3" => Mime::from("audio/mpeg"),
+        "mp4" => Mime::from("video/mp4"),
+        _ => return None,
+    };
+
+    Some(mime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn content_types_from_extensions() {
+        let essence = |ext| from_extension(ext).map(|m| m.essence().to_owned());
+
+        assert_eq!(essence("png").as_deref(), Some("image/png"));
+        assert_eq!(essence(".JPG").as_deref(), Some("image/jpeg"));
+        assert_eq!(essence("txt").as_deref(), Some("text/plain"));
+        assert_eq!(essence("json").as_deref(), Some("application/json"));
+        assert_eq!(essence("tar").as_deref(), Some("application/x-tar"));
+        assert_eq!(essence("unknown"), None);
+        assert_eq!(essence(""), None);
+    }
+
+    #[test]
+    fn common_content_types() {
+        assert_eq!(auto().to_string(), "b2/x-auto");
+        assert_eq!(octet_stream().to_string(), "application/octet-stream");
+        assert_eq!(plain_text().essence(), "text/plain");
+    }
+}
@@ -0,0 +1,94 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+   License, v. 2.0. If a copy of the MPL was not distributed with this
+   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Aliases for the types used with the enabled HTTP client backend.
+//!
+//! Most b2-client types are generic over the [HttpClient](crate::HttpClient)
+//! and its error type. When any of the `with_surf`, `with_hyper`,
+//! `with_isahc`, or `with_fetch` features is enabled, this module names the
+//! concrete types so that application code does not need to:
+//!
+//! ```no_run
+//! # #[cfg(feature = "with_surf")]
+//! use b2_client::{default_backend as b2, list_buckets, ListBuckets};
+//!
+//! # #[cfg(feature = "with_surf")]
+//! async fn bucket_count(auth: &mut b2::Authorization) -> b2::Result<usize> {
+//!     let buckets = list_buckets(auth, ListBuckets::builder().build()).await?;
+//!     Ok(buckets.len())
+//! }
+//! ```
+//!
+//! If multiple backends are enabled, the aliases use the first of surf,
+//! hyper, isahc, and fetch, in that order. Cargo enables the union of the
+//! features requested by every crate in the dependency graph, so another
+//! dependency can change which backend is chosen; libraries and applications
+//! that enable several backends should use the generic types instead.
+
+#[cfg(feature = "with_surf")]
+type BackendError = surf::Error;
+#[cfg(all(feature = "with_hyper", not(feature = "with_surf")))]
+type BackendError = hyper::Error;
+#[cfg(all(
+    feature = "with_isahc",
+    not(feature = "with_surf"),
+    not(feature = "with_hyper"),
+))]
+type BackendError = isahc::Error;
+#[cfg(all(
+    feature = "with_fetch",
+    target_arch = "wasm32",
+    not(feature = "with_surf"),
+    not(feature = "with_hyper"),
+    not(feature = "with_isahc"),
+))]
+type BackendError = crate::client::FetchError;
+
+/// The HTTP client of the enabled backend.
+#[cfg(feature = "with_surf")]
+pub type Client = crate::client::SurfClient;
+/// The HTTP client of the enabled backend.
+#[cfg(all(feature = "with_hyper", not(feature = "with_surf")))]
+pub type Client = crate::client::HyperClient;
+/// The HTTP client of the enabled backend.
+#[cfg(all(
+    feature = "with_isahc",
+    not(feature = "with_surf"),
+    not(feature = "with_hyper"),
+))]
+pub type Client = crate::client::IsahcClient;
+/// The HTTP client of the enabled backend.
+#[cfg(all(
+    feature = "with_fetch",
+    target_arch = "wasm32",
+    not(feature = "with_surf"),
+    not(feature = "with_hyper"),
+    not(feature = "with_isahc"),
+))]
+pub type Client = crate::client::FetchClient;
+
+/// A b2-client [Error](crate::error::Error) from the enabled backend.
+pub type Error = crate::error::Error<BackendError>;
+
+/// A `Result` whose error is an [Error] from the enabled backend.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An [Authorization](crate::account::Authorization) using the enabled
+/// backend.
+pub type Authorization = crate::account::Authorization<Client>;
+
+/// A [DownloadAuthorization](crate::file::DownloadAuthorization) using the
+/// enabled backend.
+pub type DownloadAuthorization = crate::file::DownloadAuthorization<Client>;
+
+/// An [UploadAuthorization](crate::file::UploadAuthorization) using the
+/// enabled backend.
+pub type UploadAuthorization<'a> =
+    crate::file::UploadAuthorization<'a, Client, BackendError>;
+
+/// An [UploadPartAuthorization](crate::file::UploadPartAuthorization) using
+/// the enabled backend.
+pub type UploadPartAuthorization<'a, 'b> =
+    crate::file::UploadPartAuthorization<'a, 'b, Client, BackendError>;
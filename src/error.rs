@@ -23,7 +23,12 @@ use std::{
     fmt,
 };
 
-use crate::bucket::LifecycleRule;
+use crate::{
+    account::Capability,
+    bucket::LifecycleRule,
+    client::HeaderMap,
+    clock::Clock,
+};
 
 use serde::{Serialize, Deserialize};
 
@@ -32,6 +37,9 @@ use serde::{Serialize, Deserialize};
 // actually wants to handle them, rather than merely print them. As part of
 // this, we need data-oriented errors rather than string-oriented errors.
 /// Errors from validating B2 requests prior to making the request.
+///
+/// The variants holding a `String` are catch-alls for validation failures
+/// that do not yet have their own variant.
 #[derive(Debug)]
 pub enum ValidationError {
     /// Failure to parse a URL.
@@ -54,6 +62,59 @@ pub enum ValidationError {
     ///
     /// The string is a short description of the failure.
     Incompatible(String),
+    /// A part of a large file other than the final part is smaller than the
+    /// minimum part size.
+    PartTooSmall {
+        /// The number of the part that is too small.
+        part_number: u16,
+        /// The size of the part in bytes.
+        size: u64,
+        /// The minimum part size in bytes.
+        minimum: u64,
+    },
+    /// A byte range extends past the end of its source file.
+    RangeOutOfBounds {
+        /// The first byte of the range.
+        start: u64,
+        /// The last byte of the range.
+        end: u64,
+        /// The size of the source file in bytes.
+        size: u64,
+    },
+    /// A name is longer than B2 allows.
+    NameTooLong {
+        /// The length of the name.
+        length: usize,
+        /// The maximum length of the name.
+        max: usize,
+    },
+    /// A name contains a character that B2 does not allow.
+    InvalidCharacter(char),
+    /// A list of capabilities is empty.
+    MissingCapabilities,
+    /// A key's capability is not allowed when the key is restricted to a
+    /// bucket.
+    CapabilityNotAllowedForBucket(Capability),
+    /// A file name prefix restriction was given without restricting the key to
+    /// a bucket.
+    PrefixWithoutBucket,
+}
+
+impl ValidationError {
+    /// Replace every occurrence of `text` in the error message.
+    pub(crate) fn redact(self, text: &str, replacement: &str) -> Self {
+        match self {
+            Self::BadUrl(s) => Self::BadUrl(s.replace(text, replacement)),
+            Self::BadFormat(s) => Self::BadFormat(s.replace(text, replacement)),
+            Self::MissingData(s) =>
+                Self::MissingData(s.replace(text, replacement)),
+            Self::OutOfBounds(s) =>
+                Self::OutOfBounds(s.replace(text, replacement)),
+            Self::Incompatible(s) =>
+                Self::Incompatible(s.replace(text, replacement)),
+            e => e,
+        }
+    }
 }
 
 impl std::error::Error for ValidationError {}
@@ -66,6 +127,29 @@ impl fmt::Display for ValidationError {
             Self::MissingData(s) => write!(f, "{}", s),
             Self::OutOfBounds(s) => write!(f, "{}", s),
             Self::Incompatible(s) => write!(f, "{}", s),
+            Self::PartTooSmall { part_number, size, minimum } => write!(f,
+                "Part {} is {} bytes; only the last part may be under {} bytes",
+                part_number, size, minimum
+            ),
+            Self::RangeOutOfBounds { start, end, size } => write!(f,
+                "Range bytes={}-{} is outside the source file of {} bytes",
+                start, end, size
+            ),
+            Self::NameTooLong { max, .. } => write!(f,
+                "Name must be no more than {} characters.", max
+            ),
+            Self::InvalidCharacter(ch) => write!(f,
+                "Invalid character: {}", ch
+            ),
+            Self::MissingCapabilities => write!(f,
+                "A key must have at least one capability."
+            ),
+            Self::CapabilityNotAllowedForBucket(cap) => write!(f,
+                "Invalid capability when bucket_id is set: {:?}", cap
+            ),
+            Self::PrefixWithoutBucket => write!(f,
+                "bucket_id must be set when name_prefix is given"
+            ),
         }
     }
 }
@@ -277,7 +361,16 @@ pub enum Error<E>
     where E: fmt::Debug + fmt::Display,
 {
     /// An error from the underlying HTTP client.
+    ///
+    /// Failures to reach the server are reported as [Error::Transport]
+    /// instead.
     Client(E),
+    /// The HTTP client could not reach the server; e.g., the connection was
+    /// refused or reset, or the host name could not be resolved.
+    ///
+    /// The request may not have been received by B2. The underlying HTTP
+    /// client's error is provided.
+    Transport(E),
     /// An I/O error from the local filesystem.
     IO(std::io::Error),
     /// An error from the Backblaze B2 API.
@@ -291,13 +384,84 @@ pub enum Error<E>
     /// returning an error or to return what we know will be an authorization
     /// error prior to sending a request to the API.
     Unauthorized(crate::account::Capability),
+    /// The [Authorization](crate::account::Authorization) is restricted to
+    /// file names beginning with the provided prefix, and the request is for
+    /// a file or prefix outside of it.
+    ///
+    /// B2 would reject the request with an `access_denied` error that does not
+    /// mention the restriction.
+    NamePrefixRestricted(String),
     /// An error validating data prior to making a Backblaze B2 API call.
     Validation(ValidationError),
     /// Attempted to send a request without a valid
     /// [Authorization](crate::account::Authorization).
+    ///
+    /// This is also returned when attempting to upload a file with an expired
+    /// or previously-rejected
+    /// [UploadAuthorization](crate::file::UploadAuthorization).
     MissingAuthorization,
     /// Attempted to send a non-existent request.
     NoRequest,
+    /// The response body was larger than the permitted limit.
+    ///
+    /// The value is the limit in bytes.
+    ResponseTooLarge(u64),
+    /// The request did not complete within its
+    /// [Timeouts](crate::client::Timeouts), or
+    /// [wait_for_bucket](crate::bucket::wait_for_bucket) gave up waiting.
+    Timeout,
+    /// The request requires a feature that is not enabled on the bucket.
+    ///
+    /// This is currently returned by
+    /// [update_file_retention](crate::file::update_file_retention) and
+    /// [update_file_legal_hold](crate::file::update_file_legal_hold) when the
+    /// bucket does not have Object Lock (formerly File Lock) enabled.
+    FeatureNotEnabled {
+        /// The name of the feature that must be enabled.
+        feature: &'static str,
+        /// The error returned by the B2 API.
+        error: B2Error,
+    },
+    /// The transfer was cancelled via a
+    /// [CancellationToken](crate::file::CancellationToken).
+    Cancelled,
+    /// [rename_file](crate::file::rename_file) copied the file to its new
+    /// name, but could not delete the original.
+    ///
+    /// The copy is not removed, so the file now exists under both names.
+    RenameIncomplete {
+        /// The new file.
+        file: Box<crate::file::File>,
+        /// The error from deleting the original file.
+        error: Box<Error<E>>,
+    },
+    /// The SHA1 checksum of downloaded data does not match the checksum
+    /// reported by B2.
+    ///
+    /// This is also returned when B2 rejects an upload because the data it
+    /// received does not match the provided checksum; B2 does not report the
+    /// checksum of the data it received, so `actual` is empty.
+    ChecksumMismatch {
+        /// The checksum reported by B2.
+        expected: String,
+        /// The checksum of the downloaded data.
+        actual: String,
+    },
+}
+
+impl<E> Error<E>
+    where E: fmt::Debug + fmt::Display,
+{
+    /// Convert an Object Lock error from B2 to [Error::FeatureNotEnabled].
+    ///
+    /// Other errors are returned unchanged.
+    pub(crate) fn with_object_lock_context(self) -> Self {
+        match self {
+            Self::B2(error) if error.is_object_lock_not_enabled() =>
+                Self::FeatureNotEnabled { feature: "Object Lock", error },
+            e => e,
+        }
+    }
 }
 
 impl<E> std::error::Error for Error<E>
@@ -312,14 +476,34 @@ impl<E> fmt::Display for Error<E>
 
         match self {
             Self::Client(e) => Display::fmt(&e, f),
+            Self::Transport(e) =>
+                write!(f, "Could not reach the server: {}", e),
             Self::IO(e) => e.fmt(f),
             Self::B2(e) => Display::fmt(&e, f),
             Self::Format(e) => e.fmt(f),
             Self::Unauthorized(c) => write!(f, "Missing capability: {:?}", c),
+            Self::NamePrefixRestricted(prefix) =>
+                write!(f, "File name is outside the authorized prefix: {}",
+                    prefix),
             Self::Validation(e) => e.fmt(f),
             Self::MissingAuthorization =>
                 write!(f, "An Authorization is required for that operation"),
             Self::NoRequest => write!(f, "No request was created"),
+            Self::ResponseTooLarge(limit) =>
+                write!(f, "Response is larger than {} bytes", limit),
+            Self::Timeout => write!(f, "The request timed out"),
+            Self::FeatureNotEnabled { feature, error } => write!(f,
+                "{} is not enabled for the bucket ({})", feature, error
+            ),
+            Self::Cancelled => write!(f, "The transfer was cancelled"),
+            Self::RenameIncomplete { file, error } => write!(f,
+                "Copied the file to {} but could not delete the original: {}",
+                file.file_name(), error
+            ),
+            Self::ChecksumMismatch { expected, actual } => write!(f,
+                "The data's SHA1 checksum is {} but B2 reported {}",
+                actual, expected
+            ),
         }
     }
 }
@@ -351,21 +535,62 @@ impl<E> From<serde_json::Error> for Error<E>
 #[cfg(feature = "with_surf")]
 impl From<surf::Error> for Error<surf::Error> {
     fn from(e: surf::Error) -> Self {
-        Self::Client(e)
+        use surf_isahc::Error as CurlError;
+        use std::io::ErrorKind;
+
+        match e.downcast_ref::<CurlError>() {
+            Some(CurlError::Timeout) => return Self::Timeout,
+            Some(CurlError::ConnectFailed
+                | CurlError::CouldntResolveHost
+                | CurlError::CouldntResolveProxy
+                | CurlError::SSLConnectFailed(_)
+                | CurlError::NoResponse
+            ) => return Self::Transport(e),
+            _ => {},
+        }
+
+        let is_transport = e.downcast_ref::<std::io::Error>()
+            .map(|e| matches!(e.kind(),
+                ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+            ))
+            .unwrap_or(false);
+
+        if is_transport {
+            Self::Transport(e)
+        } else {
+            Self::Client(e)
+        }
     }
 }
 
 #[cfg(feature = "with_hyper")]
 impl From<hyper::Error> for Error<hyper::Error> {
     fn from(e: hyper::Error) -> Self {
-        Self::Client(e)
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() || e.is_closed() || e.is_incomplete_message()
+        {
+            Self::Transport(e)
+        } else {
+            Self::Client(e)
+        }
     }
 }
 
 #[cfg(feature = "with_isahc")]
 impl From<isahc::Error> for Error<isahc::Error> {
     fn from(e: isahc::Error) -> Self {
-        Self::Client(e)
+        use isahc::error::ErrorKind;
+
+        match e.kind() {
+            ErrorKind::Timeout => Self::Timeout,
+            ErrorKind::ConnectionFailed | ErrorKind::NameResolution =>
+                Self::Transport(e),
+            _ => Self::Client(e),
+        }
     }
 }
 
@@ -376,6 +601,13 @@ impl From<isahc::http::Error> for Error<isahc::Error> {
     }
 }
 
+#[cfg(all(feature = "with_fetch", target_arch = "wasm32"))]
+impl From<crate::client::FetchError> for Error<crate::client::FetchError> {
+    fn from(e: crate::client::FetchError) -> Self {
+        Self::Client(e)
+    }
+}
+
 impl<E> From<ValidationError> for Error<E>
     where E: fmt::Debug + fmt::Display,
 {
@@ -396,6 +628,10 @@ pub enum ErrorCode {
     BadRequest, // Also 503
     BucketMissingFileLock,
     DuplicateBucketName,
+    /// The bucket does not have Object Lock enabled.
+    ///
+    /// B2 reports this as a `bad_request`; we recognize it by its message.
+    FileLockNotEnabled,
     FileNotPresent,
     InvalidBucketId,
     InvalidFileId,
@@ -493,23 +729,192 @@ impl ErrorCode {
 ///
 /// See <https://www.backblaze.com/b2/docs/calling.html#error_handling> for
 /// information on B2 error handling.
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct B2Error {
     /// The HTTP status code accompanying the error.
     status: u16,
     /// A code that identifies the error.
-    #[serde(rename = "code")]
     code_str: String,
     /// A description of what went wrong.
     message: String,
+    /// The complete error response.
+    raw_body: serde_json::Value,
+    /// The response's `Retry-After` header.
+    retry_after: Option<RetryAfter>,
+}
+
+/// The value of a `Retry-After` header.
+///
+/// The values are kept in seconds so that [B2Error], and therefore [Error],
+/// stay small.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RetryAfter {
+    Seconds(u64),
+    // A Unix timestamp.
+    Date(i64),
+}
+
+impl RetryAfter {
+    /// Parse a `Retry-After` header value, which may be a number of seconds or
+    /// an HTTP-date.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Self::Seconds(secs));
+        }
+
+        chrono::DateTime::parse_from_rfc2822(value).ok()
+            .map(|date| Self::Date(date.timestamp()))
+    }
+
+    /// The delay from `now`; a date in the past is a delay of zero.
+    fn delay_from(&self, now: chrono::DateTime<chrono::Utc>)
+    -> std::time::Duration {
+        let secs = match *self {
+            Self::Seconds(secs) => secs,
+            Self::Date(date) =>
+                date.saturating_sub(now.timestamp()).max(0) as u64,
+        };
+
+        std::time::Duration::from_secs(secs)
+    }
+}
+
+impl<'de> Deserialize<'de> for B2Error {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Fields {
+            status: u16,
+            code: String,
+            message: String,
+        }
+
+        let raw_body = serde_json::Value::deserialize(deserializer)?;
+        let fields = Fields::deserialize(&raw_body)
+            .map_err(serde::de::Error::custom)?;
+
+        Ok(Self {
+            status: fields.status,
+            code_str: fields.code,
+            message: fields.message,
+            raw_body,
+            retry_after: None,
+        })
+    }
 }
 
 impl B2Error {
+    /// Replace every occurrence of `text` in the error message.
+    pub(crate) fn redact(&mut self, text: &str, replacement: &str) {
+        self.message = self.message.replace(text, replacement);
+
+        if let Some(message) = self.raw_body.get_mut("message") {
+            *message = self.message.clone().into();
+        }
+    }
+
+    /// The JSON error response as B2 sent it.
+    ///
+    /// This includes any fields that B2 sends beyond the status, code, and
+    /// message, which can help when debugging an integration. If the message
+    /// was [redacted](crate::account::LogRedaction), it is redacted here too.
+    pub fn raw_body(&self) -> &serde_json::Value { &self.raw_body }
+
+    /// Read the `Retry-After` header of the error's response.
+    pub(crate) fn set_retry_after(&mut self, headers: &HeaderMap) {
+        self.retry_after = headers.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("Retry-After"))
+            .and_then(|(_, v)| RetryAfter::parse(v));
+    }
+
+    /// The delay requested by the `Retry-After` header of the response.
+    ///
+    /// B2 may send this header with `429 Too Many Requests` and
+    /// `503 Service Unavailable` errors; both the delay-seconds and HTTP-date
+    /// forms are supported. An HTTP-date is relative to the system clock;
+    /// see [retry_after_with_clock](Self::retry_after_with_clock).
+    ///
+    /// When this is present, it should be used instead of the delay
+    /// calculated by a [RetryPolicy](crate::retry::RetryPolicy).
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        self.retry_after_with_clock(&crate::clock::SystemClock)
+    }
+
+    /// The delay requested by the `Retry-After` header of the response,
+    /// measuring an HTTP-date from the time of the provided [Clock].
+    ///
+    /// See [retry_after](Self::retry_after).
+    pub fn retry_after_with_clock(&self, clock: &dyn Clock)
+    -> Option<std::time::Duration> {
+        self.retry_after.map(|retry_after| retry_after.delay_from(clock.now()))
+    }
+
     /// Get the HTTP status code for the error.
     pub fn http_status(&self) -> u16 { self.status }
 
+    /// The error code as B2 sent it.
+    pub(crate) fn code_str(&self) -> &str { &self.code_str }
+
     pub fn code(&self) -> ErrorCode {
-        ErrorCode::from_api_code(&self.code_str)
+        match ErrorCode::from_api_code(&self.code_str) {
+            ErrorCode::BadRequest if self.mentions_disabled_file_lock() =>
+                ErrorCode::FileLockNotEnabled,
+            code => code,
+        }
+    }
+
+    fn mentions_disabled_file_lock(&self) -> bool {
+        let msg = self.message.to_lowercase();
+
+        (msg.contains("file lock") || msg.contains("object lock"))
+            && (msg.contains("not enabled") || msg.contains("disabled"))
+    }
+
+    /// Returns true if B2 rejected an upload because the data it received did
+    /// not match the provided SHA1 checksum.
+    pub fn is_checksum_mismatch(&self) -> bool {
+        let msg = self.message.to_lowercase();
+
+        self.code() == ErrorCode::BadRequest
+            && (msg.contains("sha1") || msg.contains("checksum"))
+            && msg.contains("did not match")
+    }
+
+    /// Returns true if the error is due to the bucket not having Object Lock
+    /// enabled.
+    pub fn is_object_lock_not_enabled(&self) -> bool {
+        matches!(self.code(),
+            ErrorCode::FileLockNotEnabled | ErrorCode::BucketMissingFileLock
+        )
+    }
+
+    /// Returns true if the error is due to an account usage cap.
+    ///
+    /// A cap-exceeded error will not resolve by retrying the request; the cap
+    /// must be raised or the usage period must reset.
+    pub fn is_cap_exceeded(&self) -> bool {
+        matches!(self.code(),
+            ErrorCode::CapExceeded
+            | ErrorCode::StorageCapExceeded
+            | ErrorCode::TransactionCapExceeded
+        )
+    }
+
+    /// Returns true if the request that caused the error may succeed if it is
+    /// sent again.
+    ///
+    /// Requests that fail with an HTTP status of 408, 429, 500, or 503 are
+    /// retriable; a [RetryPolicy](crate::retry::RetryPolicy) can be used to
+    /// choose how long to wait first. An [ErrorCode::ExpiredAuthToken] error
+    /// is also retriable, but only after obtaining a new authorization. All
+    /// other errors, such as `bad_request` or `access_denied`, will fail again
+    /// if the same request is resent.
+    pub fn is_retriable(&self) -> bool {
+        matches!(self.status, 408 | 429 | 500 | 503)
+            || self.code() == ErrorCode::ExpiredAuthToken
     }
 }
 
@@ -520,3 +925,181 @@ impl fmt::Display for B2Error {
         write!(f, "{}: {}", self.code_str, self.message)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn cap_exceeded_errors_are_detected() {
+        let codes = [
+            ("cap_exceeded", ErrorCode::CapExceeded),
+            ("storage_cap_exceeded", ErrorCode::StorageCapExceeded),
+            ("transaction_cap_exceeded", ErrorCode::TransactionCapExceeded),
+        ];
+
+        for (code, expected) in codes {
+            let body = format!(r#"{{
+                "status": 403,
+                "code": "{}",
+                "message": "Transaction cap exceeded."
+            }}"#, code);
+
+            let err: B2Error = serde_json::from_str(&body).unwrap();
+
+            assert_eq!(err.http_status(), 403);
+            assert_eq!(err.code(), expected);
+            assert!(err.is_cap_exceeded());
+        }
+    }
+
+    #[test]
+    fn other_errors_are_not_cap_exceeded() {
+        let err: B2Error = serde_json::from_str(r#"{
+            "status": 403,
+            "code": "access_denied",
+            "message": "Access denied"
+        }"#).unwrap();
+
+        assert!(! err.is_cap_exceeded());
+    }
+
+    #[test]
+    fn raw_body_keeps_unknown_fields() {
+        let mut err: B2Error = serde_json::from_str(r#"{
+            "status": 400,
+            "code": "bad_request",
+            "message": "Invalid file name: secret.txt",
+            "requestId": "abc123"
+        }"#).unwrap();
+
+        assert_eq!(err.raw_body()["requestId"], "abc123");
+
+        err.redact("secret.txt", "<file>");
+        assert_eq!(err.raw_body()["message"], "Invalid file name: <file>");
+        assert_eq!(err.to_string(), "bad_request: Invalid file name: <file>");
+    }
+
+    #[test]
+    fn retry_after_parses_seconds_and_dates() {
+        use chrono::TimeZone as _;
+        use std::time::Duration;
+
+        // Tue, 01 Mar 2022 12:00:00 GMT
+        let now = chrono::Utc.timestamp_opt(1646136000, 0).unwrap();
+        let delay = |v| RetryAfter::parse(v).map(|r| r.delay_from(now));
+
+        assert_eq!(delay(" 120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            delay("Tue, 01 Mar 2022 12:00:30 GMT"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            delay("Tue, 01 Mar 2022 11:00:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(delay("soon"), None);
+    }
+
+    #[test]
+    fn retry_after_is_read_from_response_headers() {
+        use crate::clock::FixedClock;
+        use chrono::TimeZone as _;
+
+        let body = br#"{
+            "status": 503,
+            "code": "service_unavailable",
+            "message": "Service temporarily unavailable"
+        }"#;
+
+        let mut err: B2Error = serde_json::from_slice(body).unwrap();
+        assert_eq!(err.retry_after(), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after".into(), "5".into());
+        err.set_retry_after(&headers);
+
+        assert_eq!(err.retry_after(), Some(std::time::Duration::from_secs(5)));
+        assert_eq!(err.raw_body(), &serde_json::from_slice::<serde_json::Value>(
+            body
+        ).unwrap());
+
+        headers.insert(
+            "retry-after".into(),
+            "Tue, 01 Mar 2022 12:00:30 GMT".into()
+        );
+        err.set_retry_after(&headers);
+
+        // Tue, 01 Mar 2022 12:00:00 GMT
+        let clock = FixedClock::new(
+            chrono::Utc.timestamp_opt(1646136000, 0).unwrap()
+        );
+
+        assert_eq!(
+            err.retry_after_with_clock(&clock),
+            Some(std::time::Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn object_lock_not_enabled_is_feature_error() {
+        let err: B2Error = serde_json::from_str(r#"{
+            "status": 400,
+            "code": "bad_request",
+            "message": "File lock is not enabled on this bucket"
+        }"#).unwrap();
+
+        assert_eq!(err.code(), ErrorCode::FileLockNotEnabled);
+
+        match Error::<String>::B2(err).with_object_lock_context() {
+            e @ Error::FeatureNotEnabled { feature: "Object Lock", .. } => {
+                let msg = e.to_string();
+                assert!(msg.starts_with("Object Lock is not enabled"));
+            },
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        let err: B2Error = serde_json::from_str(r#"{
+            "status": 400,
+            "code": "bad_request",
+            "message": "Invalid fileId"
+        }"#).unwrap();
+
+        assert_eq!(err.code(), ErrorCode::BadRequest);
+        assert!(matches!(Error::<String>::B2(err).with_object_lock_context(),
+            Error::B2(_)
+        ));
+    }
+
+    #[test]
+    fn retriable_errors() {
+        let errors = [
+            (408, "request_timeout", true),
+            (429, "too_many_requests", true),
+            (500, "internal_error", true),
+            (503, "service_unavailable", true),
+            (503, "bad_request", true),
+            (401, "expired_auth_token", true),
+            (400, "bad_request", false),
+            (400, "invalid_bucket_id", false),
+            (401, "bad_auth_token", false),
+            (401, "unauthorized", false),
+            (403, "access_denied", false),
+            (403, "cap_exceeded", false),
+            (404, "not_found", false),
+        ];
+
+        for (status, code, retriable) in errors {
+            let body = format!(r#"{{
+                "status": {},
+                "code": "{}",
+                "message": "Something went wrong"
+            }}"#, status, code);
+
+            let err: B2Error = serde_json::from_str(&body).unwrap();
+
+            assert_eq!(err.is_retriable(), retriable, "{} {}", status, code);
+        }
+    }
+}
@@ -26,7 +26,8 @@
 //!
 //! You can upload multiple files with a single `UploadAuthorization`, but only
 //! one at a time. To upload multiple files in parallel, each thread or task
-//! needs to obtain its own `UploadAuthorization`.
+//! needs to obtain its own `UploadAuthorization`; [upload_many] does this for
+//! you when uploading files from the local filesystem.
 //!
 //!
 //! # Uploading Large Files
@@ -55,7 +56,6 @@
 //! # Examples
 //!
 //! ```no_run
-//! # fn calculate_sha1(data: &[u8]) -> String { String::default() }
 //! use std::env;
 //! use anyhow;
 //! use b2_client::{self as b2, HttpClient as _};
@@ -72,7 +72,7 @@
 //!     let mut upload_auth = b2::get_upload_authorization(&mut auth, &bucket)
 //!         .await?;
 //!
-//!     let checksum = calculate_sha1(&data);
+//!     let checksum = b2::compute_sha1(data);
 //!
 //!     let file = b2::UploadFile::builder()
 //!         .file_name(name)?
@@ -84,7 +84,6 @@
 //! ```
 //!
 //! ```no_run
-//! # fn calculate_sha1(data: &[u8]) -> String { String::default() }
 //! use std::env;
 //! use anyhow;
 //! use b2_client::{self as b2, HttpClient as _};
@@ -114,9 +113,8 @@
 //!         &file
 //!     ).await?;
 //!
-//!     // Assuming a `calculate_sha1` function is defined:
-//!     let sha1 = calculate_sha1(&data_part1);
-//!     let sha2 = calculate_sha1(&data_part2);
+//!     let sha1 = b2::compute_sha1(data_part1);
+//!     let sha2 = b2::compute_sha1(data_part2);
 //!
 //!     let upload_req = b2::UploadFilePart::builder()
 //!         .part_sha1_checksum(&sha1)
@@ -144,25 +142,26 @@
 //! }
 //! ```
 
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 use crate::{
     prelude::*,
-    account::Capability,
+    account::{Capability, LogRedaction},
     bucket::{
         Bucket,
         FileRetentionMode,
         FileRetentionPolicy,
         ServerSideEncryption,
     },
-    client::{HeaderMap, HttpClient},
+    client::{HeaderMap, HttpClient, RequestKind},
     error::*,
-    types::ContentDisposition,
+    types::{ContentDisposition, IntoDuration},
     validate::{
         validate_content_disposition,
         validate_file_metadata_size,
         validated_file_info,
         validated_file_name,
+        validated_sha1_checksum,
     },
 };
 
@@ -287,6 +286,62 @@ impl FileRetention {
     }
 }
 
+/// The ID of a file version stored in B2.
+///
+/// `FileId` and [FileName] are distinct types so that one cannot be passed
+/// where the other is expected. Both can be created from a string or a [File].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FileId(String);
+
+impl FileId {
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+/// The name of a file stored in B2.
+///
+/// The name is not validated on creation; see [FileId].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FileName(String);
+
+impl FileName {
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+macro_rules! impl_file_string {
+    ($type:ident, $field:ident) => {
+        impl From<&str> for $type {
+            fn from(s: &str) -> Self { Self(s.to_owned()) }
+        }
+
+        impl From<String> for $type {
+            fn from(s: String) -> Self { Self(s) }
+        }
+
+        impl From<&String> for $type {
+            fn from(s: &String) -> Self { Self(s.clone()) }
+        }
+
+        impl From<&File> for $type {
+            fn from(file: &File) -> Self { Self(file.$field.clone()) }
+        }
+
+        impl AsRef<str> for $type {
+            fn as_ref(&self) -> &str { &self.0 }
+        }
+
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+impl_file_string!(FileId, file_id);
+impl_file_string!(FileName, file_name);
+
 // TODO: Rename to FileMetadata?
 /// Metadata of a file stored in B2.
 #[derive(Debug, Deserialize)]
@@ -411,9 +466,27 @@ impl File {
 
         match self.action {
             FileAction::Folder => None,
-            _ => Some(Utc.timestamp_millis(self.upload_timestamp)),
+            _ => Utc.timestamp_millis_opt(self.upload_timestamp).single(),
         }
     }
+
+    /// The last modification time of the source file, as set via
+    /// [UploadFileBuilder::last_modified] or
+    /// [StartLargeFileBuilder::last_modified].
+    ///
+    /// This is read from the `src_last_modified_millis` entry of the
+    /// [file_info](Self::file_info); returns `None` if it is missing or is not
+    /// a valid timestamp.
+    pub fn last_modified(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::{TimeZone as _, Utc};
+
+        let millis = match self.file_info.get("src_last_modified_millis")? {
+            serde_json::Value::String(s) => s.parse::<i64>().ok()?,
+            v => v.as_i64()?,
+        };
+
+        Utc.timestamp_millis_opt(millis).single()
+    }
 }
 
 /// A part of a large file currently being uploaded.
@@ -461,7 +534,7 @@ pub struct CancelledFileUpload {
     pub file_name: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DeletedFile {
     pub file_id: String,
@@ -493,9 +566,9 @@ pub async fn cancel_large_file_by_id<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::json!({ "fileId": id.as_ref() }))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let info: B2Result<CancelledFileUpload> = serde_json::from_slice(&res)?;
+    let info: B2Result<CancelledFileUpload> = B2Result::from_response(&res)?;
     info.into()
 }
 
@@ -536,6 +609,140 @@ impl ByteRange {
 
     pub fn start(&self) -> u64 { self.start }
     pub fn end(&self) -> u64 { self.end }
+
+    /// Check that the range lies within a source file of `size` bytes.
+    fn validate_within(&self, size: u64) -> Result<(), ValidationError> {
+        if self.end < size {
+            Ok(())
+        } else {
+            Err(ValidationError::RangeOutOfBounds {
+                start: self.start,
+                end: self.end,
+                size,
+            })
+        }
+    }
+}
+
+/// Check a copy request's byte range against the size of its source file.
+///
+/// Sources without a meaningful size are left for B2 to validate.
+async fn validate_copy_range<C, E>(
+    auth: &mut Authorization<C>,
+    source_file_id: &str,
+    range: Option<&ByteRange>,
+) -> Result<(), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    if let Some(range) = range {
+        let source = get_file_info(auth, source_file_id).await?;
+
+        if let Some(size) = source.content_length() {
+            range.validate_within(size)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a copy's destination bucket belongs to the authorized account.
+async fn validate_copy_destination<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+) -> Result<(), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    use crate::bucket::{get_bucket_by_id, BucketLookup};
+
+    let bucket_id = match bucket_id {
+        Some(bucket_id) => bucket_id,
+        None => return Ok(()),
+    };
+
+    match get_bucket_by_id(auth, bucket_id).await? {
+        BucketLookup::Found(_) => Ok(()),
+        BucketLookup::NotFound => Err(ValidationError::Incompatible(format!(
+            "The destination bucket {} does not belong to the account",
+            bucket_id
+        )).into()),
+        BucketLookup::Forbidden => Err(ValidationError::Incompatible(format!(
+            "The authorization cannot access the destination bucket {}",
+            bucket_id
+        )).into()),
+    }
+}
+
+/// The portion of a file returned by a ranged download, parsed from the
+/// `Content-Range` header of a `206 Partial Content` response.
+///
+/// Both `start` and `end` are inclusive. The `total` size of the file is `None`
+/// if the server did not report it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ContentRange {
+    start: u64,
+    end: u64,
+    total: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parse a `Content-Range` header value of the form
+    /// `bytes <start>-<end>/<total>`, where `<total>` may be `*`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ValidationError::BadFormat] if the value is not a satisfied
+    /// byte range; this includes the `bytes */<total>` form sent with a
+    /// `416 Range Not Satisfiable` response.
+    pub fn from_header(value: &str) -> Result<Self, ValidationError> {
+        let bad_format = || ValidationError::BadFormat(
+            format!("Invalid Content-Range: {}", value)
+        );
+
+        let (range, total) = value.trim()
+            .strip_prefix("bytes ")
+            .and_then(|v| v.split_once('/'))
+            .ok_or_else(bad_format)?;
+
+        let (start, end) = range.split_once('-')
+            .and_then(|(start, end)| Some((
+                start.trim().parse::<u64>().ok()?,
+                end.trim().parse::<u64>().ok()?,
+            )))
+            .ok_or_else(bad_format)?;
+
+        let total = match total.trim() {
+            "*" => None,
+            total => Some(total.parse::<u64>().map_err(|_| bad_format())?),
+        };
+
+        if start > end || total.map(|t| end >= t) == Some(true) {
+            return Err(bad_format());
+        }
+
+        Ok(Self { start, end, total })
+    }
+
+    /// The first byte of the range.
+    pub fn start(&self) -> u64 { self.start }
+
+    /// The last byte of the range.
+    pub fn end(&self) -> u64 { self.end }
+
+    /// The size in bytes of the complete file, if known.
+    pub fn total(&self) -> Option<u64> { self.total }
+
+    /// The number of bytes in the range.
+    #[allow(clippy::len_without_is_empty)] // A range is never empty.
+    pub fn len(&self) -> u64 { self.end - self.start + 1 }
+
+    /// Returns `true` if the range ends with the last byte of the file.
+    ///
+    /// Returns `false` if the total size of the file is unknown.
+    pub fn is_final(&self) -> bool {
+        self.total.map(|t| self.end + 1 == t).unwrap_or(false)
+    }
 }
 
 /// Describe the action to take with file metadata when copying a file.
@@ -573,6 +780,10 @@ pub struct CopyFile<'a> {
     #[serde(rename = "destinationServerSideEncryption")]
     #[serde(skip_serializing_if = "Option::is_none")]
     dest_encryption: Option<ServerSideEncryption>,
+    #[serde(skip)]
+    validate_range: bool,
+    #[serde(skip)]
+    validate_destination: bool,
 }
 
 impl<'a> CopyFile<'a> {
@@ -598,6 +809,8 @@ pub struct CopyFileBuilder<'a> {
     legal_hold: Option<LegalHoldValue>,
     source_encryption: Option<ServerSideEncryption>,
     dest_encryption: Option<ServerSideEncryption>,
+    validate_range: bool,
+    validate_destination: bool,
 
     // To merge into file_info on build if metadata_directive is Replace:
     last_modified: Option<i64>,
@@ -618,8 +831,8 @@ impl<'a> CopyFileBuilder<'a> {
     }
 
     /// Set the source file ID of the file to copy.
-    pub fn source_file_id(mut self, file: impl Into<String>) -> Self {
-        self.source_file_id = Some(file.into());
+    pub fn source_file_id(mut self, file: impl Into<FileId>) -> Self {
+        self.source_file_id = Some(file.into().0);
         self
     }
 
@@ -627,12 +840,26 @@ impl<'a> CopyFileBuilder<'a> {
     ///
     /// If not provided, the same bucket ID as the source file is used.
     ///
-    /// Both buckets must belong to the same account.
+    /// Both buckets must belong to the same account; see
+    /// [validate_destination_bucket](Self::validate_destination_bucket).
     pub fn destination_bucket_id(mut self, bucket: impl Into<String>) -> Self {
         self.destination_bucket_id = Some(bucket.into());
         self
     }
 
+    /// Check that the destination bucket belongs to the authorized account
+    /// before copying.
+    ///
+    /// B2 cannot copy files between accounts, but does not say so when asked
+    /// to. This costs an additional request via
+    /// [get_bucket_by_id](crate::bucket::get_bucket_by_id), which requires
+    /// [Capability::ListBuckets]. It has no effect if no
+    /// [destination bucket](Self::destination_bucket_id) is set.
+    pub fn validate_destination_bucket(mut self) -> Self {
+        self.validate_destination = true;
+        self
+    }
+
     /// Set the filename to use for the new file.
     pub fn destination_file_name(mut self, name: &'a str)
     -> Result<Self, FileNameValidationError> {
@@ -646,6 +873,15 @@ impl<'a> CopyFileBuilder<'a> {
         self
     }
 
+    /// Check the range against the size of the source file before copying.
+    ///
+    /// This costs an additional request via [get_file_info], which requires
+    /// [Capability::ReadFiles]. It has no effect if no range is set.
+    pub fn validate_range(mut self) -> Self {
+        self.validate_range = true;
+        self
+    }
+
     /// Determine whether to copy the source metadata to the new file.
     ///
     /// If [MetadataDirective::Copy] (the default), the source metadata will be
@@ -884,6 +1120,8 @@ impl<'a> CopyFileBuilder<'a> {
             legal_hold: self.legal_hold,
             source_encryption: self.source_encryption,
             dest_encryption: self.dest_encryption,
+            validate_range: self.validate_range,
+            validate_destination: self.validate_destination,
         })
     }
 }
@@ -895,6 +1133,18 @@ impl<'a> CopyFileBuilder<'a> {
 ///
 /// If copying from one bucket to another, both buckets must belong to the same
 /// account.
+///
+/// # Errors
+///
+/// If the request was built with
+/// [validate_range](CopyFileBuilder::validate_range), returns
+/// [ValidationError::RangeOutOfBounds] if the range extends past the end of
+/// the source file.
+///
+/// If the request was built with
+/// [validate_destination_bucket](CopyFileBuilder::validate_destination_bucket),
+/// returns [ValidationError::Incompatible] if the destination bucket is not
+/// in the authorized account or the [Authorization] cannot access it.
 pub async fn copy_file<'a, C, E>(
     auth: &mut Authorization<C>,
     file: CopyFile<'_>
@@ -912,14 +1162,23 @@ pub async fn copy_file<'a, C, E>(
     if file.dest_encryption.is_some() {
         require_capability!(auth, Capability::WriteBucketEncryption);
     }
+    if file.validate_range {
+        validate_copy_range(auth, &file.source_file_id, file.range.as_ref())
+            .await?;
+    }
+    if file.validate_destination {
+        validate_copy_destination(auth, file.destination_bucket_id.as_deref())
+            .await?;
+    }
 
     let res = auth.client.post(auth.api_url("b2_copy_file"))
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_request_kind(RequestKind::Transfer)
         .with_body_json(serde_json::to_value(file)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file: B2Result<File> = serde_json::from_slice(&res)?;
+    let file: B2Result<File> = B2Result::from_response(&res)?;
     file.into()
 }
 
@@ -936,6 +1195,8 @@ pub struct CopyFilePart<'a> {
     source_server_side_encryption: Option<&'a ServerSideEncryption>,
     #[serde(skip_serializing_if = "Option::is_none")]
     destination_server_side_encryption: Option<&'a ServerSideEncryption>,
+    #[serde(skip)]
+    validate_range: bool,
 }
 
 impl<'a> CopyFilePart<'a> {
@@ -953,6 +1214,7 @@ pub struct CopyFilePartBuilder<'a> {
     range: Option<ByteRange>,
     source_encryption: Option<&'a ServerSideEncryption>,
     dest_encryption: Option<&'a ServerSideEncryption>,
+    validate_range: bool,
 }
 
 impl<'a> CopyFilePartBuilder<'a> {
@@ -1013,6 +1275,15 @@ impl<'a> CopyFilePartBuilder<'a> {
         self
     }
 
+    /// Check the range against the size of the source file before copying.
+    ///
+    /// This costs an additional request via [get_file_info], which requires
+    /// [Capability::ReadFiles]. It has no effect if no range is set.
+    pub fn validate_range(mut self) -> Self {
+        self.validate_range = true;
+        self
+    }
+
     /// Set the encryption settings of the source file.
     ///
     /// This must match the settings with which the file was encrypted.
@@ -1056,6 +1327,7 @@ impl<'a> CopyFilePartBuilder<'a> {
             range: self.range,
             source_server_side_encryption: self.source_encryption,
             destination_server_side_encryption: self.dest_encryption,
+            validate_range: self.validate_range,
         })
     }
 }
@@ -1064,6 +1336,13 @@ impl<'a> CopyFilePartBuilder<'a> {
 ///
 /// The [Authorization] must have [Capability::WriteFiles], and if the bucket is
 /// private, [Capability::ReadFiles].
+///
+/// # Errors
+///
+/// If the request was built with
+/// [validate_range](CopyFilePartBuilder::validate_range), returns
+/// [ValidationError::RangeOutOfBounds] if the range extends past the end of
+/// the source file.
 pub async fn copy_file_part<C, E>(
     auth: &mut Authorization<C>,
     file_part: CopyFilePart<'_>
@@ -1072,14 +1351,22 @@ pub async fn copy_file_part<C, E>(
           E: fmt::Debug + fmt::Display,
 {
     require_capability!(auth, Capability::WriteFiles);
+    if file_part.validate_range {
+        validate_copy_range(
+            auth,
+            file_part.source_file_id,
+            file_part.range.as_ref()
+        ).await?;
+    }
 
     let res = auth.client.post(auth.api_url("b2_copy_part"))
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_request_kind(RequestKind::Transfer)
         .with_body_json(serde_json::to_value(file_part)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let part: B2Result<FilePart> = serde_json::from_slice(&res)?;
+    let part: B2Result<FilePart> = B2Result::from_response(&res)?;
     part.into()
 }
 
@@ -1136,7 +1423,7 @@ pub async fn download_file_headers<C, E>(
 /// list of headers that may be returned.
 pub async fn download_file_headers_by_id<C, E>(
     auth: &mut Authorization<C>,
-    file_id: impl AsRef<str>
+    file_id: impl Into<FileId>
 ) -> Result<HeaderMap, Error<E>>
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
@@ -1147,30 +1434,205 @@ pub async fn download_file_headers_by_id<C, E>(
     // the file without passing the authorization token.
     require_capability!(auth, Capability::ReadFiles);
 
+    let auth_token = auth.download_header_value(None);
+
     let res = auth.client.head(
             format!("{}?fileId={}",
                 auth.download_url("b2_download_file_by_id"),
-                file_id.as_ref()
+                file_id.into()
             )
         )
         .expect("Invalid URL")
-        .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_header("Authorization", &auth_token).unwrap()
         .send_keep_headers().await?;
 
     Ok(res.1)
 }
 
-#[derive(Debug)]
+/// The metadata of a file, as returned in the headers of a download.
+///
+/// Obtain `FileHeaders` via [head_file_by_id] or [head_file_by_name].
+#[derive(Debug, Clone)]
+pub struct FileHeaders {
+    file_id: String,
+    file_name: String,
+    content_length: u64,
+    content_type: Option<String>,
+    content_sha1: Option<String>,
+    upload_time: Option<chrono::DateTime<chrono::Utc>>,
+    content_range: Option<ContentRange>,
+    file_info: HashMap<String, String>,
+}
+
+impl FileHeaders {
+    /// Parse the file metadata from the headers of a download response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ValidationError::MissingData] if the headers do not contain
+    /// the file ID and name; this typically means that the file does not exist
+    /// or the request was not authorized.
+    pub fn from_headers(headers: &HeaderMap)
+    -> Result<Self, ValidationError> {
+        let decode = |s: &str| percent_encoding::percent_decode_str(s)
+            .decode_utf8_lossy()
+            .into_owned();
+
+        let required = |name: &str| header_value(headers, name)
+            .ok_or_else(|| ValidationError::MissingData(
+                format!("The response has no {} header", name)
+            ));
+
+        let file_id = required("X-Bz-File-Id")?.to_owned();
+        let file_name = decode(required("X-Bz-File-Name")?);
+
+        let content_length = header_value(headers, "Content-Length")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let content_sha1 = header_value(headers, "X-Bz-Content-Sha1")
+            .filter(|v| *v != "none")
+            .map(String::from);
+
+        let file_info = headers.iter()
+            .filter_map(|(k, v)| {
+                let prefix = "x-bz-info-";
+
+                if k.len() > prefix.len()
+                    && k[.. prefix.len()].eq_ignore_ascii_case(prefix)
+                {
+                    Some((k[prefix.len() ..].to_owned(), decode(v)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            file_id,
+            file_name,
+            content_length,
+            content_type: header_value(headers, "Content-Type")
+                .map(String::from),
+            content_sha1,
+            upload_time: last_modified_from_headers(headers),
+            content_range: header_value(headers, "Content-Range")
+                .and_then(|v| ContentRange::from_header(v).ok()),
+            file_info,
+        })
+    }
+
+    /// The file's ID.
+    pub fn file_id(&self) -> &str { &self.file_id }
+
+    /// The file's name.
+    pub fn file_name(&self) -> &str { &self.file_name }
+
+    /// The size of the file in bytes.
+    pub fn content_length(&self) -> u64 { self.content_length }
+
+    /// The MIME type of the file.
+    pub fn content_type(&self) -> Option<&str> { self.content_type.as_deref() }
+
+    /// The SHA1 checksum of the file.
+    ///
+    /// Large files do not have a checksum unless one was provided in the file
+    /// info when the file was created.
+    pub fn content_sha1(&self) -> Option<&str> { self.content_sha1.as_deref() }
+
+    /// The time at which the file was uploaded.
+    pub fn upload_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.upload_time
+    }
+
+    /// The range of bytes returned by a ranged download.
+    ///
+    /// This is `None` unless the response was `206 Partial Content`; see
+    /// [DownloadFileBuilder::range]. For a partial response,
+    /// [content_length](Self::content_length) is the length of the range.
+    pub fn content_range(&self) -> Option<ContentRange> { self.content_range }
+
+    /// The file's custom information, from the `X-Bz-Info-*` headers.
+    ///
+    /// The keys do not include the `X-Bz-Info-` prefix.
+    pub fn file_info(&self) -> &HashMap<String, String> { &self.file_info }
+}
+
+/// Retrieve the metadata of a file without downloading it.
+///
+/// B2 bills the request as a Class B transaction, as it does a download, but
+/// no download bandwidth is used. Unlike [get_file_info], the file may be
+/// looked up by name; see [head_file_by_name].
+///
+/// The [Authorization] must have [Capability::ReadFiles].
+pub async fn head_file_by_id<C, E>(
+    auth: &mut Authorization<C>,
+    file_id: impl Into<FileId>
+) -> Result<FileHeaders, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let headers = download_file_headers_by_id(auth, file_id).await?;
+    Ok(FileHeaders::from_headers(&headers)?)
+}
+
+/// Retrieve the metadata of the latest version of a file by its name, without
+/// downloading it.
+///
+/// You may provide a mutable reference to either an [Authorization] or a
+/// [DownloadAuthorization]. An `Authorization` must have
+/// [Capability::ReadFiles].
+///
+/// B2 bills the request as a Class B transaction, as it does a download, but
+/// no download bandwidth is used.
+pub async fn head_file_by_name<'a, C, E>(
+    auth: impl Into<DownloadAuth<'a, C>>,
+    bucket_name: &str,
+    file_name: impl Into<FileName>,
+) -> Result<FileHeaders, Error<E>>
+    where C: HttpClient<Error=Error<E>> + 'a,
+          E: fmt::Debug + fmt::Display,
+{
+    let mut auth = auth.into();
+
+    require_capability!(auth, Capability::ReadFiles);
+
+    let file_name = file_name.into();
+    let redaction = auth.log_redaction();
+
+    let url = crate::account::file_url(
+        auth.download_get_url(),
+        bucket_name,
+        &percent_encode!(file_name.as_str())
+    );
+
+    let auth_token = auth.header_value();
+
+    let client = match auth {
+        DownloadAuth::Auth(ref mut auth) => &mut auth.client,
+        DownloadAuth::Download(ref mut auth) => &mut auth.client,
+    };
+
+    let (_, headers) = client.head(url)
+        .expect("Invalid URL")
+        .with_header("Authorization", &auth_token).unwrap()
+        .send_keep_headers().await
+        .map_err(|e| redaction.error(e, file_name.as_str()))?;
+
+    Ok(FileHeaders::from_headers(&headers)?)
+}
+
+#[derive(Debug, Clone)]
 enum FileHandle<'a> {
-    Id(&'a str),
+    Id(FileId),
     Name((String, &'a str)), // (Percent-encoded file name, bucket name)
 }
 
 /// A request to download a file or a portion of a file from the B2 API.
 ///
 /// A simple file request can be created via [with_name](Self::with_name) or
-/// [with_id](Self::with_id); for more complex requests use a
-/// [DownloadFileBuilder].
+/// [with_id](Self::with_id), optionally limited to a [range](Self::range); for
+/// more complex requests use a [DownloadFileBuilder].
 ///
 /// If you use self-managed server-side encryption, you must use
 /// [DownloadFileBuilder] to pass the encryption information.
@@ -1178,7 +1640,7 @@ enum FileHandle<'a> {
 /// See <https://www.backblaze.com/b2/docs/b2_download_file_by_id.html> for
 /// information on downloading files, including the list of headers that may be
 /// returned.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DownloadFile<'a> {
     #[serde(skip_serializing)]
@@ -1199,13 +1661,17 @@ pub struct DownloadFile<'a> {
     b2_content_type: Option<String>,
     #[serde(skip_serializing)]
     encryption: Option<ServerSideEncryption>,
+    #[serde(skip_serializing)]
+    if_modified_since: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing)]
+    max_bytes: Option<u64>,
 }
 
 impl<'a> DownloadFile<'a> {
     /// Download a file with the specified file ID.
-    pub fn with_id(id: &'a str) -> Self {
+    pub fn with_id(id: impl Into<FileId>) -> Self {
         Self {
-            file: FileHandle::Id(id),
+            file: FileHandle::Id(id.into()),
             range: None,
             b2_content_disposition: None,
             b2_content_language: None,
@@ -1214,15 +1680,19 @@ impl<'a> DownloadFile<'a> {
             b2_content_encoding: None,
             b2_content_type: None,
             encryption: None,
+            if_modified_since: None,
+            max_bytes: None,
         }
     }
 
     /// Download a file with the specified file name.
     ///
     /// The name will be percent-encoded.
-    pub fn with_name(name: &str, bucket: &'a str) -> Self {
+    pub fn with_name(name: impl Into<FileName>, bucket: &'a str) -> Self {
         Self {
-            file: FileHandle::Name((percent_encode!(name), bucket)),
+            file: FileHandle::Name(
+                (percent_encode!(name.into().as_str()), bucket)
+            ),
             range: None,
             b2_content_disposition: None,
             b2_content_language: None,
@@ -1231,6 +1701,8 @@ impl<'a> DownloadFile<'a> {
             b2_content_encoding: None,
             b2_content_type: None,
             encryption: None,
+            if_modified_since: None,
+            max_bytes: None,
         }
     }
 
@@ -1238,6 +1710,36 @@ impl<'a> DownloadFile<'a> {
         DownloadFileBuilder::default()
     }
 
+    /// Only download the specified byte range of the file.
+    ///
+    /// See [DownloadFileBuilder::range] for further information.
+    ///
+    /// # Examples
+    ///
+    /// Download the first kilobyte of a file from a private bucket:
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "with_surf")]
+    /// # use b2_client::{
+    /// #     client::SurfClient,
+    /// #     file::{download_file, ByteRange, DownloadAuthorization,
+    /// #         DownloadFile},
+    /// # };
+    /// # #[cfg(feature = "with_surf")]
+    /// # async fn f(mut download_auth: DownloadAuthorization<SurfClient>)
+    /// # -> anyhow::Result<()> {
+    /// let (data, _headers) = download_file(
+    ///     &mut download_auth,
+    ///     DownloadFile::with_name("my/files/data.bin", "my-bucket")
+    ///         .range(ByteRange::new(0, 1023)?)
+    /// ).await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn range(mut self, range: ByteRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
     /// Generate the public URL for a GET request for a file in a public bucket.
     ///
     /// A file in a public bucket does not require an authorization token to
@@ -1259,11 +1761,8 @@ impl<'a> DownloadFile<'a> {
                 auth.download_url("b2_download_file_by_id"),
                 id
             ),
-            FileHandle::Name((name, bucket)) => format!(
-                "{}/file/{}/{}?",
-                auth.download_get_url(),
-                bucket,
-                name
+            FileHandle::Name((name, bucket)) => format!("{}?",
+                crate::account::file_url(auth.download_get_url(), bucket, name)
             ),
         }
     }
@@ -1280,6 +1779,8 @@ pub struct DownloadFileBuilder<'a> {
     content_encoding: Option<String>,
     content_type: Option<String>,
     encryption: Option<ServerSideEncryption>,
+    if_modified_since: Option<chrono::DateTime<chrono::Utc>>,
+    max_bytes: Option<u64>,
 }
 
 impl<'a> DownloadFileBuilder<'a> {
@@ -1289,8 +1790,11 @@ impl<'a> DownloadFileBuilder<'a> {
     ///
     /// If both [file_name](Self::file_name) and [file_id](Self::file_id) are
     /// provided, the last one will be used.
-    pub fn file_name(mut self, name: &str, bucket: &'a str) -> Self {
-        self.file = Some(FileHandle::Name((percent_encode!(name), bucket)));
+    pub fn file_name(mut self, name: impl Into<FileName>, bucket: &'a str)
+    -> Self {
+        self.file = Some(
+            FileHandle::Name((percent_encode!(name.into().as_str()), bucket))
+        );
         self
     }
 
@@ -1298,15 +1802,15 @@ impl<'a> DownloadFileBuilder<'a> {
     ///
     /// If both [file_name](Self::file_name) and [file_id](Self::file_id) are
     /// provided, the last one will be used.
-    pub fn file_id(mut self, id: &'a str) -> Self {
-        self.file = Some(FileHandle::Id(id));
+    pub fn file_id(mut self, id: impl Into<FileId>) -> Self {
+        self.file = Some(FileHandle::Id(id.into()));
         self
     }
 
     /// Specify the byte range of the file to download.
     ///
     /// There will be a Content-Range header that specifies the bytes returned
-    /// and the total number of bytes.
+    /// and the total number of bytes; see [FileHeaders::content_range].
     ///
     /// The HTTP status code when a partial file is returned is `206 Partial
     /// Content` rather than `200 OK`.
@@ -1318,6 +1822,11 @@ impl<'a> DownloadFileBuilder<'a> {
     /// Override the Content-Disposition header of the response with the one
     /// provided.
     ///
+    /// This allows one stored file to be served with different download file
+    /// names. If the file is downloaded via a [DownloadAuthorization] that
+    /// was created with this or another override, a different value causes
+    /// the download to fail with [ValidationError::Incompatible].
+    ///
     /// If including this header will exceed the 7,000 byte header limit (2,048
     /// bytes if using server-side encryption), the request will be rejected.
     pub fn content_disposition(mut self, disposition: &'a ContentDisposition)
@@ -1384,6 +1893,30 @@ impl<'a> DownloadFileBuilder<'a> {
         self
     }
 
+    /// Only download the file if it has been modified since the given time.
+    ///
+    /// Use [download_file_if_modified] to distinguish an unmodified file
+    /// from a modified one; [download_file] returns an empty body if the file
+    /// has not been modified.
+    pub fn if_modified_since(mut self, time: chrono::DateTime<chrono::Utc>)
+    -> Self {
+        self.if_modified_since = Some(time);
+        self
+    }
+
+    /// Set the maximum number of bytes to download.
+    ///
+    /// If the file (or requested [range](Self::range)) is larger than `max`,
+    /// the download fails with [Error::ResponseTooLarge] without reading the
+    /// rest of the response. By default the size is unlimited.
+    ///
+    /// Enforcing the limit requires support from the [HttpClient]; all
+    /// clients provided by b2-client support it.
+    pub fn max_bytes(mut self, max: u64) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
     /// Build a [DownloadFile] request.
     pub fn build(self) -> Result<DownloadFile<'a>, ValidationError> {
         let file = self.file.ok_or_else(|| ValidationError::MissingData(
@@ -1400,6 +1933,8 @@ impl<'a> DownloadFileBuilder<'a> {
             b2_content_encoding: self.content_encoding,
             b2_content_type: self.content_type,
             encryption: self.encryption,
+            if_modified_since: self.if_modified_since,
+            max_bytes: self.max_bytes,
         })
     }
 }
@@ -1427,15 +1962,17 @@ impl<'a, C> DownloadAuth<'a, C>
     fn download_url(&self, endpoint: impl AsRef<str>) -> String {
         match self {
             Self::Auth(auth) => auth.download_url(endpoint),
-            Self::Download(auth) =>
-                format!("{}/b2api/v2/{}", auth.download_url, endpoint.as_ref())
+            Self::Download(auth) => crate::account::endpoint_url(
+                &auth.download_url,
+                endpoint.as_ref()
+            ),
         }
     }
 
-    fn authorization_token(&self) -> &str {
+    fn header_value(&self) -> String {
         match self {
-            Self::Auth(auth) => &auth.authorization_token,
-            Self::Download(auth) => &auth.authorization_token,
+            Self::Auth(auth) => auth.download_header_value(None),
+            Self::Download(auth) => auth.authorization_token.clone(),
         }
     }
 
@@ -1445,6 +1982,13 @@ impl<'a, C> DownloadAuth<'a, C>
             _ => true,
         }
     }
+
+    fn log_redaction(&self) -> LogRedaction {
+        match self {
+            Self::Auth(auth) => auth.log_redaction(),
+            Self::Download(auth) => auth.log_redaction,
+        }
+    }
 }
 
 impl<'a, C> From<&'a mut Authorization<C>> for DownloadAuth<'a, C>
@@ -1470,55 +2014,430 @@ impl<'a, C> From<&'a mut DownloadAuthorization<C>> for DownloadAuth<'a, C>
 ///
 /// Downloading files by ID requires an `Authorization`. If provided with a
 /// `DownloadAuthorization`, returns `Error::MissingAuthorization`.
+///
+/// If an `Authorization` restricted to a file name prefix is used to download
+/// a file by name outside of that prefix, returns
+/// [Error::NamePrefixRestricted].
 pub async fn download_file<'a, C, E>(
     auth: impl Into<DownloadAuth<'a, C>>,
     file: DownloadFile<'_>
 ) -> Result<(Vec<u8>, HeaderMap), Error<E>>
     where C: HttpClient<Error=Error<E>> + 'a,
           E: fmt::Debug + fmt::Display,
+{
+    download_file_impl(auth, file, None).await
+        .map(|(_, body, headers)| (body, headers))
+}
+
+/// Download a file from the B2 service into the provided buffer.
+///
+/// The buffer is cleared, then filled with the file's contents; reusing one
+/// buffer for many downloads avoids allocating a new one for each file if the
+/// [HttpClient] supports it (see [HttpClient::with_response_buffer]).
+/// Returns the file's metadata from the response headers.
+///
+/// If the download fails, the contents of `buf` are unspecified. See
+/// [download_file] for further information.
+pub async fn download_file_into<'a, C, E>(
+    auth: impl Into<DownloadAuth<'a, C>>,
+    file: DownloadFile<'_>,
+    buf: &mut Vec<u8>,
+) -> Result<FileHeaders, Error<E>>
+    where C: HttpClient<Error=Error<E>> + 'a,
+          E: fmt::Debug + fmt::Display,
+{
+    let (_, body, headers) = download_file_impl(auth, file,
+        Some(std::mem::take(buf))).await?;
+    *buf = body;
+
+    Ok(FileHeaders::from_headers(&headers)?)
+}
+
+/// Download a file, reading the response into `buf` if it is provided.
+///
+/// Returns the HTTP status if the [HttpClient] reports it, and the response
+/// body and headers.
+async fn download_file_impl<'a, C, E>(
+    auth: impl Into<DownloadAuth<'a, C>>,
+    file: DownloadFile<'_>,
+    buf: Option<Vec<u8>>,
+) -> Result<(Option<u16>, Vec<u8>, HeaderMap), Error<E>>
+    where C: HttpClient<Error=Error<E>> + 'a,
+          E: fmt::Debug + fmt::Display,
 {
     match file.file {
         FileHandle::Id(_) => {
             match auth.into() {
-                DownloadAuth::Auth(auth) => download_file_by_id(auth, file)
+                DownloadAuth::Auth(auth) => download_file_by_id(auth, file, buf)
                     .await,
                 _ => Err(Error::MissingAuthorization),
             }
         },
-        FileHandle::Name(_) => download_file_by_name(auth, file).await
+        FileHandle::Name((ref name, _)) => {
+            let auth = auth.into();
+            let redaction = auth.log_redaction();
+            let encoded = name.clone();
+
+            download_file_by_name(auth, file, buf).await.map_err(|e| {
+                let name = percent_encoding::percent_decode_str(&encoded)
+                    .decode_utf8_lossy();
+
+                redaction.error(redaction.error(e, &encoded), &name)
+            })
+        },
     }
 }
 
-async fn download_file_by_id<C, E>(
-    auth: &mut Authorization<C>,
-    file: DownloadFile<'_>
-) -> Result<(Vec<u8>, HeaderMap), Error<E>>
-    where C: HttpClient<Error=Error<E>>,
-          E: fmt::Debug + fmt::Display,
-{
-    // TODO: This is probably only required for private buckets; public buckets
-    // don't require an authorization token, but the docs read as if this is
-    // necessary if provided. Need to test, and if necessary allow downloading
-    // the file without passing the authorization token.
+/// Format a timestamp as an HTTP-date (RFC 7231).
+fn http_date(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// The result of a conditional download via [download_file_if_modified].
+#[derive(Debug)]
+pub enum DownloadResult {
+    /// The file was modified; the file's contents and the response headers.
+    Modified(Vec<u8>, HeaderMap),
+    /// The file has not been modified since the requested time; the response
+    /// headers.
+    NotModified(HeaderMap),
+}
+
+impl DownloadResult {
+    fn from_response(
+        status: Option<u16>,
+        body: Vec<u8>,
+        headers: HeaderMap,
+        since: chrono::DateTime<chrono::Utc>
+    ) -> Self {
+        // A custom HttpClient may not report the status code. A `304 Not
+        // Modified` response never has a body, and an empty (modified) file
+        // will still report an upload time later than the requested time.
+        let not_modified = match status {
+            Some(status) => status == 304,
+            None => body.is_empty()
+                && last_modified_from_headers(&headers)
+                    .map(|t| t <= since)
+                    .unwrap_or(false),
+        };
+
+        if not_modified {
+            Self::NotModified(headers)
+        } else {
+            Self::Modified(body, headers)
+        }
+    }
+
+    /// The response headers.
+    pub fn headers(&self) -> &HeaderMap {
+        match self {
+            Self::Modified(_, headers) => headers,
+            Self::NotModified(headers) => headers,
+        }
+    }
+
+    /// The time the file was last modified, if reported by B2.
+    ///
+    /// This is the `X-Bz-Upload-Timestamp` header if present; otherwise the
+    /// `Last-Modified` header.
+    pub fn last_modified(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        last_modified_from_headers(self.headers())
+    }
+}
+
+/// Look up a header by name, ignoring case.
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn last_modified_from_headers(headers: &HeaderMap)
+-> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{TimeZone as _, Utc};
+
+    let get = |name: &str| header_value(headers, name);
+
+    let upload_time = get("X-Bz-Upload-Timestamp")
+        .and_then(|v| v.parse::<i64>().ok())
+        .and_then(|v| Utc.timestamp_millis_opt(v).single());
+
+    upload_time.or_else(||
+        get("Last-Modified")
+            .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+            .map(|v| v.with_timezone(&Utc))
+    )
+}
+
+/// Download a file only if it has been modified since the time set via
+/// [DownloadFileBuilder::if_modified_since].
+///
+/// If no time was set, the file is always downloaded. See [download_file] for
+/// further information.
+pub async fn download_file_if_modified<'a, C, E>(
+    auth: impl Into<DownloadAuth<'a, C>>,
+    file: DownloadFile<'_>
+) -> Result<DownloadResult, Error<E>>
+    where C: HttpClient<Error=Error<E>> + 'a,
+          E: fmt::Debug + fmt::Display,
+{
+    let since = file.if_modified_since;
+    let (status, body, headers) = download_file_impl(auth, file, None).await?;
+
+    match since {
+        Some(since) =>
+            Ok(DownloadResult::from_response(status, body, headers, since)),
+        None => Ok(DownloadResult::Modified(body, headers)),
+    }
+}
+
+/// Split a file of `total_size` bytes into at most `concurrency` ranges of
+/// equal size for [download_file_accelerated].
+///
+/// `total_size` must not be zero.
+#[cfg(feature = "futures")]
+fn accelerated_ranges(total_size: u64, concurrency: usize) -> Vec<ByteRange> {
+    /// The most ranges downloaded at once.
+    const MAX_CONCURRENCY: usize = 64;
+
+    let count = (concurrency.clamp(1, MAX_CONCURRENCY) as u64).min(total_size);
+    let size = total_size.div_ceil(count);
+
+    (0..count)
+        .map(|i| i * size)
+        .filter(|start| *start < total_size)
+        .map(|start| ByteRange {
+            start,
+            end: (start + size).min(total_size) - 1,
+        })
+        .collect()
+}
+
+/// Download a file via concurrent requests for ranges of it.
+///
+/// The file is split into `concurrency` ranges of equal size based on
+/// `total_size`, the size of the file in bytes (e.g., from
+/// [File::content_length] or [head_file_by_id]); each range is downloaded by a
+/// copy of `auth`. At most 64 ranges are downloaded, however large
+/// `concurrency` is. On high-latency connections this can be much faster than a
+/// single [download_file] request.
+///
+/// The first piece of the file is downloaded before the others to learn the
+/// file's size; the returned buffer is then allocated once, and each range is
+/// written into its part of it in pieces of at most 8 MiB. Besides the buffer,
+/// the download uses at most one piece of memory per range.
+///
+/// If the file is shorter than `total_size`, the shorter file is returned. If
+/// B2 returns no data for a part of a range within the file, returns
+/// [ValidationError::RangeOutOfBounds].
+///
+/// The downloaded data is checked against the file's SHA1 checksum, or the
+/// `large_file_sha1` file information of a large file; a mismatch returns
+/// [Error::ChecksumMismatch]. Files without a checksum are not verified.
+///
+/// `file` must not have a [range](DownloadFile::range). See [download_file]
+/// for further information.
+///
+/// This is only available with the `futures` feature.
+#[cfg(feature = "futures")]
+pub async fn download_file_accelerated<C, E>(
+    auth: &Authorization<C>,
+    file: DownloadFile<'_>,
+    total_size: u64,
+    concurrency: usize,
+) -> Result<Vec<u8>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    /// The most bytes requested at once by each concurrent download.
+    const PIECE_SIZE: u64 = 8 * 1024 * 1024;
+
+    /// Download `range` of the file into `dest`, which holds the part of the
+    /// range that is within the file, one piece at a time.
+    async fn download_range<C, E>(
+        mut auth: Authorization<C>,
+        file: DownloadFile<'_>,
+        range: ByteRange,
+        dest: &mut [u8],
+    ) -> Result<(), Error<E>>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let mut buf = vec![];
+        let mut start = range.start;
+
+        while start < range.start + dest.len() as u64 {
+            let piece = ByteRange {
+                start,
+                end: (start + PIECE_SIZE - 1).min(range.end),
+            };
+
+            let req = file.clone().range(piece.clone());
+            download_file_into(&mut auth, req, &mut buf).await?;
+
+            let offset = (start - range.start) as usize;
+            let len = buf.len().min(dest.len() - offset);
+
+            if len == 0 {
+                return Err(ValidationError::RangeOutOfBounds {
+                    start: piece.start,
+                    end: piece.end,
+                    size: start,
+                }.into());
+            }
+
+            dest[offset..offset + len].copy_from_slice(&buf[..len]);
+            start += len as u64;
+        }
+
+        Ok(())
+    }
+
+    if file.range.is_some() {
+        return Err(ValidationError::Incompatible(
+            "An accelerated download cannot be limited to a range".into()
+        ).into());
+    }
+
+    // An empty file has no satisfiable range.
+    if total_size == 0 {
+        let mut auth = auth.clone();
+        let mut data = vec![];
+        let headers = download_file_into(&mut auth, file, &mut data).await?;
+
+        verify_accelerated_download(&data, &headers)?;
+        return Ok(data);
+    }
+
+    let ranges = accelerated_ranges(total_size, concurrency);
+
+    // Download the first piece before allocating the buffer, so that the file
+    // size reported by B2 limits it if `total_size` is overstated.
+    let first = ByteRange {
+        start: 0,
+        end: ranges[0].end.min(PIECE_SIZE - 1),
+    };
+
+    let mut first_piece = vec![];
+    let headers = download_file_into(
+        &mut auth.clone(),
+        file.clone().range(first),
+        &mut first_piece
+    ).await?;
+
+    let len = headers.content_range()
+        .and_then(|range| range.total())
+        .map_or(total_size, |total| total.min(total_size));
+
+    let mut data = vec![0; len as usize];
+
+    let first_len = first_piece.len().min(data.len());
+    data[..first_len].copy_from_slice(&first_piece[..first_len]);
+    drop(first_piece);
+
+    // Give each range its slice of the buffer; ranges past the end of a
+    // shorter file have nothing to download.
+    let mut rest = &mut data[..];
+    let mut downloads = vec![];
+
+    for range in ranges {
+        let within = (range.end + 1).min(len).saturating_sub(range.start);
+        let (dest, tail) = rest.split_at_mut(within as usize);
+        rest = tail;
+
+        // The first range already holds its first piece.
+        let (range, dest) = if range.start == 0 {
+            let done = first_len.min(dest.len());
+            let range = ByteRange { start: done as u64, end: range.end };
+
+            (range, &mut dest[done..])
+        } else {
+            (range, dest)
+        };
+
+        if ! dest.is_empty() {
+            downloads.push(
+                download_range(auth.clone(), file.clone(), range, dest)
+            );
+        }
+    }
+
+    for res in futures_util::future::join_all(downloads).await {
+        res?;
+    }
+
+    verify_accelerated_download(&data, &headers)?;
+    Ok(data)
+}
+
+/// Check the data returned by [download_file_accelerated] against the SHA1
+/// checksum in the file's headers, if there is one.
+#[cfg(feature = "futures")]
+fn verify_accelerated_download<E>(data: &[u8], headers: &FileHeaders)
+-> Result<(), Error<E>>
+    where E: fmt::Debug + fmt::Display,
+{
+    let checksum = headers.content_sha1()
+        .or_else(|| headers.file_info().get("large_file_sha1")
+            .map(|s| s.as_str()))
+        .map(|s| s.trim_start_matches("unverified:"));
+
+    if let Some(expected) = checksum {
+        let actual = compute_sha1(data);
+
+        if ! actual.eq_ignore_ascii_case(expected) {
+            return Err(Error::ChecksumMismatch {
+                expected: expected.to_owned(),
+                actual,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+async fn download_file_by_id<C, E>(
+    auth: &mut Authorization<C>,
+    file: DownloadFile<'_>,
+    buf: Option<Vec<u8>>,
+) -> Result<(Option<u16>, Vec<u8>, HeaderMap), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    // TODO: This is probably only required for private buckets; public buckets
+    // don't require an authorization token, but the docs read as if this is
+    // necessary if provided. Need to test, and if necessary allow downloading
+    // the file without passing the authorization token.
     require_capability!(auth, Capability::ReadFiles);
 
-    let file_id = match file.file {
-        FileHandle::Id(id) => id,
+    let file_id = match &file.file {
+        FileHandle::Id(id) => id.to_string(),
         FileHandle::Name(_) => panic!("Call download_file_by_name() instead"),
     };
 
     let mut file_req = serde_json::to_value(&file)?;
-    file_req["fileId"] = serde_json::Value::String(file_id.into());
+    file_req["fileId"] = serde_json::Value::String(file_id);
+
+    let auth_token = auth.download_header_value(None);
 
     let mut req = auth.client.post(auth.download_url("b2_download_file_by_id"))
         .expect("Invalid URL")
-        .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_header("Authorization", &auth_token).unwrap()
+        .with_request_kind(RequestKind::Transfer)
         .with_body_json(file_req);
 
     if let Some(range) = file.range {
         req = req.with_header("Range", &range.to_string())?;
     }
 
+    if let Some(time) = file.if_modified_since {
+        req = req.with_header("If-Modified-Since", &http_date(time))?;
+    }
+
+    if let Some(limit) = file.max_bytes {
+        req = req.with_response_limit(limit);
+    }
+
     if let Some(ServerSideEncryption::SelfManaged(enc)) = file.encryption {
         req = req
             .with_header(
@@ -1535,21 +2454,45 @@ async fn download_file_by_id<C, E>(
             )?;
     }
 
-    let (body, headers) = req.send_keep_headers().await?;
+    // Only a caller-provided buffer is reused; otherwise the client may
+    // allocate the body however suits it.
+    if let Some(buf) = buf {
+        req = req.with_response_buffer(buf);
+    }
+
+    let (status, body, headers) = req.send_keep_status().await?;
+    download_response(status, body, headers)
+}
 
+/// Return a download's response, or the error from B2 if the download failed.
+fn download_response<E>(status: Option<u16>, body: Vec<u8>, headers: HeaderMap)
+-> Result<(Option<u16>, Vec<u8>, HeaderMap), Error<E>>
+    where E: fmt::Debug + fmt::Display,
+{
     // An error from Backblaze would successfully deserialize as Vec<u8>, so we
-    // need to check for it specifically.
-    let res: Result<B2Error, _> = serde_json::from_slice(&body);
-    match res {
-        Ok(e) => Err(e.into()),
-        Err(_) => Ok((body, headers)),
+    // need to check for it specifically. If the client reports the status
+    // code, a successful download is never an error, even if the file looks
+    // like one.
+    let may_be_error = match status {
+        Some(status) => ! (200..300).contains(&status),
+        None => true,
+    };
+
+    if may_be_error {
+        if let Ok(mut e) = serde_json::from_slice::<B2Error>(&body) {
+            e.set_retry_after(&headers);
+            return Err(e.into());
+        }
     }
+
+    Ok((status, body, headers))
 }
 
 async fn download_file_by_name<'a, C, E>(
     auth: impl Into<DownloadAuth<'a, C>>,
-    file: DownloadFile<'_>
-) -> Result<(Vec<u8>, HeaderMap), Error<E>>
+    file: DownloadFile<'_>,
+    buf: Option<Vec<u8>>,
+) -> Result<(Option<u16>, Vec<u8>, HeaderMap), Error<E>>
     where C: HttpClient<Error=Error<E>> + 'a,
           E: fmt::Debug + fmt::Display,
 {
@@ -1562,10 +2505,19 @@ async fn download_file_by_name<'a, C, E>(
     require_capability!(auth, Capability::ReadFiles);
     assert!(matches!(file.file, FileHandle::Name(_)));
 
+    if let (DownloadAuth::Auth(auth), FileHandle::Name((name, _)))
+        = (&auth, &file.file)
+    {
+        let name = percent_encoding::percent_decode_str(name)
+            .decode_utf8_lossy();
+
+        require_name_prefix(auth, &name)?;
+    }
+
     let mut url = file.public_url(&auth).to_owned();
 
     macro_rules! add_param {
-        ($str:ident, $name:literal, $obj:expr) => {
+        ($str:ident, $name:expr, $obj:expr) => {
             $str.push_str($name);
             $str.push('=');
             $str.push_str($obj);
@@ -1573,20 +2525,27 @@ async fn download_file_by_name<'a, C, E>(
         };
     }
 
-    macro_rules! add_opt_param {
-        ($str:ident, $name:literal, $obj:expr) => {
-            if let Some(s) = $obj {
-                add_param!($str, $name, &s);
-            }
-        };
-    }
+    let requested = DownloadOverrides {
+        content_disposition: file.b2_content_disposition.map(String::from),
+        content_language: file.b2_content_language.map(String::from),
+        expires: file.b2_expires,
+        cache_control: file.b2_cache_control,
+        content_encoding: file.b2_content_encoding,
+        content_type: file.b2_content_type,
+    };
 
-    add_opt_param!(url, "b2ContentDisposition", file.b2_content_disposition);
-    add_opt_param!(url, "b2ContentLanguage", file.b2_content_language);
-    add_opt_param!(url, "b2Expires", file.b2_expires);
-    add_opt_param!(url, "b2CacheControl", file.b2_cache_control);
-    add_opt_param!(url, "b2ContentEncoding", file.b2_content_encoding);
-    add_opt_param!(url, "b2ContentType", file.b2_content_type);
+    // A download authorization created with overrides only permits downloads
+    // that use them.
+    let overrides = match &auth {
+        DownloadAuth::Auth(_) => DownloadOverrides::default(),
+        DownloadAuth::Download(auth) => auth.overrides.clone(),
+    }.merge(requested)?;
+
+    for (name, value) in overrides.iter() {
+        if let Some(value) = value {
+            add_param!(url, name, &percent_encode!(value));
+        }
+    }
 
     if let Some(ServerSideEncryption::SelfManaged(enc)) = file.encryption {
         add_param!(url,
@@ -1603,7 +2562,7 @@ async fn download_file_by_name<'a, C, E>(
         );
     }
 
-    let auth_token = auth.authorization_token().to_owned();
+    let auth_token = auth.header_value();
 
     let client = match auth {
         DownloadAuth::Auth(ref mut auth) => &mut auth.client,
@@ -1612,21 +2571,27 @@ async fn download_file_by_name<'a, C, E>(
 
     let mut req = client.get(url)
         .expect("Invalid URL")
-        .with_header("Authorization", &auth_token).unwrap();
+        .with_header("Authorization", &auth_token).unwrap()
+        .with_request_kind(RequestKind::Transfer);
 
     if let Some(range) = file.range {
         req = req.with_header("Range", &range.to_string())?
     }
 
-    let (body, headers) = req.send_keep_headers().await?;
+    if let Some(time) = file.if_modified_since {
+        req = req.with_header("If-Modified-Since", &http_date(time))?
+    }
 
-    // An error from Backblaze would successfully deserialize as Vec<u8>, so we
-    // need to check for it specifically.
-    let res: Result<B2Error, _> = serde_json::from_slice(&body);
-    match res {
-        Ok(e) => Err(e.into()),
-        Err(_) => Ok((body, headers)),
+    if let Some(limit) = file.max_bytes {
+        req = req.with_response_limit(limit);
+    }
+
+    if let Some(buf) = buf {
+        req = req.with_response_buffer(buf);
     }
+
+    let (status, body, headers) = req.send_keep_status().await?;
+    download_response(status, body, headers)
 }
 
 /// Delete a version of a file.
@@ -1638,8 +2603,8 @@ async fn download_file_by_name<'a, C, E>(
 /// [cancel_large_file].
 pub async fn delete_file_version_by_name_id<C, E>(
     auth: &mut Authorization<C>,
-    file_name: impl AsRef<str>,
-    file_id: impl AsRef<str>,
+    file_name: impl Into<FileName>,
+    file_id: impl Into<FileId>,
     bypass_governance: BypassGovernance,
 ) -> Result<DeletedFile, Error<E>>
     where C: HttpClient<Error=Error<E>>,
@@ -1647,9 +2612,11 @@ pub async fn delete_file_version_by_name_id<C, E>(
 {
     require_capability!(auth, Capability::DeleteFiles);
 
+    let file_name = file_name.into();
+
     let mut body = serde_json::json!({
-        "fileName": &file_name.as_ref(),
-        "fileId": &file_id.as_ref(),
+        "fileName": &file_name,
+        "fileId": file_id.into(),
     });
 
     if matches!(bypass_governance, BypassGovernance::Yes) {
@@ -1661,10 +2628,11 @@ pub async fn delete_file_version_by_name_id<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(body)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file: B2Result<DeletedFile> = serde_json::from_slice(&res)?;
-    file.into()
+    let file: B2Result<DeletedFile> = B2Result::from_response(&res)?;
+    Result::from(file)
+        .map_err(|e| auth.log_redaction().error(e, file_name.as_str()))
 }
 
 /// Complete the upload of a large file, merging all parts into a single [File].
@@ -1709,9 +2677,9 @@ pub async fn finish_large_file_upload_by_id<C, E>(
             "fileId": file_id.as_ref(),
             "partSha1Array": &sha1_checksums,
         }))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file: B2Result<File> = serde_json::from_slice(&res)?;
+    let file: B2Result<File> = B2Result::from_response(&res)?;
     file.into()
 }
 
@@ -1726,7 +2694,7 @@ pub async fn finish_large_file_upload_by_id<C, E>(
 /// for a large file that has not been finished yet.
 pub async fn get_file_info<C, E>(
     auth: &mut Authorization<C>,
-    file_id: impl AsRef<str>
+    file_id: impl Into<FileId>
 ) -> Result<File, Error<E>>
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
@@ -1739,11 +2707,11 @@ pub async fn get_file_info<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(json!({
-            "fileId": file_id.as_ref(),
+            "fileId": file_id.into(),
         }))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file_info: B2Result<File> = serde_json::from_slice(&res)?;
+    let file_info: B2Result<File> = B2Result::from_response(&res)?;
     match file_info {
         B2Result::Ok(mut info) => {
             if let Some(sha1) = &info.content_sha1 {
@@ -1768,6 +2736,7 @@ pub async fn get_file_info<C, E>(
 pub struct DownloadAuthorizationRequest<'a> {
     bucket_id: &'a str,
     file_name_prefix: &'a str,
+    #[serde(serialize_with = "crate::types::serialize_seconds")]
     valid_duration_in_seconds: Duration,
     #[serde(skip_serializing_if = "Option::is_none")]
     b2_content_disposition: Option<String>,
@@ -1789,6 +2758,19 @@ impl<'a> DownloadAuthorizationRequest<'a> {
     }
 }
 
+fn validated_download_duration(dur: impl IntoDuration)
+-> Result<Duration, ValidationError> {
+    let dur = dur.into_duration()?;
+
+    if dur < chrono::Duration::seconds(1) || dur > chrono::Duration::weeks(1) {
+        Err(ValidationError::OutOfBounds(
+            "Duration must be between 1 and 604,800 seconds, inclusive".into()
+        ))
+    } else {
+        Ok(Duration(dur))
+    }
+}
+
 /// A builder to create a [DownloadAuthorizationRequest].
 ///
 /// After building the `DownloadAuthorizationRequest`, pass it to
@@ -1796,6 +2778,18 @@ impl<'a> DownloadAuthorizationRequest<'a> {
 ///
 /// The bucket ID, file name prefix, and valid duration are required.
 ///
+/// B2 accepts each header override independently, but some combinations
+/// contradict each other. [build](Self::build) rejects a
+/// [cache_control](Self::cache_control) with the `no-store` directive if it
+/// is combined with:
+///
+/// * an [expiration](Self::expiration), or
+/// * a `max-age`, `s-maxage`, or `immutable` cache directive.
+///
+/// It also rejects an expiration later than the end of the authorization's
+/// [duration](Self::duration), since downloads cannot be made with the
+/// authorization by then.
+///
 /// See <https://www.backblaze.com/b2/docs/b2_get_download_authorization.html>
 /// for furter information.
 #[derive(Default)]
@@ -1811,6 +2805,13 @@ pub struct DownloadAuthorizationRequestBuilder<'a> {
     b2_cache_control: Option<String>,
     b2_content_encoding: Option<String>,
     b2_content_type: Option<String>,
+
+    // To check the cache control against the other overrides on build.
+    no_store: bool,
+    cache_lifetime: bool,
+    // To check the expiration against the duration on build.
+    expires: Option<chrono::DateTime<chrono::Utc>>,
+    clock: Option<std::sync::Arc<dyn crate::clock::Clock>>,
 }
 
 impl<'a> DownloadAuthorizationRequestBuilder<'a> {
@@ -1833,18 +2834,11 @@ impl<'a> DownloadAuthorizationRequestBuilder<'a> {
     /// valid.
     ///
     /// This must be between one second and one week, inclusive.
-    pub fn duration(mut self, dur: chrono::Duration)
+    pub fn duration(mut self, dur: impl IntoDuration)
     -> Result<Self, ValidationError> {
-        if dur < chrono::Duration::seconds(1)
-            || dur > chrono::Duration::weeks(1)
-        {
-            return Err(ValidationError::OutOfBounds(
-                "Duration must be between 1 and 604,800 seconds, inclusive"
-                    .into()
-            ));
-        }
+        let dur = validated_download_duration(dur)?;
 
-        self.valid_duration_in_seconds = Some(Duration(dur));
+        self.valid_duration_in_seconds = Some(dur);
         Ok(self)
     }
 
@@ -1866,13 +2860,38 @@ impl<'a> DownloadAuthorizationRequestBuilder<'a> {
     }
 
     /// If specified, download requests must have this expiration.
+    ///
+    /// The expiration cannot be later than the end of the
+    /// [duration](Self::duration).
     pub fn expiration(mut self, expiration: Expires) -> Self {
+        self.expires = Some(expiration.expiration().into());
         self.b2_expires = Some(expiration.value().to_string());
         self
     }
 
+    /// Use the provided [Clock](crate::clock::Clock) to check the
+    /// [expiration](Self::expiration) against the duration.
+    ///
+    /// The default is the [SystemClock](crate::clock::SystemClock).
+    pub fn with_clock(mut self, clock: impl crate::clock::Clock + 'static)
+    -> Self {
+        self.clock = Some(std::sync::Arc::new(clock));
+        self
+    }
+
     /// If specified, download requests must have this cache control.
     pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        use http_types::cache::CacheDirective;
+
+        self.no_store = cache_control.iter()
+            .any(|d| matches!(d, CacheDirective::NoStore));
+        self.cache_lifetime = cache_control.iter()
+            .any(|d| matches!(d,
+                CacheDirective::MaxAge(_)
+                | CacheDirective::SMaxAge(_)
+                | CacheDirective::Immutable
+            ));
+
         self.b2_cache_control = Some(cache_control.value().to_string());
         self
     }
@@ -1890,6 +2909,10 @@ impl<'a> DownloadAuthorizationRequestBuilder<'a> {
     }
 
     /// Build a [DownloadAuthorizationRequest].
+    ///
+    /// Returns [ValidationError::Incompatible] if the header overrides
+    /// contradict each other or the expiration is later than the end of the
+    /// duration; see [DownloadAuthorizationRequestBuilder].
     pub fn build(self)
     -> Result<DownloadAuthorizationRequest<'a>, ValidationError> {
         let bucket_id = self.bucket_id
@@ -1905,6 +2928,29 @@ impl<'a> DownloadAuthorizationRequestBuilder<'a> {
                 "The duration of the authorization token must be set".into()
             ))?;
 
+        if self.no_store && self.b2_expires.is_some() {
+            return Err(ValidationError::Incompatible(
+                "An expiration cannot be set with the no-store cache directive"
+                    .into()
+            ));
+        } else if self.no_store && self.cache_lifetime {
+            return Err(ValidationError::Incompatible(concat!(
+                "The no-store cache directive cannot be combined with max-age,",
+                " s-maxage, or immutable"
+            ).into()));
+        }
+
+        if let Some(expires) = self.expires {
+            let clock = self.clock.unwrap_or_else(crate::clock::system);
+
+            if expires > clock.now() + *valid_duration_in_seconds {
+                return Err(ValidationError::Incompatible(concat!(
+                    "The expiration cannot be later than the end of the ",
+                    "authorization's duration"
+                ).into()));
+            }
+        }
+
         Ok(DownloadAuthorizationRequest {
             bucket_id,
             file_name_prefix,
@@ -1920,11 +2966,20 @@ impl<'a> DownloadAuthorizationRequestBuilder<'a> {
 }
 
 /// A capability token that authorizes downloading files from a private bucket.
-#[derive(Debug)]
+///
+/// A `DownloadAuthorization` can be serialized to pass it to another process
+/// or store it until it expires; the HTTP client is not serialized, and a
+/// deserialized authorization uses a default client. Use
+/// [with_client](Self::with_client) to provide a different one.
+///
+/// The `Debug` output does not include the authorization token.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct DownloadAuthorization<C>
     where C: HttpClient,
 {
+    #[serde(skip, default)]
     client: C,
     api_url: String,
     download_url: String,
@@ -1932,6 +2987,105 @@ pub struct DownloadAuthorization<C>
     bucket_id: String,
     file_name_prefix: String,
     authorization_token: String,
+    overrides: DownloadOverrides,
+    obtained_at: chrono::DateTime<chrono::Utc>,
+    valid_duration: Duration,
+    #[serde(skip, default = "crate::clock::system")]
+    clock: std::sync::Arc<dyn crate::clock::Clock>,
+    #[serde(skip, default)]
+    log_redaction: LogRedaction,
+}
+
+impl<C> fmt::Debug for DownloadAuthorization<C>
+    where C: HttpClient + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("DownloadAuthorization");
+
+        if self.log_redaction.file_names {
+            s.field("client", &"<redacted>");
+        } else {
+            s.field("client", &self.client);
+        }
+
+        s.field("api_url", &self.api_url)
+            .field("download_url", &self.download_url)
+            .field("bucket_id", &self.bucket_id)
+            .field("file_name_prefix",
+                &self.log_redaction.file_name(&self.file_name_prefix))
+            .field("authorization_token", &"<redacted>")
+            .field("overrides", &self.overrides)
+            .field("obtained_at", &self.obtained_at)
+            .field("valid_duration", &self.valid_duration)
+            .field("clock", &self.clock)
+            .field("log_redaction", &self.log_redaction)
+            .finish()
+    }
+}
+
+/// The header overrides that a [DownloadAuthorization] requires downloads to
+/// use.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadOverrides {
+    content_disposition: Option<String>,
+    content_language: Option<String>,
+    expires: Option<String>,
+    cache_control: Option<String>,
+    content_encoding: Option<String>,
+    content_type: Option<String>,
+}
+
+impl DownloadOverrides {
+    /// Combine the overrides `requested` for a download with those required by
+    /// a [DownloadAuthorization], returning the query parameters to send.
+    ///
+    /// Returns [ValidationError::Incompatible] if a requested value differs
+    /// from the required value.
+    fn merge(&self, requested: Self)
+    -> Result<[(&'static str, Option<String>); 6], ValidationError> {
+        let merge = |name: &str, required: &Option<String>, req: Option<String>|
+        -> Result<Option<String>, ValidationError> {
+            match (required, req) {
+                (Some(r), Some(v)) if *r != v => {
+                    Err(ValidationError::Incompatible(format!(
+                        "The authorization requires {} to be {}",
+                        name, r
+                    )))
+                },
+                (Some(r), _) => Ok(Some(r.to_owned())),
+                (None, v) => Ok(v),
+            }
+        };
+
+        Ok([
+            ("b2ContentDisposition", merge("b2ContentDisposition",
+                &self.content_disposition, requested.content_disposition)?),
+            ("b2ContentLanguage", merge("b2ContentLanguage",
+                &self.content_language, requested.content_language)?),
+            ("b2Expires", merge("b2Expires",
+                &self.expires, requested.expires)?),
+            ("b2CacheControl", merge("b2CacheControl",
+                &self.cache_control, requested.cache_control)?),
+            ("b2ContentEncoding", merge("b2ContentEncoding",
+                &self.content_encoding, requested.content_encoding)?),
+            ("b2ContentType", merge("b2ContentType",
+                &self.content_type, requested.content_type)?),
+        ])
+    }
+}
+
+impl<'a> From<&DownloadAuthorizationRequest<'a>> for DownloadOverrides {
+    fn from(req: &DownloadAuthorizationRequest<'a>) -> Self {
+        Self {
+            content_disposition: req.b2_content_disposition.clone(),
+            content_language: req.b2_content_language.clone(),
+            expires: req.b2_expires.clone(),
+            cache_control: req.b2_cache_control.clone(),
+            content_encoding: req.b2_content_encoding.clone(),
+            content_type: req.b2_content_type.clone(),
+        }
+    }
 }
 
 impl<C> DownloadAuthorization<C>
@@ -1942,10 +3096,61 @@ impl<C> DownloadAuthorization<C>
     /// The file prefix that determines what files in the bucket are accessible
     /// via this `DownloadAuthorization`.
     pub fn file_name_prefix(&self) -> &str { &self.file_name_prefix }
+    /// The token to pass in the `Authorization` header or query parameter of
+    /// a download.
+    pub fn authorization_token(&self) -> &str { &self.authorization_token }
+
+    /// The time at which the authorization expires.
+    ///
+    /// This is calculated from the requested duration and the time at which
+    /// the authorization was received.
+    pub fn expiration(&self) -> chrono::DateTime<chrono::Utc> {
+        self.obtained_at + *self.valid_duration
+    }
+
+    /// True if the [expiration](Self::expiration) has passed according to the
+    /// local clock.
+    ///
+    /// Use [renew_download_authorization] to obtain a replacement, preferably
+    /// before this returns `true`.
+    pub fn is_probably_expired(&self) -> bool {
+        self.clock.now() >= self.expiration()
+    }
+
+    /// Use the provided HTTP client for downloads.
+    pub fn with_client(mut self, client: C) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Use the provided [Clock](crate::clock::Clock) for expiration checks.
+    ///
+    /// A deserialized `DownloadAuthorization` uses the
+    /// [SystemClock](crate::clock::SystemClock).
+    pub fn with_clock(mut self, clock: impl crate::clock::Clock + 'static)
+    -> Self {
+        self.clock = std::sync::Arc::new(clock);
+        self
+    }
+
+    /// Hide file names from `Debug` output and error messages; see
+    /// [LogRedaction].
+    ///
+    /// A `DownloadAuthorization` uses the options of the [Authorization] that
+    /// obtained it; a deserialized one does not redact file names.
+    pub fn with_log_redaction(mut self, redaction: LogRedaction) -> Self {
+        self.log_redaction = redaction;
+        self
+    }
+
+    /// The options for hiding sensitive information.
+    pub fn log_redaction(&self) -> LogRedaction { self.log_redaction }
 
     fn from_proto(
         proto: ProtoDownloadAuthorization,
         auth: &Authorization<C>,
+        overrides: DownloadOverrides,
+        valid_duration: Duration,
     ) -> Self {
         Self {
             client: auth.client.clone(),
@@ -1954,6 +3159,11 @@ impl<C> DownloadAuthorization<C>
             bucket_id: proto.bucket_id,
             file_name_prefix: proto.file_name_prefix,
             authorization_token: proto.authorization_token,
+            overrides,
+            obtained_at: auth.now(),
+            valid_duration,
+            clock: auth.clock(),
+            log_redaction: auth.log_redaction(),
         }
     }
 }
@@ -1969,7 +3179,9 @@ struct ProtoDownloadAuthorization {
 /// Generate a download authorization token to download files with a specific
 /// prefix from a private B2 bucket.
 ///
-/// The [Authorization] token must have [Capability::ShareFiles].
+/// The [Authorization] token must have [Capability::ShareFiles]. If it is
+/// restricted to a file name prefix, the requested prefix must begin with it;
+/// otherwise [Error::NamePrefixRestricted] is returned.
 ///
 /// The returned [DownloadAuthorization] can be passed to
 /// [download_file](crate::file::download_file) in place of an [Authorization]
@@ -2013,46 +3225,273 @@ pub async fn get_download_authorization<'a, C, E>(
           E: fmt::Debug + fmt::Display,
 {
     require_capability!(auth, Capability::ShareFiles);
+    require_name_prefix(auth, download_req.file_name_prefix)?;
+
+    let prefix = download_req.file_name_prefix;
+    let overrides = DownloadOverrides::from(&download_req);
+    let valid_duration = download_req.valid_duration_in_seconds;
 
     let res = auth.client.post(auth.api_url("b2_get_download_authorization"))
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(download_req)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
     let proto_auth: B2Result<ProtoDownloadAuthorization> =
-        serde_json::from_slice(&res)?;
+        B2Result::from_response(&res)?;
+
+    let download_auth: Result<_, Error<E>> = proto_auth.map(|a|
+        DownloadAuthorization::from_proto(a, auth, overrides, valid_duration)
+    ).into();
 
-    proto_auth.map(|a| DownloadAuthorization::from_proto(a, auth)).into()
+    download_auth.map_err(|e| auth.log_redaction().error(e, prefix))
 }
 
-/// An authorization to upload file contents to a B2 file.
-#[derive(Deserialize)]
-#[allow(dead_code)]
-#[serde(rename_all = "camelCase")]
-pub struct UploadPartAuthorization<'a, 'b, C, E>
+// Check that `name` is within the authorization's file name prefix, if it has
+// one.
+fn require_name_prefix<C, E>(auth: &Authorization<C>, name: &str)
+-> Result<(), Error<E>>
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
 {
-    #[serde(skip_deserializing)]
-    #[serde(default = "make_none")]
-    auth: Option<&'a mut Authorization<C>>,
-    #[serde(skip_deserializing)]
-    #[serde(default = "make_none")]
-    encryption: Option<&'b ServerSideEncryption>,
-    file_id: String,
-    upload_url: String,
-    authorization_token: String,
+    match auth.capabilities().name_prefix() {
+        Some(prefix) if ! name.starts_with(prefix.as_str()) => Err(
+            Error::NamePrefixRestricted(
+                auth.log_redaction().file_name(prefix).into_owned()
+            )
+        ),
+        _ => Ok(()),
+    }
 }
 
-fn make_none<T>() -> Option<T> { None }
-
-/// Get an [UploadPartAuthorization] to upload data to a new B2 file.
+/// Obtain a new [DownloadAuthorization] with the same bucket, file name prefix,
+/// and header overrides as `existing`, valid for `duration`.
 ///
-/// Use the returned `UploadPartAuthorization` when calling [upload_file_part].
+/// Download authorizations last no more than a week; services that hand out
+/// signed URLs for longer should renew them before they
+/// [expire](DownloadAuthorization::is_probably_expired). The existing
+/// authorization remains valid until its own expiration.
 ///
-/// The `UploadPartAuthorization` is valid for 24 hours or until an endpoint
-/// rejects an upload.
+/// The `duration` must be between one second and one week, inclusive. The
+/// [Authorization] must have [Capability::ShareFiles].
+pub async fn renew_download_authorization<C, E>(
+    auth: &mut Authorization<C>,
+    existing: &DownloadAuthorization<C>,
+    duration: impl IntoDuration,
+) -> Result<DownloadAuthorization<C>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let overrides = existing.overrides.clone();
+
+    let req = DownloadAuthorizationRequest {
+        bucket_id: &existing.bucket_id,
+        file_name_prefix: &existing.file_name_prefix,
+        valid_duration_in_seconds: validated_download_duration(duration)?,
+        b2_content_disposition: overrides.content_disposition,
+        b2_content_language: overrides.content_language,
+        b2_expires: overrides.expires,
+        b2_cache_control: overrides.cache_control,
+        b2_content_encoding: overrides.content_encoding,
+        b2_content_type: overrides.content_type,
+    };
+
+    get_download_authorization(auth, req).await
+}
+
+/// A builder to create a URL to download a file via a [DownloadAuthorization].
+///
+/// The URL contains the authorization token, so anyone with the URL can
+/// download the file until the authorization expires.
+///
+/// If the `DownloadAuthorization` was created with header overrides (e.g., via
+/// [DownloadAuthorizationRequestBuilder::content_disposition]), the URL
+/// includes them automatically; setting a different value for one of those
+/// headers causes [build](Self::build) to fail. Headers that the
+/// authorization did not specify may be set freely.
+///
+/// See <https://www.backblaze.com/b2/docs/b2_download_file_by_name.html> for
+/// information on the override parameters.
+pub struct SignedUrlBuilder<'a, C>
+    where C: HttpClient,
+{
+    auth: &'a DownloadAuthorization<C>,
+    bucket_name: &'a str,
+    file_name: FileName,
+    overrides: DownloadOverrides,
+}
+
+impl<'a, C> SignedUrlBuilder<'a, C>
+    where C: HttpClient + Clone,
+{
+    /// Create a builder for a URL to download the named file.
+    ///
+    /// The authorization only stores the ID of its bucket, so the bucket's name
+    /// must be provided.
+    pub fn new(
+        auth: &'a DownloadAuthorization<C>,
+        bucket_name: &'a str,
+        file_name: impl Into<FileName>,
+    ) -> Self {
+        Self {
+            auth,
+            bucket_name,
+            file_name: file_name.into(),
+            overrides: DownloadOverrides::default(),
+        }
+    }
+
+    /// Override the Content-Disposition header of the download.
+    pub fn content_disposition(mut self, disposition: ContentDisposition)
+    -> Self {
+        self.overrides.content_disposition = Some(disposition.0);
+        self
+    }
+
+    /// Override the Content-Language header of the download.
+    pub fn content_language<S: Into<String>>(mut self, lang: S) -> Self {
+        self.overrides.content_language = Some(lang.into());
+        self
+    }
+
+    /// Override the Expires header of the download.
+    pub fn expiration(mut self, expiration: Expires) -> Self {
+        self.overrides.expires = Some(expiration.value().to_string());
+        self
+    }
+
+    /// Override the Cache-Control header of the download.
+    pub fn cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.overrides.cache_control = Some(cache_control.value().to_string());
+        self
+    }
+
+    /// Override the Content-Encoding header of the download.
+    pub fn content_encoding(mut self, encoding: ContentEncoding) -> Self {
+        self.overrides.content_encoding =
+            Some(format!("{}", encoding.encoding()));
+        self
+    }
+
+    /// Override the Content-Type header of the download.
+    pub fn content_type(mut self, content_type: impl Into<Mime>) -> Self {
+        self.overrides.content_type = Some(content_type.into().to_string());
+        self
+    }
+
+    /// Build the download URL.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [ValidationError::OutOfBounds] if the file name does not begin
+    ///   with the authorization's [file name
+    ///   prefix](DownloadAuthorization::file_name_prefix).
+    /// * Returns [ValidationError::Incompatible] if an override differs from
+    ///   the value required by the authorization.
+    pub fn build(self) -> Result<String, ValidationError> {
+        if ! self.file_name.as_str()
+            .starts_with(self.auth.file_name_prefix())
+        {
+            return Err(ValidationError::OutOfBounds(format!(
+                "The file name must begin with the authorized prefix: {}",
+                self.auth.file_name_prefix()
+            )));
+        }
+
+        let params = self.auth.overrides.merge(self.overrides)?;
+
+        let mut url = format!("{}?Authorization={}",
+            crate::account::file_url(
+                &self.auth.download_url,
+                self.bucket_name,
+                &percent_encode!(self.file_name.as_str())
+            ),
+            percent_encode!(self.auth.authorization_token),
+        );
+
+        for (name, value) in params.iter() {
+            if let Some(value) = value {
+                url.push('&');
+                url.push_str(name);
+                url.push('=');
+                url.push_str(&percent_encode!(value));
+            }
+        }
+
+        Ok(url)
+    }
+}
+
+/// An authorization to upload file contents to a B2 file.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadPartAuthorization<'a, 'b, C, E>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    #[serde(skip_deserializing)]
+    #[serde(default = "make_none")]
+    auth: Option<&'a mut Authorization<C>>,
+    #[serde(skip_deserializing)]
+    #[serde(default = "make_none")]
+    encryption: Option<&'b ServerSideEncryption>,
+    file_id: String,
+    upload_url: String,
+    authorization_token: String,
+    #[serde(skip_deserializing)]
+    part_sizes: PartSizes,
+}
+
+fn make_none<T>() -> Option<T> { None }
+
+/// The sizes of the parts uploaded via an [UploadPartAuthorization], used to
+/// detect parts that are too small before B2 rejects the finished file.
+#[derive(Debug, Default)]
+struct PartSizes {
+    minimum: u64,
+    highest_part: u16,
+    // The number and size of a part smaller than the minimum.
+    small_part: Option<(u16, u64)>,
+}
+
+impl PartSizes {
+    /// Check that uploading `size` bytes as `part_number` would not leave a
+    /// part other than the last smaller than the minimum.
+    fn check(&self, part_number: u16, size: u64)
+    -> Result<(), ValidationError> {
+        let too_small = |part_number, size| ValidationError::PartTooSmall {
+            part_number,
+            size,
+            minimum: self.minimum,
+        };
+
+        match self.small_part {
+            Some((num, size)) if num < part_number =>
+                Err(too_small(num, size)),
+            _ if size < self.minimum && part_number < self.highest_part =>
+                Err(too_small(part_number, size)),
+            _ => Ok(()),
+        }
+    }
+
+    fn record(&mut self, part_number: u16, size: u64) {
+        self.highest_part = self.highest_part.max(part_number);
+
+        if size < self.minimum {
+            self.small_part = Some((part_number, size));
+        } else if self.small_part.map(|(num, _)| num) == Some(part_number) {
+            self.small_part = None;
+        }
+    }
+}
+
+/// Get an [UploadPartAuthorization] to upload data to a new B2 file.
+///
+/// Use the returned `UploadPartAuthorization` when calling [upload_file_part].
+///
+/// The `UploadPartAuthorization` is valid for 24 hours or until an endpoint
+/// rejects an upload.
 ///
 /// If uploading multiple parts concurrently, each thread or task needs its own
 /// authorization.
@@ -2097,12 +3536,13 @@ pub async fn get_upload_part_authorization_by_id<'a, 'b, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(json!({ "fileId": file_id.as_ref() }))
-        .send().await?;
+        .send_keep_headers().await?;
 
     let upload_auth: B2Result<UploadPartAuthorization<'_, '_, _, _>> =
-        serde_json::from_slice(&res)?;
+        B2Result::from_response(&res)?;
 
     upload_auth.map(move |mut a| {
+        a.part_sizes.minimum = auth.minimum_part_size();
         a.auth = Some(auth);
         a.encryption = encryption;
         a
@@ -2123,6 +3563,11 @@ pub struct UploadAuthorization<'a, C, E>
     bucket_id: String,
     upload_url: String,
     authorization_token: String,
+    #[serde(skip_deserializing)]
+    #[serde(default = "chrono::Utc::now")]
+    obtained_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_deserializing)]
+    rejected: bool,
 }
 
 impl<'a, C, E> UploadAuthorization<'a, C, E>
@@ -2130,6 +3575,28 @@ impl<'a, C, E> UploadAuthorization<'a, C, E>
           E: fmt::Debug + fmt::Display,
 {
     pub fn bucket_id(&self) -> &str { &self.bucket_id }
+
+    /// True if the authorization is more than 24 hours old and can no longer
+    /// be used.
+    pub fn is_expired(&self) -> bool {
+        self.now() - self.obtained_at >= chrono::Duration::hours(24)
+    }
+
+    /// The current time according to the [Authorization]'s clock.
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        self.auth.as_ref().map(|auth| auth.now())
+            .unwrap_or_else(chrono::Utc::now)
+    }
+
+    /// True if the authorization can still be used to upload files.
+    ///
+    /// An authorization is no longer usable once it has expired, or once an
+    /// upload using it has failed because the upload URL was busy or
+    /// unreachable; obtain a new authorization via [get_upload_authorization]
+    /// to continue uploading.
+    pub fn is_valid(&self) -> bool {
+        ! (self.rejected || self.is_expired())
+    }
 }
 
 /// Obtain an authorization to upload files to a bucket.
@@ -2141,6 +3608,7 @@ impl<'a, C, E> UploadAuthorization<'a, C, E>
 ///
 /// The `UploadAuthorization` is valid for 24 hours or until an upload attempt
 /// is rejected. You can make multiple file uploads with a single authorization.
+/// Use [UploadAuthorization::is_valid] to check whether it can still be used.
 ///
 /// The [Authorization] must have [Capability::WriteFiles].
 ///
@@ -2177,12 +3645,16 @@ pub async fn get_upload_authorization_by_id<'a, 'b, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(json!({ "bucketId": bucket_id.as_ref() }))
-        .send().await?;
+        .send_keep_headers().await?;
 
     let upload_auth: B2Result<UploadAuthorization<'_, _, _>> =
-        serde_json::from_slice(&res)?;
+        B2Result::from_response(&res)?;
 
-    upload_auth.map(move |mut a| { a.auth = Some(auth); a }).into()
+    upload_auth.map(move |mut a| {
+        a.obtained_at = auth.now();
+        a.auth = Some(auth);
+        a
+    }).into()
 }
 
 /// Hide a file so that it cannot be downloaded by name.
@@ -2240,10 +3712,11 @@ pub async fn hide_file_by_name<C, E>(
             "bucketId": bucket_id.as_ref(),
             "fileName": file_name.as_ref(),
         }))
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file: B2Result<File> = serde_json::from_slice(&res)?;
-    file.into()
+    let file: B2Result<File> = B2Result::from_response(&res)?;
+    Result::from(file)
+        .map_err(|e| auth.log_redaction().error(e, file_name.as_ref()))
 }
 
 /// A request to list the names of files stored in a bucket.
@@ -2262,6 +3735,11 @@ impl<'a> ListFileNames<'a> {
     pub fn builder() -> ListFileNamesBuilder<'a> {
         ListFileNamesBuilder::default()
     }
+
+    /// The maximum number of files that will be returned by this request.
+    ///
+    /// If `None`, B2 will return up to 100 files.
+    pub fn max_file_count(&self) -> Option<u16> { self.max_file_count }
 }
 
 /// A builder for a [ListFileNames] request.
@@ -2354,13 +3832,41 @@ impl<'a> ListFileNamesBuilder<'a> {
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct FileNameList {
-    files: Vec<File>,
+struct FileNameList<T = File> {
+    files: Vec<T>,
     next_file_name: Option<String>,
 }
 
+/// Request a page of file names, deserializing each file as a `T`.
+async fn list_file_names_page<T, C, E>(
+    auth: &mut Authorization<C>,
+    request: &ListFileNames<'_>,
+) -> Result<FileNameList<T>, Error<E>>
+    where T: serde::de::DeserializeOwned,
+          C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    require_capability!(auth, Capability::ListFiles);
+
+    let res = auth.client.post(auth.api_url("b2_list_file_names"))
+        .expect("Invalid URL")
+        .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_body_json(serde_json::to_value(request)?)
+        .send_keep_headers().await?;
+
+    let files: B2Result<FileNameList<T>> = B2Result::from_response(&res)?;
+    let files: Result<_, Error<E>> = files.into();
+
+    files.map_err(|e| {
+        auth.log_redaction().error(e, request.prefix.unwrap_or_default())
+    })
+}
+
 /// Get a list of file names in a bucket.
 ///
+/// Only the newest version of each file is listed, and hidden files are
+/// omitted; use [list_file_versions] to list old versions and hide markers.
+///
 /// See <https://www.backblaze.com/b2/docs/b2_list_file_names.html> for more
 /// information, including setting filename prefixes for filtering and a
 /// delimiter for working with virtual folders.
@@ -2372,28 +3878,222 @@ pub async fn list_file_names<'a, C, E>(
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
 {
+    let files: FileNameList = list_file_names_page(auth, &request).await?;
+
+    if let Some(next_file) = files.next_file_name {
+        let mut request = request;
+        request.start_file_name = Some(next_file);
+
+        Ok((files.files, Some(request)))
+    } else {
+        Ok((files.files, None))
+    }
+}
+
+/// Get a list of all file names in a bucket, making multiple requests as
+/// needed.
+///
+/// Requests are made until all matching files have been listed or `limit`
+/// files have been received. If the `request` does not set a
+/// [max_file_count](ListFileNamesBuilder::max_file_count), 1,000 files are
+/// requested per call.
+///
+/// B2 charges a transaction for every 1,000 files returned, so requesting more
+/// than 1,000 files per call reduces the number of round trips but not the
+/// cost of the listing. Setting a `limit` guards against unexpectedly
+/// listing millions of files.
+///
+/// See [list_file_names] for further information.
+pub async fn list_all_file_names<C, E>(
+    auth: &mut Authorization<C>,
+    request: ListFileNames<'_>,
+    limit: Option<usize>,
+) -> Result<Vec<File>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let page_size = request.max_file_count.unwrap_or(1000);
+
+    let mut files = vec![];
+    let mut request = Some(request);
+
+    while let Some(mut req) = request {
+        let remaining = limit.map(|l| l.saturating_sub(files.len()));
+
+        req.max_file_count = match remaining {
+            Some(0) => break,
+            Some(n) if n < page_size as usize => Some(n as u16),
+            _ => Some(page_size),
+        };
+
+        let (mut page, next_req) = list_file_names(auth, req).await?;
+        files.append(&mut page);
+        request = next_req;
+    }
+
+    if let Some(limit) = limit {
+        files.truncate(limit);
+    }
+
+    Ok(files)
+}
+
+/// Get a list of file names in the given bucket.
+///
+/// This is a shortcut for [list_file_names] with the bucket ID taken from
+/// `bucket`, optionally filtering by the filename `prefix`. Up to 100 files are
+/// returned; to continue the listing, pass the returned [ListFileNames]
+/// request to [list_file_names].
+pub async fn list_bucket_files<'a, C, E>(
+    auth: &mut Authorization<C>,
+    bucket: &'a Bucket,
+    prefix: Option<&'a str>,
+) -> Result<(Vec<File>, Option<ListFileNames<'a>>), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let mut req = ListFileNames::builder()
+        .bucket_id(bucket.bucket_id());
+
+    if let Some(prefix) = prefix {
+        req = req.prefix(prefix).map_err(|e|
+            ValidationError::BadFormat(e.to_string())
+        )?;
+    }
+
+    // The bucket ID is always set, so this cannot fail.
+    let req = req.build().unwrap();
+
+    list_file_names(auth, req).await
+}
+
+/// A summary of a file in a bucket, as listed by [list_file_summaries].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSummary {
+    file_name: String,
+    file_id: String,
+    content_length: u64,
+    content_sha1: Option<String>,
+    // Only the large file checksum is read from the file info.
+    #[serde(default)]
+    file_info: SummaryFileInfo,
+    upload_timestamp: i64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct SummaryFileInfo {
+    large_file_sha1: Option<String>,
+}
+
+impl FileSummary {
+    /// The name of the file.
+    pub fn file_name(&self) -> &str { &self.file_name }
+
+    /// The B2 ID of the file.
+    pub fn file_id(&self) -> &str { &self.file_id }
+
+    /// The number of bytes stored in the file.
+    pub fn content_length(&self) -> u64 { self.content_length }
+
+    /// The SHA-1 checksum of the bytes in the file.
+    ///
+    /// For large files, this is the `large_file_sha1` entry of the file's info
+    /// if it was set when the file was uploaded.
+    pub fn sha1_checksum(&self) -> Option<&str> {
+        match self.content_sha1.as_deref() {
+            Some("none") | None => self.file_info.large_file_sha1.as_deref(),
+            sha1 => sha1,
+        }
+    }
+
+    /// The date and time at which the file was uploaded.
+    pub fn upload_time(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::{TimeZone as _, Utc};
+
+        Utc.timestamp_millis_opt(self.upload_timestamp).single()
+    }
+}
+
+/// List every file in a bucket as a stream of [FileSummary]s.
+///
+/// This lists the same files as [list_file_names], 1,000 files per request,
+/// optionally filtering by the filename `prefix`. Only the name, ID, size,
+/// checksum, and upload time of each file are kept, and only one page of
+/// files is held in memory at a time, so it is suitable for building
+/// manifests of buckets with millions of files.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// If a request fails, its error is the last item of the stream.
+///
+/// The [Authorization] must have [Capability::ListFiles].
+///
+/// This is only available with the `futures` feature.
+#[cfg(feature = "futures")]
+pub fn list_file_summaries<'a, C, E>(
+    auth: &'a mut Authorization<C>,
+    bucket_id: Option<&str>,
+    prefix: Option<&str>,
+) -> Result<
+    impl futures_util::Stream<Item = Result<FileSummary, Error<E>>> + 'a,
+    Error<E>
+>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display + 'a,
+{
+    struct State<'a, C>
+        where C: HttpClient,
+    {
+        auth: &'a mut Authorization<C>,
+        bucket_id: String,
+        prefix: Option<String>,
+        // `None` once the last page has been requested.
+        start_file_name: Option<Option<String>>,
+        page: std::vec::IntoIter<FileSummary>,
+    }
+
     require_capability!(auth, Capability::ListFiles);
 
-    let res = auth.client.post(auth.api_url("b2_list_file_names"))
-        .expect("Invalid URL")
-        .with_header("Authorization", &auth.authorization_token).unwrap()
-        .with_body_json(serde_json::to_value(&request)?)
-        .send().await?;
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
 
-    let files: B2Result<FileNameList> = serde_json::from_slice(&res)?;
-    match files {
-        B2Result::Ok(files) => {
-            if let Some(next_file) = files.next_file_name {
-                let mut request = request;
-                request.start_file_name = Some(next_file);
+    let prefix = prefix.map(|prefix|
+        validated_file_name(prefix)
+            .map(ToOwned::to_owned)
+            .map_err(|e| ValidationError::BadFormat(e.to_string()))
+    ).transpose()?;
 
-                Ok((files.files, Some(request)))
-            } else {
-                Ok((files.files, None))
+    let state = State {
+        auth,
+        bucket_id,
+        prefix,
+        start_file_name: Some(None),
+        page: vec![].into_iter(),
+    };
+
+    Ok(futures_util::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(file) = state.page.next() {
+                return Some((Ok(file), state));
             }
-        },
-        B2Result::Err(e) => Err(e.into()),
-    }
+
+            let request = ListFileNames {
+                bucket_id: &state.bucket_id,
+                start_file_name: state.start_file_name.take()?,
+                max_file_count: Some(1000),
+                prefix: state.prefix.as_deref(),
+                delimiter: None,
+            };
+
+            match list_file_names_page(state.auth, &request).await {
+                Ok(files) => {
+                    state.start_file_name = files.next_file_name.map(Some);
+                    state.page = files.files.into_iter();
+                },
+                Err(e) => return Some((Err(e), state)),
+            }
+        }
+    }))
 }
 
 /// A request to list the names of files stored in a bucket.
@@ -2407,6 +4107,14 @@ pub struct ListFileVersions<'a> {
     max_file_count: Option<u16>,
     prefix: Option<&'a str>,
     delimiter: Option<char>,
+    #[serde(skip)]
+    single_file: bool,
+    #[serde(skip)]
+    exclude_hidden: bool,
+    // The name of the last file listed and whether its newest version is a
+    // hide marker, so hidden files can be removed across pages.
+    #[serde(skip)]
+    last_file: Option<(String, bool)>,
 }
 
 impl<'a> ListFileVersions<'a> {
@@ -2424,6 +4132,8 @@ pub struct ListFileVersionsBuilder<'a> {
     max_file_count: Option<u16>,
     prefix: Option<&'a str>,
     delimiter: Option<char>,
+    single_file: Option<&'a str>,
+    exclude_hidden: bool,
 }
 
 impl<'a> ListFileVersionsBuilder<'a> {
@@ -2497,6 +4207,31 @@ impl<'a> ListFileVersionsBuilder<'a> {
         }
     }
 
+    /// Only list the versions of the file named `file_name`.
+    ///
+    /// The listing starts at `file_name` and stops once a file with a
+    /// different name is reached, including files of which `file_name` is a
+    /// prefix, so only versions of this file are returned.
+    ///
+    /// This replaces any [prefix](Self::prefix); the starting file name is set
+    /// to `file_name` unless [start_file_name](Self::start_file_name) is set.
+    pub fn for_single_file(mut self, file_name: &'a str)
+    -> Result<Self, FileNameValidationError> {
+        self.single_file = Some(validated_file_name(file_name)?);
+        Ok(self)
+    }
+
+    /// Omit every version of files whose newest version is a hide marker.
+    ///
+    /// This lists only the files that [list_file_names] would list, along
+    /// with their older versions. All versions of a file that was hidden and
+    /// then uploaded again are listed, including the hide marker. Unfinished
+    /// large files are always listed.
+    pub fn exclude_hidden(mut self) -> Self {
+        self.exclude_hidden = true;
+        self
+    }
+
     /// Build a [ListFileVersions] request.
     ///
     /// Returns an error if the bucket ID has not been set.
@@ -2513,13 +4248,24 @@ impl<'a> ListFileVersionsBuilder<'a> {
             );
         }
 
+        let (start_file_name, prefix) = match self.single_file {
+            Some(name) => (
+                self.start_file_name.or_else(|| Some(name.to_owned())),
+                Some(name),
+            ),
+            None => (self.start_file_name, self.prefix),
+        };
+
         Ok(ListFileVersions {
             bucket_id,
-            start_file_name: self.start_file_name,
+            start_file_name,
             start_file_id: self.start_file_id,
             max_file_count: self.max_file_count,
-            prefix: self.prefix,
+            prefix,
             delimiter: self.delimiter,
+            single_file: self.single_file.is_some(),
+            exclude_hidden: self.exclude_hidden,
+            last_file: None,
         })
     }
 }
@@ -2537,6 +4283,16 @@ struct FileVersionList {
 /// Files are listed in alphabetical order by filename, then by upload timestamp
 /// sorted descending.
 ///
+/// Unlike [list_file_names], which lists only the newest version of each file
+/// and omits hidden files, this lists hide markers and every version of hidden
+/// files unless the request was built with
+/// [exclude_hidden](ListFileVersionsBuilder::exclude_hidden).
+///
+/// If the request was built with
+/// [for_single_file](ListFileVersionsBuilder::for_single_file), only versions
+/// of that file are returned, and no further request is returned once the
+/// listing moves past it.
+///
 /// See <https://www.backblaze.com/b2/docs/b2_list_file_versions.html> for more
 /// information.
 #[allow(clippy::needless_lifetimes)] // False positive.
@@ -2553,23 +4309,304 @@ pub async fn list_file_versions<'a, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(&request)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let files: B2Result<FileVersionList> = serde_json::from_slice(&res)?;
+    let files: B2Result<FileVersionList> = B2Result::from_response(&res)?;
     match files {
-        B2Result::Ok(files) => {
+        B2Result::Ok(mut files) => {
             let mut request = request;
+            let mut done = files.next_file_name.is_none();
+
+            if let (true, Some(name)) = (request.single_file, request.prefix) {
+                let count = files.files.len();
+                files.files.retain(|f| f.file_name == name);
+
+                // Versions are sorted by name; once we see a different name
+                // there are no more versions of the file.
+                done |= files.files.len() < count
+                    || files.next_file_name.as_deref() != Some(name);
+            }
 
-            if files.next_file_name.is_some() {
+            if request.exclude_hidden {
+                remove_hidden_files(&mut files.files, &mut request.last_file);
+            }
+
+            if done {
+                Ok((files.files, None))
+            } else {
                 request.start_file_name = files.next_file_name;
                 request.start_file_id = files.next_file_id;
 
                 Ok((files.files, Some(request)))
-            } else {
-                Ok((files.files, None))
             }
         },
-        B2Result::Err(e) => Err(e.into()),
+        B2Result::Err(e) => Err(auth.log_redaction()
+            .error(e.into(), request.prefix.unwrap_or_default())),
+    }
+}
+
+// Remove every version of each file whose newest version is a hide marker.
+//
+// Versions are listed by name, newest first; `last` is the name of the
+// previous file and whether it is hidden.
+fn remove_hidden_files(files: &mut Vec<File>, last: &mut Option<(String, bool)>)
+{
+    files.retain(|f| {
+        if f.action == FileAction::Start {
+            return true;
+        }
+
+        match last {
+            Some((name, hidden)) if *name == f.file_name => ! *hidden,
+            _ => {
+                let hidden = f.action == FileAction::Hide;
+                *last = Some((f.file_name.clone(), hidden));
+                ! hidden
+            },
+        }
+    });
+}
+
+/// Delete every version of the file named `file_name` in the specified bucket,
+/// including hide markers and unfinished large files.
+///
+/// Returns the file versions that were deleted. If `dry_run` is `true`, the
+/// versions are listed and returned but nothing is deleted.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::ListFiles], and unless `dry_run`
+/// is set, [Capability::DeleteFiles]; bypassing governance rules also requires
+/// [Capability::BypassGovernance]. If a deletion fails, the versions listed
+/// after it are not deleted.
+///
+/// Rather than following a single listing that could miss or repeat versions
+/// uploaded by other clients in the meantime, the versions are listed again
+/// from the start after each batch is deleted, and the function returns once
+/// a listing finds no versions. Every version that exists when the function
+/// is called is deleted, as are any versions uploaded before the final
+/// listing.
+pub async fn delete_all_file_versions<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    file_name: &str,
+    bypass_governance: BypassGovernance,
+    dry_run: bool,
+) -> Result<Vec<DeletedFile>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    require_delete_capabilities(auth, bypass_governance, dry_run)?;
+
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+    let bucket_id = bucket_id.as_str();
+
+    let first_page = || ListFileVersions {
+        bucket_id,
+        start_file_name: Some(file_name.to_owned()),
+        start_file_id: None,
+        max_file_count: Some(1000),
+        prefix: Some(file_name),
+        delimiter: None,
+        single_file: true,
+        exclude_hidden: false,
+        last_file: None,
+    };
+
+    if dry_run {
+        let mut versions = vec![];
+        let mut request = Some(first_page());
+
+        while let Some(req) = request {
+            let (files, next_req) = list_file_versions(auth, req).await?;
+
+            request = next_req;
+            versions.extend(files);
+        }
+
+        return delete_file_versions(auth, versions, bypass_governance, true)
+            .await;
+    }
+
+    let mut deleted = vec![];
+
+    loop {
+        let (files, _) = list_file_versions(auth, first_page()).await?;
+
+        if files.is_empty() {
+            return Ok(deleted);
+        }
+
+        deleted.extend(
+            delete_file_versions(auth, files, bypass_governance, false).await?
+        );
+    }
+}
+
+/// Ensure that `auth` may delete files.
+///
+/// No capabilities beyond listing files are needed for a dry run.
+pub(crate) fn require_delete_capabilities<C, E>(
+    auth: &Authorization<C>,
+    bypass_governance: BypassGovernance,
+    dry_run: bool,
+) -> Result<(), Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    require_capability!(auth, Capability::ListFiles);
+
+    if ! dry_run {
+        require_capability!(auth, Capability::DeleteFiles);
+
+        if matches!(bypass_governance, BypassGovernance::Yes) {
+            require_capability!(auth, Capability::BypassGovernance);
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the given file versions, or only return them if `dry_run` is set.
+pub(crate) async fn delete_file_versions<C, E>(
+    auth: &mut Authorization<C>,
+    files: Vec<File>,
+    bypass_governance: BypassGovernance,
+    dry_run: bool,
+) -> Result<Vec<DeletedFile>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    if dry_run {
+        return Ok(files.into_iter()
+            .map(|f| DeletedFile { file_id: f.file_id, file_name: f.file_name })
+            .collect());
+    }
+
+    let mut deleted = Vec::with_capacity(files.len());
+
+    for file in files {
+        deleted.push(
+            delete_file_version(auth, file, bypass_governance).await?
+        );
+    }
+
+    Ok(deleted)
+}
+
+/// Get the current version of a file.
+///
+/// Returns the newest version of the file named `file_name` in the specified
+/// bucket. If the newest version is a hide marker, or no file with that name
+/// exists, returns `None`. Unfinished large file uploads are ignored.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::ListFiles].
+pub async fn get_latest_file_version<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    file_name: &str,
+) -> Result<Option<File>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+    let bucket_id = bucket_id.as_str();
+
+    let mut request = Some(ListFileVersions {
+        bucket_id,
+        start_file_name: Some(file_name.to_owned()),
+        start_file_id: None,
+        max_file_count: Some(10),
+        prefix: Some(file_name),
+        delimiter: None,
+        single_file: true,
+        exclude_hidden: false,
+        last_file: None,
+    });
+
+    while let Some(req) = request {
+        let (files, next_req) = list_file_versions(auth, req).await?;
+
+        // Versions are sorted newest first.
+        for file in files.into_iter() {
+            match file.action {
+                FileAction::Start => continue,
+                FileAction::Hide => return Ok(None),
+                _ => return Ok(Some(file)),
+            }
+        }
+
+        request = next_req;
+    }
+
+    Ok(None)
+}
+
+/// Rename a file by copying its current version to `to_name`, then deleting
+/// the original version.
+///
+/// The file's content and metadata are copied unchanged, and the new [File]
+/// is returned. If the file has older versions, they are not copied, and the
+/// most recent of them becomes the current version of `from_name`; use
+/// [delete_all_file_versions] to remove them.
+///
+/// B2 has no rename operation, so other clients may briefly see the file under
+/// both names. Only files smaller than 5 GB can be renamed; see [copy_file].
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::ListFiles],
+/// [Capability::WriteFiles], and [Capability::DeleteFiles].
+///
+/// # Errors
+///
+/// * If `to_name` is not a valid file name, returns
+///   [ValidationError::BadFormat].
+/// * If no file named `from_name` exists, returns
+///   [ValidationError::MissingData].
+/// * If the copy succeeds but the original cannot be deleted, returns
+///   [Error::RenameIncomplete] with the new file; the copy is not removed.
+pub async fn rename_file<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    from_name: &str,
+    to_name: &str,
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    require_capability!(auth, Capability::ListFiles);
+    require_capability!(auth, Capability::WriteFiles);
+    require_capability!(auth, Capability::DeleteFiles);
+
+    let redaction = auth.log_redaction();
+
+    let to_name = validated_file_name(to_name)
+        .map_err(|e| ValidationError::BadFormat(e.to_string()))?;
+
+    let source = get_latest_file_version(auth, bucket_id, from_name).await?
+        .ok_or_else(|| ValidationError::MissingData(format!(
+            "No file named {} exists",
+            redaction.file_name(from_name)
+        )))?;
+
+    let copy = CopyFile::builder()
+        .source_file_id(source.file_id())
+        .destination_file_name(to_name)
+        .expect("The file name was already validated")
+        .metadata_directive(MetadataDirective::Copy)
+        .build()?;
+
+    let file = copy_file(auth, copy).await?;
+
+    match delete_file_version(auth, source, BypassGovernance::No).await {
+        Ok(_) => Ok(file),
+        Err(error) => Err(Error::RenameIncomplete {
+            file: Box::new(file),
+            error: Box::new(error),
+        }),
     }
 }
 
@@ -2661,9 +4698,9 @@ pub async fn list_file_parts<'a, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(&request)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let parts: B2Result<FilePartList> = serde_json::from_slice(&res)?;
+    let parts: B2Result<FilePartList> = B2Result::from_response(&res)?;
     match parts {
         B2Result::Ok(parts) => {
             if let Some(next_part) = parts.next_part_number {
@@ -2781,9 +4818,9 @@ pub async fn list_unfinished_large_files<'a, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(&request)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let files: B2Result<FileIdList> = serde_json::from_slice(&res)?;
+    let files: B2Result<FileIdList> = B2Result::from_response(&res)?;
     match files {
         B2Result::Ok(files) => {
             if let Some(next_file_id) = files.next_file_id {
@@ -3090,9 +5127,9 @@ pub async fn start_large_file<'a, C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(file)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let file: B2Result<File> = serde_json::from_slice(&res)?;
+    let file: B2Result<File> = B2Result::from_response(&res)?;
     file.into()
 }
 
@@ -3201,6 +5238,9 @@ impl<'a> UpdateFileLegalHoldBuilder<'a> {
 // TODO: B2 returns the same data we sent it. Not sure there's a reason to do
 // the same - change or continue returning ()?
 /// Enable or disable a legal hold on a file.
+///
+/// The bucket containing the file must have File Lock enabled; otherwise
+/// [Error::FeatureNotEnabled] is returned.
 pub async fn update_file_legal_hold<C, E>(
     auth: &mut Authorization<C>,
     file_update: UpdateFileLegalHold<'_>
@@ -3214,10 +5254,10 @@ pub async fn update_file_legal_hold<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(file_update)?)
-        .send().await?;
+        .send_keep_headers().await?;
 
-    let res: B2Result<UpdateFileLegalHold> = serde_json::from_slice(&res)?;
-    res.map(|_| ()).into()
+    let res: B2Result<UpdateFileLegalHold> = B2Result::from_response(&res)?;
+    Result::from(res.map(|_| ())).map_err(Error::with_object_lock_context)
 }
 
 /// A request to update file retention settings on a file.
@@ -3325,7 +5365,8 @@ impl<'a> UpdateFileRetentionBuilder<'a> {
 /// The retention settings for files locked with [FileRetentionMode::Compliance]
 /// cannot be removed or shortened, but their retention dates can be extended.
 ///
-/// The bucket containing the file must have File Lock enabled.
+/// The bucket containing the file must have File Lock enabled; otherwise
+/// [Error::FeatureNotEnabled] is returned.
 pub async fn update_file_retention<C, E>(
     auth: &mut Authorization<C>,
     retention_update: UpdateFileRetention<'_>,
@@ -3343,10 +5384,50 @@ pub async fn update_file_retention<C, E>(
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
         .with_body_json(serde_json::to_value(retention_update)?)
-        .send().await?;
+        .send_keep_headers().await?;
+
+    let res: B2Result<UpdateFileRetention> = B2Result::from_response(&res)?;
+    Result::from(res.map(|_| ())).map_err(Error::with_object_lock_context)
+}
+
+/// Calculate the SHA1 checksum of `data` as a lowercase hexadecimal string.
+///
+/// This is the format B2 uses for file and part checksums, so the result can
+/// be passed to [UploadFileBuilder::sha1_checksum] or compared with
+/// [File::sha1_checksum] to determine whether B2 already has the data.
+pub fn compute_sha1(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
 
-    let res: B2Result<UpdateFileRetention> = serde_json::from_slice(&res)?;
-    res.map(|_| ()).into()
+/// A reader that calculates the SHA1 checksum of the data read through it.
+///
+/// Use this to checksum data while it is read rather than holding it to pass
+/// to [compute_sha1].
+pub(crate) struct Sha1Reader<R> {
+    inner: R,
+    sha1: sha1_smol::Sha1,
+}
+
+impl<R> Sha1Reader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, sha1: sha1_smol::Sha1::new() }
+    }
+
+    /// The checksum of the data read so far, in the format of
+    /// [compute_sha1].
+    pub(crate) fn finalize(self) -> String {
+        self.sha1.digest().to_string()
+    }
+}
+
+impl<R> std::io::Read for Sha1Reader<R>
+    where R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.sha1.update(&buf[.. len]);
+        Ok(len)
+    }
 }
 
 /// A request to upload a file to B2.
@@ -3360,6 +5441,8 @@ pub struct UploadFile<'a> {
     legal_hold: Option<LegalHoldValue>,
     file_retention: Option<(FileRetentionMode, i64)>,
     encryption: Option<ServerSideEncryption>,
+    skip_if_unchanged: bool,
+    expect_continue: bool,
 }
 
 impl<'a> UploadFile<'a> {
@@ -3399,6 +5482,8 @@ pub struct UploadFileBuilder<'a> {
     file_retention_mode: Option<FileRetentionMode>,
     file_retention_time: Option<i64>,
     encryption: Option<ServerSideEncryption>,
+    skip_if_unchanged: bool,
+    expect_continue: bool,
 }
 
 impl<'a> UploadFileBuilder<'a> {
@@ -3433,6 +5518,14 @@ impl<'a> UploadFileBuilder<'a> {
     ///
     /// B2 will use this to verify the accuracy of the file upload, and it will
     /// be returned in the header `X-Bz-Content-Sha1` when downloading the file.
+    ///
+    /// b2-client does not calculate the checksum itself, so a previously
+    /// computed value (e.g., from [compute_sha1]) can be reused; it is sent
+    /// as provided. [build](Self::build) returns
+    /// [ValidationError::BadFormat] unless the checksum is 40 hexadecimal
+    /// digits, `do_not_verify`, or `hex_digits_at_end`. If B2 finds that the
+    /// data does not match the checksum, the upload returns
+    /// [Error::ChecksumMismatch].
     pub fn sha1_checksum(mut self, checksum: &'a str) -> Self {
         self.sha1_checksum = Some(checksum);
         self
@@ -3580,6 +5673,37 @@ impl<'a> UploadFileBuilder<'a> {
         self
     }
 
+    /// Skip the upload if the latest version of the file in B2 already has
+    /// the same SHA1 checksum.
+    ///
+    /// Before uploading, [upload_file] looks up the newest version of the file
+    /// and, if its checksum matches [sha1_checksum](Self::sha1_checksum),
+    /// returns that version instead of uploading the data again. Hidden files
+    /// and unfinished large files never match. For large files, the checksum
+    /// is compared with the `large_file_sha1` file information.
+    ///
+    /// The [sha1_checksum](Self::sha1_checksum) is required, and the
+    /// [Authorization] must have [Capability::ListFiles].
+    pub fn skip_if_sha1_matches(mut self) -> Self {
+        self.skip_if_unchanged = true;
+        self
+    }
+
+    /// Ask B2 to accept the request before the file data is sent.
+    ///
+    /// The upload is sent with an `Expect: 100-continue` header so that B2 can
+    /// reject it, e.g. when the upload URL is too busy or the authorization has
+    /// expired, without first receiving the whole file. This saves bandwidth
+    /// on large uploads at the cost of a round trip before sending the data.
+    ///
+    /// Only [HttpClient] backends that implement
+    /// [with_expect_continue](HttpClient::with_expect_continue) send the
+    /// header; currently this is the [SurfClient](crate::client::SurfClient).
+    pub fn expect_continue(mut self) -> Self {
+        self.expect_continue = true;
+        self
+    }
+
     /// Build an [UploadFile] request.
     pub fn build(self) -> Result<UploadFile<'a>, ValidationError> {
         let file_name = self.file_name.ok_or_else(||
@@ -3589,7 +5713,15 @@ impl<'a> UploadFileBuilder<'a> {
         let content_type = self.content_type
             .unwrap_or_else(|| "b2/x-auto".into());
 
-        let sha1_checksum = self.sha1_checksum.unwrap_or("do_not_verify");
+        if self.skip_if_unchanged && self.sha1_checksum.is_none() {
+            return Err(ValidationError::MissingData(
+                "The SHA1 checksum is required to skip unchanged files".into()
+            ));
+        }
+
+        let sha1_checksum = validated_sha1_checksum(
+            self.sha1_checksum.unwrap_or("do_not_verify")
+        )?;
 
         if self.file_retention_mode.is_some()
             ^ self.file_retention_time.is_some()
@@ -3599,23 +5731,27 @@ impl<'a> UploadFileBuilder<'a> {
             ));
         }
 
-        let file_info = if let Some(mut file_info) = self.file_info {
+        let file_info = {
+            let mut file_info = self.file_info
+                .unwrap_or_else(|| serde_json::json!({}));
+
             let info_map = file_info.as_object_mut()
                 .expect("file_info is not a JSON object");
 
-            add_file_info!(info_map, "X-Bz-info-src_last_modified_millis",
+            // The header names must match those sent by upload_file_impl.
+            add_file_info!(info_map, "X-Bz-Info-src_last_modified_millis",
                 self.last_modified.map(|v| v.to_string()));
-            add_file_info!(info_map, "X-Bz-info-b2-content-disposition",
+            add_file_info!(info_map, "X-Bz-Info-b2-content-disposition",
                 self.content_disposition);
-            add_file_info!(info_map, "X-Bz-info-b2-content-language",
+            add_file_info!(info_map, "X-Bz-Info-b2-content-language",
                 self.content_language);
-            add_file_info!(info_map, "X-Bz-info-b2-expires", self.expires);
-            add_file_info!(info_map, "X-Bz-info-b2-content-encoding",
+            add_file_info!(info_map, "X-Bz-Info-b2-expires", self.expires);
+            add_file_info!(info_map, "X-Bz-Info-b2-cache-control",
+                self.cache_control);
+            add_file_info!(info_map, "X-Bz-Info-b2-content-encoding",
                 self.content_encoding);
 
-            Some(file_info)
-        } else {
-            None
+            if info_map.is_empty() { None } else { Some(file_info) }
         };
 
         validate_file_metadata_size(
@@ -3635,6 +5771,8 @@ impl<'a> UploadFileBuilder<'a> {
             legal_hold: self.legal_hold,
             file_retention,
             encryption: self.encryption,
+            skip_if_unchanged: self.skip_if_unchanged,
+            expect_continue: self.expect_continue,
         })
     }
 }
@@ -3643,6 +5781,13 @@ impl<'a> UploadFileBuilder<'a> {
 ///
 /// You must first call [get_upload_authorization] to obtain an authorization to
 /// upload files to the bucket; then pass that authorization to `upload_file`.
+///
+/// If the upload fails, the `UploadAuthorization` cannot be used again. If it
+/// has expired or a previous upload with it failed, returns
+/// [Error::MissingAuthorization] without making a request.
+///
+/// To upload a file without first reading it into memory, see
+/// [upload_file_from_path].
 pub async fn upload_file<C, E>(
     auth: &mut UploadAuthorization<'_, C, E>,
     upload: UploadFile<'_>,
@@ -3651,85 +5796,682 @@ pub async fn upload_file<C, E>(
     where C: HttpClient<Error=Error<E>>,
           E: fmt::Debug + fmt::Display,
 {
-    // Unwrap safety: an `UploadAuthorization` can only be created from
-    // `get_upload_authorization`, which will always embed an `Authorization`
-    // reference before returning.
-    let inner_auth = auth.auth.as_mut().unwrap();
+    upload_file_impl(auth, upload, UploadBody::Bytes(data)).await
+}
 
-    require_capability!(inner_auth, Capability::WriteFiles);
+/// Upload a file from the local filesystem to a B2 bucket.
+///
+/// This is identical to [upload_file], except that the request body is read
+/// from the file at `path`. The surf, hyper, and isahc clients stream the file
+/// to B2 rather than reading it into memory first; see
+/// [HttpClient::read_body_from_file].
+pub async fn upload_file_from_path<C, E>(
+    auth: &mut UploadAuthorization<'_, C, E>,
+    upload: UploadFile<'_>,
+    path: impl Into<std::path::PathBuf>,
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let path = path.into();
+    let len = std::fs::metadata(&path)?.len();
 
-    if upload.file_retention.is_some() {
-        // We check this here rather than when we need it below to satisfy the
-        // borrow checker.
-        require_capability!(inner_auth, Capability::WriteFileRetentions);
-    }
+    upload_file_impl(auth, upload, UploadBody::File(path, len)).await
+}
 
-    let mut req = inner_auth.client.post(&auth.upload_url)
-        .expect("Invalid URL")
-        .with_header("Authorization", &auth.authorization_token)?
-        .with_header("X-Bz-File-Name", &upload.file_name)?
-        .with_header("Content-Type", &upload.content_type)?
-        .with_header("Content-Length", &data.len().to_string())?
-        .with_header("X-Bz-Content-Sha1", upload.sha1_checksum)?;
+/// Upload files one at a time, reusing a single upload URL.
+///
+/// An upload URL can be used for any number of sequential uploads until it
+/// expires or an upload using it fails. A `SequentialUploader` obtains a new
+/// URL before the next upload whenever the current one can no longer be used,
+/// which is more efficient than obtaining a new URL for every file.
+///
+/// Failed uploads are not retried; the error is returned and the next upload
+/// will use a new URL. To upload files concurrently, use a separate
+/// [UploadAuthorization] for each task or see [upload_many].
+pub struct SequentialUploader<'a, C, E>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    upload_auth: UploadAuthorization<'a, C, E>,
+}
 
-    if let Some(mut file_info) = upload.file_info {
-        let info_map = file_info.as_object_mut()
-            .expect("file_info is not a JSON object");
+impl<'a, C, E> SequentialUploader<'a, C, E>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    /// Create a `SequentialUploader` to upload files to the specified bucket.
+    ///
+    /// If `bucket_id` is `None`, the files are uploaded to the bucket to which
+    /// the [Authorization] is restricted.
+    ///
+    /// The [Authorization] must have [Capability::WriteFiles].
+    pub async fn new(auth: &'a mut Authorization<C>, bucket_id: Option<&str>)
+    -> Result<SequentialUploader<'a, C, E>, Error<E>> {
+        let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+
+        Ok(Self {
+            upload_auth: get_upload_authorization_by_id(auth, bucket_id)
+                .await?,
+        })
+    }
 
-        macro_rules! add_metadata_header {
-            ($header_name:literal) => {
-                if let Some(val) = info_map.remove($header_name) {
-                    req = req.with_header($header_name, val.as_str().unwrap())?
-                }
-            };
-        }
+    /// The ID of the bucket the files are uploaded to.
+    pub fn bucket_id(&self) -> &str { &self.upload_auth.bucket_id }
 
-        add_metadata_header!("X-Bz-Info-src_last_modified_millis");
-        add_metadata_header!("X-Bz-Info-b2-content-disposition");
-        add_metadata_header!("X-Bz-Info-b2-content-language");
-        add_metadata_header!("X-Bz-Info-b2-expires");
-        add_metadata_header!("X-Bz-Info-b2-cache-control");
-        add_metadata_header!("X-Bz-Info-content-encoding");
+    /// The URL that the next upload will use, if it is still valid.
+    pub fn upload_url(&self) -> &str { &self.upload_auth.upload_url }
 
-        for (key, val) in info_map.into_iter() {
-            req = req.with_header(key, &val.to_string())?;
-        }
-    }
+    /// Obtain a new upload URL.
+    ///
+    /// This is done automatically when the current URL expires or an upload
+    /// fails.
+    pub async fn refresh(&mut self) -> Result<(), Error<E>> {
+        let bucket_id = self.upload_auth.bucket_id.clone();
+
+        // Unwrap safety: an `UploadAuthorization` always holds an
+        // `Authorization` reference. We only reborrow it, so it remains even if
+        // this future is dropped before the request completes.
+        let auth = self.upload_auth.auth.as_deref_mut().unwrap();
+
+        let UploadAuthorization {
+            bucket_id, upload_url, authorization_token, obtained_at, ..
+        } = get_upload_authorization_by_id(auth, &bucket_id).await?;
+
+        self.upload_auth.bucket_id = bucket_id;
+        self.upload_auth.upload_url = upload_url;
+        self.upload_auth.authorization_token = authorization_token;
+        self.upload_auth.obtained_at = obtained_at;
+        self.upload_auth.rejected = false;
 
-    if let Some(legal_hold) = upload.legal_hold {
-        req = req.with_header("X-Bz-File-Legal-Hold", &legal_hold.to_string())?;
+        Ok(())
     }
 
-    if let Some((mode, timestamp)) = upload.file_retention {
-        req = req
-            .with_header("X-Bz-File-Retention-Mode", &mode.to_string())?
-            .with_header("X-Bz-File-Retention-Retain-Until-Timestamp",
-                &timestamp.to_string())?;
+    /// Upload a file, obtaining a new upload URL first if necessary.
+    ///
+    /// See [upload_file] for further information.
+    pub async fn upload_file(&mut self, upload: UploadFile<'_>, data: &[u8])
+    -> Result<File, Error<E>> {
+        if ! self.upload_auth.is_valid() {
+            self.refresh().await?;
+        }
+
+        upload_file(&mut self.upload_auth, upload, data).await
     }
 
-    if let Some(enc) = upload.encryption {
-        if let Some(headers) = enc.to_headers() {
-            for (header, value) in headers.into_iter() {
-                req = req.with_header(header, &value)?;
-            }
+    /// Upload a file from the local filesystem, obtaining a new upload URL
+    /// first if necessary.
+    ///
+    /// See [upload_file_from_path] for further information.
+    pub async fn upload_file_from_path(
+        &mut self,
+        upload: UploadFile<'_>,
+        path: impl Into<std::path::PathBuf>,
+    ) -> Result<File, Error<E>> {
+        if ! self.upload_auth.is_valid() {
+            self.refresh().await?;
         }
-    }
 
-    let res = req.with_body(data).send().await?;
+        upload_file_from_path(&mut self.upload_auth, upload, path).await
+    }
+}
 
-    let file: B2Result<File> = serde_json::from_slice(&res)?;
-    file.into()
+/// A cache of upload URLs for each bucket.
+///
+/// Obtaining an upload URL requires a request to B2. A bursty uploader can
+/// [take](Self::take) a URL from the pool for each upload and [put](Self::put)
+/// it back afterward so that later uploads reuse it; a new URL is only
+/// requested when the pool has none for the bucket.
+///
+/// A taken [UploadUrl] does not borrow the [Authorization], so concurrent
+/// uploads can each take their own URL and
+/// [authorize](UploadUrl::authorize) it with a clone of the `Authorization`.
+///
+/// Upload URLs do not expire on a fixed schedule, but they do go stale. A URL
+/// is discarded rather than returned to the pool if an upload using it failed
+/// (e.g., B2 responded with a 401 or 503 status), or if it was obtained more
+/// than the pool's maximum age ago.
+///
+/// The `Debug` output does not include the authorization tokens.
+#[derive(Debug)]
+pub struct UploadUrlPool {
+    max_urls_per_bucket: usize,
+    max_age: std::time::Duration,
+    urls: HashMap<String, Vec<UploadUrl>>,
 }
 
-/// A request to upload part of a large file.
-#[derive(Clone)]
-pub struct UploadFilePart<'a> {
-    part_number: u16,
-    content_sha1: &'a str,
-    encryption: Option<ServerSideEncryption>,
+/// An upload URL taken from an [UploadUrlPool].
+///
+/// Use [authorize](Self::authorize) to upload files with it, then return the
+/// [UploadAuthorization] to the pool via [UploadUrlPool::put].
+///
+/// The `Debug` output does not include the authorization token.
+pub struct UploadUrl {
+    bucket_id: String,
+    upload_url: String,
+    authorization_token: String,
+    obtained_at: chrono::DateTime<chrono::Utc>,
 }
 
-impl<'a> UploadFilePart<'a> {
+impl fmt::Debug for UploadUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadUrl")
+            .field("bucket_id", &self.bucket_id)
+            .field("upload_url", &self.upload_url)
+            .field("authorization_token", &"<redacted>")
+            .field("obtained_at", &self.obtained_at)
+            .finish()
+    }
+}
+
+impl<'a, C, E> From<UploadAuthorization<'a, C, E>> for UploadUrl
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    fn from(upload_auth: UploadAuthorization<'a, C, E>) -> Self {
+        let UploadAuthorization {
+            bucket_id, upload_url, authorization_token, obtained_at, ..
+        } = upload_auth;
+
+        Self { bucket_id, upload_url, authorization_token, obtained_at }
+    }
+}
+
+impl UploadUrl {
+    /// The ID of the bucket the URL uploads to.
+    pub fn bucket_id(&self) -> &str { &self.bucket_id }
+
+    /// Use the URL to upload files with the provided [Authorization].
+    ///
+    /// The `Authorization` need not be the one that obtained the URL, but it
+    /// must be for the same account.
+    pub fn authorize<C, E>(self, auth: &mut Authorization<C>)
+    -> UploadAuthorization<'_, C, E>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        UploadAuthorization {
+            auth: Some(auth),
+            bucket_id: self.bucket_id,
+            upload_url: self.upload_url,
+            authorization_token: self.authorization_token,
+            obtained_at: self.obtained_at,
+            rejected: false,
+        }
+    }
+
+    fn is_older_than(
+        &self,
+        age: std::time::Duration,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        (now - self.obtained_at).to_std()
+            .map(|elapsed| elapsed >= age)
+            .unwrap_or(false)
+    }
+}
+
+impl Default for UploadUrlPool {
+    /// Create a pool that keeps up to four URLs per bucket for up to an hour.
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+impl UploadUrlPool {
+    /// Create a pool that keeps up to `max_urls_per_bucket` unused URLs for
+    /// each bucket.
+    ///
+    /// URLs are kept for up to an hour; use [Self::with_max_age] to change
+    /// this.
+    pub fn new(max_urls_per_bucket: usize) -> Self {
+        Self {
+            max_urls_per_bucket,
+            max_age: std::time::Duration::from_secs(60 * 60),
+            urls: HashMap::new(),
+        }
+    }
+
+    /// Discard URLs that were obtained more than `age` ago.
+    ///
+    /// B2 does not document how long an upload URL remains usable beyond the
+    /// 24-hour lifetime of its authorization token; retiring URLs sooner
+    /// spreads uploads across B2's servers as their load changes.
+    pub fn with_max_age(mut self, age: std::time::Duration) -> Self {
+        self.max_age = age;
+        self
+    }
+
+    /// The maximum number of unused URLs kept for each bucket.
+    pub fn max_urls_per_bucket(&self) -> usize { self.max_urls_per_bucket }
+
+    /// The maximum age of a URL in the pool.
+    pub fn max_age(&self) -> std::time::Duration { self.max_age }
+
+    /// The number of unused URLs in the pool for the specified bucket.
+    ///
+    /// This may include URLs that have aged out but not yet been discarded.
+    pub fn available(&self, bucket_id: &str) -> usize {
+        self.urls.get(bucket_id).map(|urls| urls.len()).unwrap_or(0)
+    }
+
+    /// Take an upload URL for the specified bucket from the pool, obtaining a
+    /// new one from B2 if the pool has none.
+    ///
+    /// `bucket_id` may be omitted; see
+    /// [Authorization::bucket_id_or_restricted].
+    ///
+    /// The [Authorization] must have [Capability::WriteFiles].
+    pub async fn take<C, E>(
+        &mut self,
+        auth: &mut Authorization<C>,
+        bucket_id: Option<&str>,
+    ) -> Result<UploadUrl, Error<E>>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        require_capability!(auth, Capability::WriteFiles);
+
+        let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+        let max_age = self.max_age;
+        let now = auth.now();
+
+        let cached = self.urls.get_mut(&bucket_id).and_then(|urls| {
+            // The most recently returned URLs are at the end.
+            urls.retain(|url| ! url.is_older_than(max_age, now));
+            urls.pop()
+        });
+
+        match cached {
+            Some(url) => Ok(url),
+            None => get_upload_authorization_by_id(auth, bucket_id).await
+                .map(UploadUrl::from),
+        }
+    }
+
+    /// Return an upload URL to the pool for reuse.
+    ///
+    /// The URL is discarded if it is no longer
+    /// [valid](UploadAuthorization::is_valid), is older than the pool's maximum
+    /// age, or the pool already holds its maximum number of URLs for the
+    /// bucket.
+    pub fn put<C, E>(&mut self, upload_auth: UploadAuthorization<'_, C, E>)
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        if ! upload_auth.is_valid() {
+            return;
+        }
+
+        let now = upload_auth.now();
+        let url = UploadUrl::from(upload_auth);
+
+        if url.is_older_than(self.max_age, now) {
+            return;
+        }
+
+        let urls = self.urls.entry(url.bucket_id.clone()).or_default();
+
+        if urls.len() < self.max_urls_per_bucket {
+            urls.push(url);
+        }
+    }
+
+    /// Upload a file using a URL from the pool, returning the URL to the pool
+    /// afterward.
+    ///
+    /// See [Self::take] and [upload_file] for further information.
+    pub async fn upload_file<C, E>(
+        &mut self,
+        auth: &mut Authorization<C>,
+        bucket_id: Option<&str>,
+        upload: UploadFile<'_>,
+        data: &[u8],
+    ) -> Result<File, Error<E>>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let mut upload_auth = self.take(auth, bucket_id).await?
+            .authorize(auth);
+        let res = upload_file(&mut upload_auth, upload, data).await;
+
+        self.put(upload_auth);
+        res
+    }
+}
+
+/// The source of the data for [upload_file_impl].
+enum UploadBody<'a> {
+    Bytes(&'a [u8]),
+    /// The path to the file and its length in bytes.
+    File(std::path::PathBuf, u64),
+}
+
+impl<'a> UploadBody<'a> {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Bytes(data) => data.len() as u64,
+            Self::File(_, len) => *len,
+        }
+    }
+}
+
+/// If the upload requests skipping unchanged files, find the latest version of
+/// the file if its checksum matches.
+async fn unchanged_file<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: &str,
+    upload: &UploadFile<'_>,
+) -> Result<Option<File>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    if ! upload.skip_if_unchanged {
+        return Ok(None);
+    }
+
+    let file_name = percent_encoding::percent_decode_str(&upload.file_name)
+        .decode_utf8_lossy();
+
+    let latest = get_latest_file_version(auth, Some(bucket_id), &file_name)
+        .await?;
+
+    Ok(latest.filter(|file| {
+        let sha1 = file.sha1_checksum().map(|s| s.as_str())
+            .or_else(|| file.file_info["large_file_sha1"].as_str());
+
+        sha1 == Some(upload.sha1_checksum)
+    }))
+}
+
+/// True if B2 asks for a new upload URL after an upload failed with `e`.
+///
+/// Other errors, such as a `bad_request` for a checksum mismatch or an
+/// exceeded cap, are due to the upload itself; the URL may be used again.
+fn is_upload_url_failure<E>(e: &Error<E>) -> bool
+    where E: fmt::Debug + fmt::Display,
+{
+    match e {
+        Error::B2(e) => matches!(e.http_status(), 401 | 408 | 429 | 503),
+        Error::Transport(_) | Error::Timeout => true,
+        _ => false,
+    }
+}
+
+async fn upload_file_impl<C, E>(
+    auth: &mut UploadAuthorization<'_, C, E>,
+    upload: UploadFile<'_>,
+    body: UploadBody<'_>,
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    if ! auth.is_valid() {
+        return Err(Error::MissingAuthorization);
+    }
+
+    // Unwrap safety: an `UploadAuthorization` can only be created from
+    // `get_upload_authorization`, which will always embed an `Authorization`
+    // reference before returning.
+    let inner_auth = auth.auth.as_mut().unwrap();
+    let redaction = inner_auth.log_redaction();
+
+    require_capability!(inner_auth, Capability::WriteFiles);
+
+    if upload.file_retention.is_some() {
+        // We check this here rather than when we need it below to satisfy the
+        // borrow checker.
+        require_capability!(inner_auth, Capability::WriteFileRetentions);
+    }
+
+    if let Some(file) = unchanged_file(inner_auth, &auth.bucket_id, &upload)
+        .await?
+    {
+        return Ok(file);
+    }
+
+    let mut req = inner_auth.client.post(&auth.upload_url)
+        .expect("Invalid URL")
+        .with_header("Authorization", &auth.authorization_token)?
+        .with_request_kind(RequestKind::Transfer)
+        .with_header("X-Bz-File-Name", &upload.file_name)?
+        .with_header("Content-Type", &upload.content_type)?
+        .with_header("Content-Length", &body.len().to_string())?
+        .with_header("X-Bz-Content-Sha1", upload.sha1_checksum)?;
+
+    if upload.expect_continue {
+        req = req.with_expect_continue();
+    }
+
+    if let Some(mut file_info) = upload.file_info {
+        let info_map = file_info.as_object_mut()
+            .expect("file_info is not a JSON object");
+
+        macro_rules! add_metadata_header {
+            ($header_name:literal) => {
+                if let Some(val) = info_map.remove($header_name) {
+                    req = req.with_header($header_name, val.as_str().unwrap())?
+                }
+            };
+        }
+
+        add_metadata_header!("X-Bz-Info-src_last_modified_millis");
+        add_metadata_header!("X-Bz-Info-b2-content-disposition");
+        add_metadata_header!("X-Bz-Info-b2-content-language");
+        add_metadata_header!("X-Bz-Info-b2-expires");
+        add_metadata_header!("X-Bz-Info-b2-cache-control");
+        add_metadata_header!("X-Bz-Info-b2-content-encoding");
+
+        for (key, val) in info_map.into_iter() {
+            let val = val.as_str().map(String::from)
+                .unwrap_or_else(|| val.to_string());
+
+            req = req.with_header(key, &val)?;
+        }
+    }
+
+    if let Some(legal_hold) = upload.legal_hold {
+        req = req.with_header("X-Bz-File-Legal-Hold", &legal_hold.to_string())?;
+    }
+
+    if let Some((mode, timestamp)) = upload.file_retention {
+        req = req
+            .with_header("X-Bz-File-Retention-Mode", &mode.to_string())?
+            .with_header("X-Bz-File-Retention-Retain-Until-Timestamp",
+                &timestamp.to_string())?;
+    }
+
+    if let Some(enc) = upload.encryption {
+        if let Some(headers) = enc.to_headers() {
+            for (header, value) in headers.into_iter() {
+                req = req.with_header(header, &value)?;
+            }
+        }
+    }
+
+    let req = match body {
+        UploadBody::Bytes(data) => req.with_body(data),
+        UploadBody::File(path, _) => req.read_body_from_file(path),
+    };
+
+    let res = match req.send_keep_headers().await {
+        Ok(res) => res,
+        Err(e) => {
+            auth.rejected = is_upload_url_failure(&e);
+            return Err(e);
+        },
+    };
+
+    let file: B2Result<File> = B2Result::from_response(&res)?;
+    let file = Result::from(file);
+
+    if let Err(e) = &file {
+        auth.rejected = is_upload_url_failure(e);
+    }
+
+    let file_name = percent_encoding::percent_decode_str(&upload.file_name)
+        .decode_utf8_lossy();
+
+    file.map_err(|e| match e {
+        Error::B2(e) if e.is_checksum_mismatch() => Error::ChecksumMismatch {
+            expected: upload.sha1_checksum.to_owned(),
+            actual: String::new(),
+        },
+        e => redaction.error(e, &file_name),
+    })
+}
+
+/// Upload multiple files from the local filesystem to a B2 bucket.
+///
+/// Up to `concurrency` files are uploaded at once. Each concurrent upload
+/// obtains its own [UploadAuthorization] from a copy of `auth`, and obtains a
+/// new one after a failed upload.
+///
+/// `files` is a list of local paths and the file names to give them in B2.
+/// Each file is read fully into memory before it is uploaded and is not
+/// checksummed before sending.
+///
+/// The upload results are returned in the same order as `files`; a failure to
+/// read or upload one file does not abort the remaining uploads.
+///
+/// # Notes
+///
+/// The files are read with the blocking [std::fs::read], on whichever thread
+/// polls the returned future; b2-client does not depend on an async runtime
+/// that could read them asynchronously. Each read stalls that thread and
+/// every other task scheduled on it. If that matters, run `upload_many` where
+/// blocking is allowed (e.g., via your runtime's `spawn_blocking`), or read
+/// the files yourself and upload them with [upload_file].
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::WriteFiles].
+///
+/// This is only available with the `futures` feature.
+#[cfg(feature = "futures")]
+pub async fn upload_many<C, E>(
+    auth: &Authorization<C>,
+    bucket_id: Option<&str>,
+    files: Vec<(std::path::PathBuf, String)>,
+    concurrency: usize,
+) -> Vec<Result<File, Error<E>>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    use std::{path::PathBuf, sync::Mutex};
+
+    type Queue = Mutex<std::iter::Enumerate<std::vec::IntoIter<
+        (PathBuf, String)
+    >>>;
+
+    type Results<E> = Vec<(usize, Result<File, Error<E>>)>;
+
+    // Get the next file we're able to read, recording the errors of any we
+    // can't.
+    fn next_job<E>(queue: &Queue, results: &mut Results<E>)
+    -> Option<(usize, String, Vec<u8>)>
+        where E: fmt::Debug + fmt::Display,
+    {
+        loop {
+            let (i, (path, name)) = queue.lock().unwrap().next()?;
+
+            match std::fs::read(path) {
+                Ok(data) => return Some((i, name, data)),
+                Err(e) => results.push((i, Err(e.into()))),
+            }
+        }
+    }
+
+    async fn upload_worker<C, E>(
+        mut auth: Authorization<C>,
+        bucket_id: &str,
+        queue: &Queue,
+    ) -> Results<E>
+        where C: HttpClient<Error=Error<E>>,
+              E: fmt::Debug + fmt::Display,
+    {
+        let mut results = vec![];
+        let mut job = next_job(queue, &mut results);
+
+        while job.is_some() {
+            let upload_auth = get_upload_authorization_by_id(
+                &mut auth,
+                bucket_id
+            ).await;
+
+            let mut upload_auth = match upload_auth {
+                Ok(upload_auth) => upload_auth,
+                Err(e) => {
+                    if let Some((i, _, _)) = job.take() {
+                        results.push((i, Err(e)));
+                    }
+
+                    job = next_job(queue, &mut results);
+                    continue;
+                },
+            };
+
+            while let Some((i, name, data)) = job.take() {
+                let res = match UploadFile::builder().file_name(&name) {
+                    Ok(upload) => match upload.build() {
+                        Ok(upload) =>
+                            upload_file(&mut upload_auth, upload, &data).await,
+                        Err(e) => Err(e.into()),
+                    },
+                    Err(e) => Err(ValidationError::BadFormat(e.to_string())
+                        .into()),
+                };
+
+                let failed = res.is_err();
+                results.push((i, res));
+                job = next_job(queue, &mut results);
+
+                // Most upload failures require a new upload URL.
+                if failed {
+                    break;
+                }
+            }
+        }
+
+        results
+    }
+
+    let bucket_id = match auth.bucket_id_or_restricted(bucket_id) {
+        Ok(bucket_id) => bucket_id,
+        Err(e) => {
+            let msg = e.to_string();
+
+            return files.iter()
+                .map(|_| Err(ValidationError::MissingData(msg.clone()).into()))
+                .collect();
+        },
+    };
+
+    let file_count = files.len();
+    let queue: Queue = Mutex::new(files.into_iter().enumerate());
+
+    let workers = (0..concurrency.clamp(1, file_count.max(1)))
+        .map(|_| upload_worker(auth.clone(), &bucket_id, &queue));
+
+    let mut results: Vec<_> = futures_util::future::join_all(workers).await
+        .into_iter()
+        .flatten()
+        .collect();
+
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, res)| res).collect()
+}
+
+/// A request to upload part of a large file.
+#[derive(Clone)]
+pub struct UploadFilePart<'a> {
+    part_number: u16,
+    content_sha1: &'a str,
+    encryption: Option<ServerSideEncryption>,
+    expect_continue: bool,
+}
+
+impl<'a> UploadFilePart<'a> {
     pub fn builder() -> UploadFilePartBuilder<'a> {
         UploadFilePartBuilder::default()
     }
@@ -3755,6 +6497,7 @@ pub struct UploadFilePartBuilder<'a> {
     part_number: u16,
     content_sha1: &'a str,
     encryption: Option<ServerSideEncryption>,
+    expect_continue: bool,
 }
 
 impl<'a> Default for UploadFilePartBuilder<'a> {
@@ -3763,6 +6506,7 @@ impl<'a> Default for UploadFilePartBuilder<'a> {
             part_number: 1,
             content_sha1: "do_not_verify",
             encryption: None,
+            expect_continue: false,
         }
     }
 }
@@ -3799,12 +6543,21 @@ impl<'a> UploadFilePartBuilder<'a> {
         self
     }
 
+    /// Ask B2 to accept the request before the part's data is sent.
+    ///
+    /// See [UploadFileBuilder::expect_continue].
+    pub fn expect_continue(mut self) -> Self {
+        self.expect_continue = true;
+        self
+    }
+
     /// Create an [UploadFilePart] request to pass to [upload_file_part].
     pub fn build(self) -> UploadFilePart<'a> {
         UploadFilePart {
             part_number: self.part_number,
             content_sha1: self.content_sha1,
             encryption: self.encryption,
+            expect_continue: self.expect_continue,
         }
     }
 }
@@ -3821,7 +6574,10 @@ impl<'a> UploadFilePartBuilder<'a> {
 /// have [Capability::WriteFiles].
 ///
 /// A large file must have at least two parts, and all parts except the last
-/// must be at least 5 MB in size. See
+/// must be at least the [minimum part size](Authorization::part_size_bounds).
+/// If a part uploaded with the same `UploadPartAuthorization` would leave a
+/// part other than the last below the minimum, returns
+/// [ValidationError::PartTooSmall] without making a request. See
 /// <https://www.backblaze.com/b2/docs/uploading.html> for further information
 /// on uploading files.
 ///
@@ -3860,13 +6616,20 @@ pub async fn upload_file_part<C, E>(
 
     require_capability!(inner_auth, Capability::WriteFiles);
 
+    auth.part_sizes.check(upload.part_number, data.len() as u64)?;
+
     let mut req = inner_auth.client.post(&auth.upload_url)
         .expect("Invalid URL")
         .with_header("Authorization", &auth.authorization_token).unwrap()
+        .with_request_kind(RequestKind::Transfer)
         .with_header("X-Bz-Part-Number", &upload.part_number.to_string())?
         .with_header("Content-Length", &data.len().to_string())?
         .with_header("X-Bz-Content-Sha1", upload.content_sha1)?;
 
+    if upload.expect_continue {
+        req = req.with_expect_continue();
+    }
+
     if let Some(enc) = &upload.encryption {
         if let Some(headers) = enc.to_headers() {
             for (header, value) in headers.into_iter() {
@@ -3875,28 +6638,523 @@ pub async fn upload_file_part<C, E>(
         }
     }
 
-    let res = req.with_body(data).send().await?;
+    let res = req.with_body(data).send_keep_headers().await?;
 
-    let part: B2Result<FilePart> = serde_json::from_slice(&res)?;
-    part.into()
-}
+    let part: B2Result<FilePart> = B2Result::from_response(&res)?;
 
-#[cfg(all(test, feature = "with_surf"))]
-mod tests_mocked {
-    use super::*;
-    use crate::{
-        account::Capability,
-        error::ErrorCode,
-        test_utils::{create_test_auth, create_test_client},
-    };
-    use surf_vcr::VcrMode;
+    if let B2Result::Ok(_) = part {
+        auth.part_sizes.record(upload.part_number, data.len() as u64);
+    }
 
+    part.into()
+}
 
-    #[async_std::test]
-    async fn start_large_file_upload_success() -> anyhow::Result<()> {
-        let client = create_test_client(
-            VcrMode::Replay,
-            "test_sessions/large_file.yaml",
+/// Choose the size of each part of a large file upload.
+///
+/// If `part_size` is `None`, the account's [recommended part
+/// size](Authorization::recommended_part_size) is used, increased if necessary
+/// so that the file fits within 10,000 parts.
+///
+/// If `part_size` is provided, it is used as-is after validation.
+///
+/// # Errors
+///
+/// Returns [ValidationError::OutOfBounds] if:
+///
+/// * `part_size` is smaller than the [minimum part
+///   size](Authorization::minimum_part_size) or larger than 5 GB.
+/// * `file_size` would require more than 10,000 parts of `part_size` bytes.
+pub fn large_file_part_size<C>(
+    auth: &Authorization<C>,
+    file_size: u64,
+    part_size: Option<u64>,
+) -> Result<u64, ValidationError>
+    where C: HttpClient,
+{
+    let (minimum, recommended) = auth.part_size_bounds();
+
+    choose_part_size(file_size, part_size, recommended, minimum)
+}
+
+fn choose_part_size(
+    file_size: u64,
+    part_size: Option<u64>,
+    recommended: u64,
+    minimum: u64,
+) -> Result<u64, ValidationError> {
+    const MAX_PARTS: u64 = 10_000;
+    const MAX_PART_SIZE: u64 = 5_000_000_000;
+
+    let min_for_file = file_size.div_ceil(MAX_PARTS);
+
+    let size = match part_size {
+        Some(size) => {
+            if size < minimum || size > MAX_PART_SIZE {
+                return Err(ValidationError::OutOfBounds(format!(
+                    "Part size must be between {} and {} bytes. Was {}",
+                    minimum, MAX_PART_SIZE, size
+                )));
+            } else if size < min_for_file {
+                return Err(ValidationError::OutOfBounds(format!(
+                    concat!(
+                        "A part size of {} bytes requires more than 10,000 ",
+                        "parts; the minimum for this file is {} bytes"
+                    ),
+                    size, min_for_file
+                )));
+            }
+
+            size
+        },
+        None => recommended.max(minimum).max(min_for_file),
+    };
+
+    if size > MAX_PART_SIZE {
+        Err(ValidationError::OutOfBounds(format!(
+            "The file is too large to upload in 10,000 parts: {} bytes",
+            file_size
+        )))
+    } else {
+        Ok(size)
+    }
+}
+
+/// A handle to cancel a transfer in progress, such as an [upload_path] call
+/// given the token via [UploadOptions::cancellation_token].
+///
+/// Clones of a `CancellationToken` share their state, so one clone can be
+/// passed to the transfer while another is kept to cancel it; e.g., from a
+/// Ctrl-C handler.
+///
+/// Cancellation is checked between requests; a request in progress is not
+/// interrupted.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token that has not been cancelled.
+    pub fn new() -> Self { Self::default() }
+
+    /// Cancel the transfers using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// True if [cancel](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Returns [Error::Cancelled] if the token has been cancelled.
+    fn check<E>(&self) -> Result<(), Error<E>>
+        where E: fmt::Debug + fmt::Display,
+    {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Options for [upload_path].
+#[derive(Default)]
+pub struct UploadOptions {
+    large_file_threshold: Option<u64>,
+    part_size: Option<u64>,
+    cancel: Option<CancellationToken>,
+    progress: Option<Box<dyn FnMut(u64, u64) + Send>>,
+}
+
+impl fmt::Debug for UploadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadOptions")
+            .field("large_file_threshold", &self.large_file_threshold)
+            .field("part_size", &self.part_size)
+            .field("cancel", &self.cancel)
+            .field("progress", &self.progress.as_ref().map(|_| "<callback>"))
+            .finish()
+    }
+}
+
+impl UploadOptions {
+    /// Upload files of at least `bytes` bytes as large files.
+    ///
+    /// The default is twice the account's [recommended part
+    /// size](Authorization::recommended_part_size), which is 200 MB for most
+    /// accounts. Files larger than 5 GB are always uploaded as large files,
+    /// since B2 does not accept larger files in a single request, and files
+    /// too small to split into two parts are never uploaded as large files.
+    pub fn large_file_threshold(mut self, bytes: u64) -> Self {
+        self.large_file_threshold = Some(bytes);
+        self
+    }
+
+    /// The size of each part of a large file.
+    ///
+    /// If not provided, the part size is chosen by [large_file_part_size],
+    /// reduced if necessary so that the file has at least two parts.
+    pub fn part_size(mut self, bytes: u64) -> Self {
+        self.part_size = Some(bytes);
+        self
+    }
+
+    /// Stop the upload if `token` is cancelled.
+    ///
+    /// Cancellation is checked before the upload begins and before each part
+    /// of a large file is uploaded. If a large file was started, it is
+    /// cancelled via [cancel_large_file_by_id]. The upload returns
+    /// [Error::Cancelled].
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Report the upload's progress.
+    ///
+    /// `progress` is called with the number of bytes sent so far and the size
+    /// of the file: with zero before the upload begins, after each part of a
+    /// large file is uploaded, and with the file's size once the upload
+    /// succeeds. The same count is not reported twice. A file that is not
+    /// uploaded as a large file is sent in a single request, so only the first
+    /// and last calls are made.
+    pub fn progress(mut self, progress: impl FnMut(u64, u64) + Send + 'static)
+    -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Choose the part size to upload a file of `file_size` bytes, or `None`
+    /// to upload it in a single request.
+    fn part_size_for<C>(&self, auth: &Authorization<C>, file_size: u64)
+    -> Result<Option<u64>, ValidationError>
+        where C: HttpClient,
+    {
+        const MAX_SINGLE_UPLOAD: u64 = 5_000_000_000;
+
+        let (minimum, recommended) = auth.part_size_bounds();
+        let threshold = self.large_file_threshold
+            .unwrap_or_else(|| recommended.saturating_mul(2));
+
+        if file_size < threshold && file_size <= MAX_SINGLE_UPLOAD {
+            return Ok(None);
+        }
+
+        let size = match self.part_size {
+            Some(size) => large_file_part_size(auth, file_size, Some(size))?,
+            None => large_file_part_size(auth, file_size, None)?
+                .min(file_size.div_ceil(2).max(minimum)),
+        };
+
+        if size >= file_size && file_size <= MAX_SINGLE_UPLOAD {
+            Ok(None)
+        } else {
+            Ok(Some(size))
+        }
+    }
+}
+
+/// Upload a file from the local filesystem to a B2 bucket, as a large file if
+/// it is at least the [large-file
+/// threshold](UploadOptions::large_file_threshold).
+///
+/// Smaller files are uploaded via [upload_file_from_path]. Larger files are
+/// uploaded via [start_large_file], [upload_file_part], and
+/// [finish_large_file_upload]. The parts are uploaded one at a time, and each
+/// part is read into memory before it is uploaded. If a part fails to upload,
+/// the large file is cancelled and the error is returned. If finishing the
+/// file fails but B2 reports it as finished via [get_file_info] (which
+/// requires [Capability::ReadFiles]), the finished file is returned.
+/// The [UploadOptions] can also stop the upload or report its progress.
+///
+/// For a large file, the [UploadFile]'s SHA1 checksum is stored as the
+/// `large_file_sha1` file information, and its retention settings are applied
+/// via [update_file_retention] after the file is finished; the returned [File]
+/// does not include them.
+///
+/// `bucket_id` may be omitted; see [Authorization::bucket_id_or_restricted].
+///
+/// The [Authorization] must have [Capability::WriteFiles].
+///
+/// # Notes
+///
+/// This function blocks the thread polling it while it reads the file's size
+/// via [std::fs::metadata] and, for a large file, while it reads each part
+/// via [std::fs::File] before uploading it; b2-client does not depend on an
+/// async runtime that could perform the reads asynchronously. A slow disk
+/// stalls every task scheduled on that thread. If that matters, call this
+/// where blocking is allowed (e.g., via your runtime's `spawn_blocking`), or
+/// read the parts yourself and upload them via [upload_file_part].
+pub async fn upload_path<C, E>(
+    auth: &mut Authorization<C>,
+    bucket_id: Option<&str>,
+    upload: UploadFile<'_>,
+    path: impl Into<std::path::PathBuf>,
+    mut options: UploadOptions,
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let cancel = options.cancel.take().unwrap_or_default();
+    let mut progress = options.progress.take();
+
+    cancel.check()?;
+
+    let bucket_id = auth.bucket_id_or_restricted(bucket_id)?;
+    let bucket_id = bucket_id.as_str();
+
+    let path = path.into();
+    let len = std::fs::metadata(&path)?.len();
+
+    let mut reported = None;
+    let mut report = |sent| {
+        if reported != Some(sent) {
+            reported = Some(sent);
+
+            if let Some(progress) = progress.as_mut() {
+                progress(sent, len);
+            }
+        }
+    };
+
+    report(0);
+
+    let file = match options.part_size_for(auth, len)? {
+        None => {
+            let mut upload_auth = get_upload_authorization_by_id(
+                auth,
+                bucket_id
+            ).await?;
+
+            cancel.check()?;
+
+            let body = UploadBody::File(path, len);
+            upload_file_impl(&mut upload_auth, upload, body).await?
+        },
+        Some(part_size) => upload_large_file_from_path(
+            auth,
+            bucket_id,
+            upload,
+            &path,
+            part_size,
+            &cancel,
+            &mut report
+        ).await?,
+    };
+
+    report(len);
+    Ok(file)
+}
+
+/// Finish a large file, treating a file that B2 has already finished as
+/// success.
+///
+/// B2 may finish the file but fail to report it, e.g. with a transient error
+/// or a timeout. A transient error is retried once; if B2 had finished the
+/// file, the retry fails because no upload of it is in progress. If finishing
+/// fails, the file is looked up via [get_file_info], which only succeeds for
+/// finished files. The lookup requires [Capability::ReadFiles]; without it,
+/// the first error is returned.
+async fn finish_large_file_once<C, E>(
+    auth: &mut Authorization<C>,
+    file: &File,
+    sha1_checksums: &[String],
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+{
+    let err = match finish_large_file_upload(auth, file, sha1_checksums).await
+    {
+        Ok(file) => return Ok(file),
+        Err(e) => e,
+    };
+
+    if matches!(&err, Error::B2(e) if e.is_retriable()) {
+        if let Ok(file) =
+            finish_large_file_upload(auth, file, sha1_checksums).await
+        {
+            return Ok(file);
+        }
+    }
+
+    match get_file_info(auth, file.file_id.clone()).await {
+        Ok(info) if info.action == FileAction::Upload => Ok(info),
+        _ => Err(err),
+    }
+}
+
+async fn upload_large_file_from_path<C, E, P>(
+    auth: &mut Authorization<C>,
+    bucket_id: &str,
+    upload: UploadFile<'_>,
+    path: &std::path::Path,
+    part_size: u64,
+    cancel: &CancellationToken,
+    progress: P,
+) -> Result<File, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          P: FnMut(u64),
+{
+    require_capability!(auth, Capability::WriteFiles);
+
+    if upload.file_retention.is_some() {
+        require_capability!(auth, Capability::WriteFileRetentions);
+    }
+
+    if let Some(file) = unchanged_file(auth, bucket_id, &upload).await? {
+        return Ok(file);
+    }
+
+    // UploadFile stores the file information under its header names.
+    let mut file_info = serde_json::Map::new();
+
+    if let Some(serde_json::Value::Object(info)) = upload.file_info {
+        for (key, val) in info {
+            let key = key.strip_prefix("X-Bz-Info-").map(String::from)
+                .unwrap_or(key);
+
+            file_info.insert(key, val);
+        }
+    }
+
+    if upload.sha1_checksum != "do_not_verify" {
+        file_info.insert("large_file_sha1".into(), upload.sha1_checksum.into());
+    }
+
+    let encryption = upload.encryption.clone();
+
+    let start = StartLargeFile {
+        bucket_id,
+        file_name: upload.file_name,
+        content_type: upload.content_type,
+        file_info: (! file_info.is_empty()).then(|| file_info.into()),
+        file_retention: None,
+        legal_hold: upload.legal_hold,
+        server_side_encryption: upload.encryption,
+    };
+
+    let file = start_large_file(auth, start).await?;
+
+    let res = upload_parts_from_path(
+        auth,
+        &file,
+        path,
+        part_size,
+        encryption,
+        cancel,
+        progress
+    ).await;
+
+    let checksums = match res {
+        Ok(checksums) => checksums,
+        Err(e) => {
+            // The upload error is more useful than a failure to cancel.
+            let _ = cancel_large_file_by_id(auth, &file.file_id).await;
+            return Err(e);
+        },
+    };
+
+    let file = finish_large_file_once(auth, &file, &checksums).await?;
+
+    if let Some((mode, retain_until)) = upload.file_retention {
+        let update = UpdateFileRetention {
+            file_name: &file.file_name,
+            file_id: &file.file_id,
+            file_retention: FileRetentionSetting {
+                mode: Some(mode),
+                retain_until: Some(retain_until),
+            },
+            bypass_governance: None,
+        };
+
+        update_file_retention(auth, update).await?;
+    }
+
+    Ok(file)
+}
+
+/// Upload the file at `path` in parts of `part_size` bytes, returning the
+/// checksum of each part.
+///
+/// `progress` is called with the number of bytes sent after each part. The
+/// parts are read with blocking I/O; see the notes on [upload_path].
+async fn upload_parts_from_path<C, E, P>(
+    auth: &mut Authorization<C>,
+    file: &File,
+    path: &std::path::Path,
+    part_size: u64,
+    encryption: Option<ServerSideEncryption>,
+    cancel: &CancellationToken,
+    mut progress: P,
+) -> Result<Vec<String>, Error<E>>
+    where C: HttpClient<Error=Error<E>>,
+          E: fmt::Debug + fmt::Display,
+          P: FnMut(u64),
+{
+    use std::io::Read as _;
+
+    cancel.check()?;
+
+    let mut source = std::fs::File::open(path)?;
+    let mut upload_auth = get_upload_part_authorization(auth, file).await?;
+    let mut checksums: Vec<String> = vec![];
+    let mut sent = 0;
+
+    loop {
+        cancel.check()?;
+
+        let mut data = vec![];
+        let mut reader = Sha1Reader::new((&mut source).take(part_size));
+        reader.read_to_end(&mut data)?;
+
+        if data.is_empty() {
+            break;
+        }
+
+        checksums.push(reader.finalize());
+
+        // The part size was chosen so that there are no more than 10,000
+        // parts.
+        let mut part = UploadFilePart::builder()
+            .part_number(checksums.len() as u16)
+            .part_sha1_checksum(&checksums[checksums.len() - 1]);
+
+        if let Some(enc) = &encryption {
+            part = part.server_side_encryption(enc.clone());
+        }
+
+        upload_file_part(&mut upload_auth, &part.build(), &data).await?;
+
+        sent += data.len() as u64;
+        progress(sent);
+    }
+
+    Ok(checksums)
+}
+
+#[cfg(all(test, feature = "with_surf"))]
+mod tests_mocked {
+    use super::*;
+    use crate::{
+        account::Capability,
+        error::ErrorCode,
+        test_utils::{
+            create_auth_with,
+            create_test_auth,
+            create_test_client,
+            temp_path,
+        },
+    };
+    use super::tests::signed_url_test_auth;
+    use surf_vcr::VcrMode;
+
+
+    #[async_std::test]
+    async fn start_large_file_upload_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
             None, None
         ).await?;
 
@@ -4046,6 +7304,84 @@ mod tests_mocked {
         Ok(())
     }
 
+    #[async_std::test]
+    async fn downloads_are_limited_to_key_name_prefix() -> anyhow::Result<()> {
+        use crate::account::Capabilities;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_auth_with(
+            client,
+            Capabilities::new(
+                vec![Capability::ReadFiles, Capability::ShareFiles],
+                None, None, Some("files/".into())
+            )
+        );
+
+        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+
+        match download_file(&mut auth, req).await.unwrap_err() {
+            Error::NamePrefixRestricted(prefix) => assert_eq!(prefix, "files/"),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        let req = DownloadAuthorizationRequest::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name_prefix("other/")?
+            .duration(chrono::Duration::seconds(30))?
+            .build()?;
+
+        match get_download_authorization(&mut auth, req).await.unwrap_err() {
+            Error::NamePrefixRestricted(prefix) => assert_eq!(prefix, "files/"),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        let req = DownloadAuthorizationRequest::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name_prefix("files/")?
+            .duration(chrono::Duration::seconds(30))?
+            .build()?;
+
+        get_download_authorization(&mut auth, req).await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn redacted_downloads_hide_name_prefix() -> anyhow::Result<()> {
+        use crate::account::Capabilities;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_auth_with(
+            client,
+            Capabilities::new(
+                vec![Capability::ReadFiles],
+                None, None, Some("files/".into())
+            )
+        ).with_log_redaction(LogRedaction { file_names: true });
+
+        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+
+        match download_file(&mut auth, req).await.unwrap_err() {
+            Error::NamePrefixRestricted(prefix) => assert_eq!(
+                prefix,
+                auth.log_redaction().file_name("files/")
+            ),
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        Ok(())
+    }
+
     #[async_std::test]
     async fn obtain_part_upload_authorization() -> anyhow::Result<()> {
         let client = create_test_client(
@@ -4122,425 +7458,2056 @@ mod tests_mocked {
     }
 
     #[async_std::test]
-    async fn copy_file_success() -> anyhow::Result<()> {
+    async fn upload_file_sends_provided_sha1() -> anyhow::Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        let sent = Arc::new(Mutex::new(vec![]));
+        let sent_headers = sent.clone();
+
         let client = create_test_client(
             VcrMode::Replay,
-            "test_sessions/large_file.yaml",
+            "test_sessions/file.yaml",
+            Some(Box::new(move |req| {
+                if let Some(sha1) = req.headers.get("x-bz-content-sha1") {
+                    sent_headers.lock().unwrap().extend(sha1.clone());
+                }
+            })),
+            None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        upload_file(&mut upload_auth, file, b"abcd").await?;
+
+        assert_eq!(*sent.lock().unwrap(),
+            ["81fe8bfe87576c3ecb22426f8e57847382917acf"]);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_reports_checksum_mismatch() -> anyhow::Result<()> {
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new(|path, res| {
+                // Replace B2's response to an upload with a checksum error.
+                if path.contains("b2_upload_file") {
+                    res.error(400, "bad_request",
+                        "Sha1 did not match data received");
+                }
+            }))
+            .with_middleware(vcr);
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        match upload_file(&mut upload_auth, file, b"abcd").await.unwrap_err() {
+            Error::ChecksumMismatch { expected, actual } => {
+                assert_eq!(expected,
+                    "81fe8bfe87576c3ecb22426f8e57847382917acf");
+                assert!(actual.is_empty());
+            },
+            e => panic!("Unexpected error: {:?}", e),
+        }
+
+        // The upload URL is not at fault.
+        assert!(upload_auth.is_valid());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_rejects_busy_upload_url() -> anyhow::Result<()> {
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new(|path, res| {
+                if path.contains("b2_upload_file") {
+                    match res.call {
+                        0 => res.error(400, "bad_request", "Invalid file name"),
+                        _ => res.error(503, "service_unavailable",
+                            "c001_v0001000_t0000 is too busy"),
+                    }
+                }
+            }))
+            .with_middleware(vcr);
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = || UploadFile::builder()
+            .file_name("test-file-upload.txt").unwrap()
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build().unwrap();
+
+        match upload_file(&mut upload_auth, file(), b"abcd").await {
+            Err(Error::B2(e)) => assert_eq!(e.http_status(), 400),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert!(upload_auth.is_valid());
+
+        match upload_file(&mut upload_auth, file(), b"abcd").await {
+            Err(Error::B2(e)) => assert_eq!(e.http_status(), 503),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert!(! upload_auth.is_valid());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_resends_when_expectation_fails() -> anyhow::Result<()>
+    {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        // The recorded session rejects the expectation with a 417 response,
+        // then accepts the upload without the Expect header.
+        let file = UploadFile::builder()
+            .file_name("test-file-expect.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .expect_continue()
+            .build()?;
+
+        let file = upload_file(&mut upload_auth, file, b"abcd").await?;
+
+        assert_eq!(file.action, FileAction::Upload);
+        assert_eq!(file.file_name(), "test-file-expect.txt");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_round_trips_last_modified() -> anyhow::Result<()> {
+        use chrono::{TimeZone as _, Utc};
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![
+            Capability::WriteFiles, Capability::ReadFiles
+        ]).await;
+
+        let mtime = Utc.timestamp_millis_opt(1640884500123).unwrap();
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-mtime.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .last_modified(mtime)
+            .build()?;
+
+        let file = upload_file(&mut upload_auth, file, b"abcd").await?;
+        assert_eq!(file.last_modified(), Some(mtime));
+        drop(upload_auth);
+
+        let file = get_file_info(&mut auth, file.file_id()).await?;
+
+        assert_eq!(file.last_modified(), Some(mtime));
+        assert_eq!(
+            file.upload_time(),
+            Some(Utc.timestamp_millis_opt(1640884530000).unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sequential_uploader_refreshes_rejected_url() -> anyhow::Result<()>
+    {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut uploader = SequentialUploader::new(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a")
+        ).await?;
+
+        let upload = || UploadFile::builder()
+            .file_name("test-file-upload.txt").unwrap()
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build().unwrap();
+
+        let file = uploader.upload_file(upload(), b"abcd").await?;
+        assert_eq!(file.action, FileAction::Upload);
+
+        uploader.upload_auth.rejected = true;
+
+        let file = uploader.upload_file(upload(), b"abcd").await?;
+        assert_eq!(file.action, FileAction::Upload);
+        assert!(uploader.upload_auth.is_valid());
+        assert!(! uploader.upload_url().is_empty());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn sequential_uploader_survives_cancelled_refresh()
+    -> anyhow::Result<()> {
+        use std::{future::Future as _, task::Poll, time::Duration};
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, Delay},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(Delay::all(Duration::from_millis(1)))
+            .with_middleware(vcr);
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut uploader = SequentialUploader::new(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a")
+        ).await?;
+
+        // Abandon a refresh before B2 responds.
+        let mut refresh = Box::pin(uploader.refresh());
+        let pending = std::future::poll_fn(|cx|
+            Poll::Ready(refresh.as_mut().poll(cx).is_pending())
+        ).await;
+        drop(refresh);
+        assert!(pending);
+
+        let file = uploader.upload_file(
+            UploadFile::builder()
+                .file_name("test-file-upload.txt")?
+                .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+                .build()?,
+            b"abcd"
+        ).await?;
+        assert_eq!(file.action, FileAction::Upload);
+
+        uploader.refresh().await?;
+        assert!(uploader.upload_auth.is_valid());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_url_pool_reuses_valid_urls() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let bucket_id = "8d625eb63be2775577c70e1a";
+        let mut pool = UploadUrlPool::new(1);
+
+        let upload = UploadFile::builder()
+            .file_name("test-file-upload.txt").unwrap()
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build().unwrap();
+
+        let file = pool.upload_file(&mut auth, Some(bucket_id), upload, b"abcd")
+            .await?;
+        assert_eq!(file.action, FileAction::Upload);
+        assert_eq!(pool.available(bucket_id), 1);
+
+        let first = pool.take(&mut auth, Some(bucket_id)).await?;
+        assert_eq!(pool.available(bucket_id), 0);
+        let url = first.upload_url.clone();
+        pool.put(first.authorize(&mut auth));
+
+        // The pool is full, so a second URL is discarded.
+        let second = get_upload_authorization_by_id(&mut auth, bucket_id)
+            .await?;
+        pool.put(second);
+        assert_eq!(pool.available(bucket_id), 1);
+
+        let mut rejected = pool.take(&mut auth, Some(bucket_id)).await?
+            .authorize(&mut auth);
+        assert_eq!(rejected.upload_url, url);
+        rejected.rejected = true;
+        pool.put(rejected);
+        assert_eq!(pool.available(bucket_id), 0);
+
+        let mut pool = UploadUrlPool::default()
+            .with_max_age(std::time::Duration::ZERO);
+
+        let stale = get_upload_authorization_by_id(&mut auth, bucket_id)
+            .await?;
+        pool.put(stale);
+        assert_eq!(pool.available(bucket_id), 0);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_url_pool_leases_urls_concurrently() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let bucket_id = "8d625eb63be2775577c70e1a";
+        let mut pool = UploadUrlPool::new(2);
+
+        let first = pool.take(&mut auth, Some(bucket_id)).await?;
+        let second = pool.take(&mut auth, Some(bucket_id)).await?;
+
+        let upload = || UploadFile::builder()
+            .file_name("test-file-upload.txt").unwrap()
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build().unwrap();
+
+        let mut first_auth = auth.clone();
+        let mut second_auth = auth.clone();
+        let mut first = first.authorize(&mut first_auth);
+        let mut second = second.authorize(&mut second_auth);
+
+        // Both leases are usable while the other is outstanding.
+        let file = upload_file(&mut second, upload(), b"abcd").await?;
+        assert_eq!(file.action, FileAction::Upload);
+        let file = upload_file(&mut first, upload(), b"abcd").await?;
+        assert_eq!(file.action, FileAction::Upload);
+
+        pool.put(first);
+        pool.put(second);
+        assert_eq!(pool.available(bucket_id), 2);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_skips_unchanged_file() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::WriteFiles, Capability::ListFiles]
+        ).await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = UploadFile::builder()
+            .file_name("new-file.txt")?
+            .sha1_checksum("5a08924b0ef1cccfb5de1d94e3d75c38dc0d3c79")
+            .skip_if_sha1_matches()
+            .build()?;
+
+        // There is no upload in the recorded session, so this fails if we
+        // don't skip it.
+        let file = upload_file(&mut upload_auth, file, b"Some text\n").await?;
+
+        assert_eq!(
+            file.file_id(),
+            concat!("4_z8d625eb63be2775577c70e1a_f1052a33c70ab4806_d20211118_",
+                "m153703_c002_v0001160_t0027")
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_from_path_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let path = temp_path("upload-file-from-path.txt");
+        std::fs::write(&path, b"abcd")?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        let res = upload_file_from_path(&mut upload_auth, file, &path).await;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(res?.action, FileAction::Upload);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_options_choose_upload_mode() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        // The recommended part size is 100 MB; the minimum is 5 MB.
+        let auth = create_test_auth(client, vec![]).await;
+
+        let options = UploadOptions::default();
+        assert_eq!(options.part_size_for(&auth, 199_999_999)?, None);
+        assert_eq!(options.part_size_for(&auth, 200_000_000)?,
+            Some(100_000_000));
+        assert_eq!(options.part_size_for(&auth, 6_000_000_000)?,
+            Some(100_000_000));
+
+        let options = UploadOptions::default().large_file_threshold(1000);
+        assert_eq!(options.part_size_for(&auth, 5_000_000)?, None);
+        assert_eq!(options.part_size_for(&auth, 9_000_000)?,
+            Some(5_000_000));
+        assert_eq!(options.part_size_for(&auth, 12_000_000)?,
+            Some(6_000_000));
+
+        let options = options.part_size(5_000_000);
+        assert_eq!(options.part_size_for(&auth, 4_000_000)?, None);
+        assert_eq!(options.part_size_for(&auth, 12_000_000)?,
+            Some(5_000_000));
+
+        assert!(options.part_size(1000).part_size_for(&auth, 12_000_000)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_path_below_threshold() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-path.txt");
+        std::fs::write(&path, b"abcd")?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            UploadOptions::default()
+        ).await;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(res?.action, FileAction::Upload);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_path_reports_single_request_progress()
+    -> anyhow::Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-progress.txt");
+        std::fs::write(&path, b"abcd")?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        let reports = Arc::new(Mutex::new(vec![]));
+
+        let options = UploadOptions::default().progress({
+            let reports = reports.clone();
+            move |sent, total| reports.lock().unwrap().push((sent, total))
+        });
+
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            options
+        ).await;
+        std::fs::remove_file(&path)?;
+
+        res?;
+        assert_eq!(*reports.lock().unwrap(), [(0, 4), (4, 4)]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "futures")]
+    #[async_std::test]
+    async fn upload_many_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-many.txt");
+        std::fs::write(&path, b"abcd")?;
+
+        let files = vec![
+            (path.clone(), "test-upload-many.txt".into()),
+            ("does-not-exist".into(), "file2.txt".into()),
+            (path.clone(), "test-upload-many.txt".into()),
+        ];
+
+        let bucket_id = Some("8d625eb63be2775577c70e1a");
+        let results = upload_many(&auth, bucket_id, files, 1).await;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(results.len(), 3);
+        let mut results = results.into_iter();
+
+        assert_eq!(results.next().unwrap()?.action, FileAction::Upload);
+        assert!(matches!(results.next().unwrap(), Err(Error::IO(_))));
+        assert_eq!(results.next().unwrap()?.action, FileAction::Upload);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "futures")]
+    #[async_std::test]
+    async fn upload_many_reports_each_failure() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let files = vec![
+            ("does-not-exist-1".into(), "file1.txt".into()),
+            ("does-not-exist-2".into(), "file2.txt".into()),
+            ("does-not-exist-3".into(), "file3.txt".into()),
+        ];
+
+        let bucket_id = Some("8d625eb63be2775577c70e1a");
+        let results = upload_many(&auth, bucket_id, files, 2).await;
+
+        assert_eq!(results.len(), 3);
+        for res in results.into_iter() {
+            match res.unwrap_err() {
+                Error::IO(_) => {},
+                e => panic!("Unexpected error type: {:?}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_file_with_expired_authorization() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        assert!(upload_auth.is_valid());
+
+        upload_auth.obtained_at = chrono::Utc::now()
+            - chrono::Duration::hours(25);
+
+        assert!(upload_auth.is_expired());
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .build()?;
+
+        match upload_file(&mut upload_auth, file, b"abcd").await.unwrap_err() {
+            Error::MissingAuthorization => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn copy_file_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::WriteFiles, Capability::ReadFiles]
+        ).await;
+
+        let file = CopyFile::builder()
+            .source_file_id(concat!(
+                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
+                "m151810_c002_v0001168_t0010"
+            ))
+            .destination_file_name("new-file.txt")?
+            .build()?;
+
+        let new_file = copy_file(&mut auth, file).await?;
+        assert_eq!(new_file.file_name, "new-file.txt");
+        assert_eq!(new_file.action, FileAction::Copy);
+
+        Ok(())
+    }
+
+    // TODO: test copy_file with a byte range.
+
+    #[async_std::test]
+    async fn copy_file_rejects_range_past_source() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::WriteFiles, Capability::ReadFiles]
+        ).await;
+
+        // The source file is four bytes long.
+        let file = CopyFile::builder()
+            .source_file_id(concat!(
+                "4_z8d625eb63be2775577c70e1a_f1187926dea44b322_d20211230_",
+                "m171512_c002_v0001110_t0055"
+            ))
+            .destination_file_name("new-file.txt")?
+            .range(ByteRange::new(0, 4)?)
+            .validate_range()
+            .build()?;
+
+        match copy_file(&mut auth, file).await.unwrap_err() {
+            Error::Validation(ValidationError::RangeOutOfBounds {
+                start: 0, end: 4, size: 4
+            }) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn copy_file_rejects_bucket_in_other_account()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/buckets.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::WriteFiles, Capability::ListBuckets]
+        ).await;
+
+        // No bucket with this ID is listed for the account.
+        let file = CopyFile::builder()
+            .source_file_id(concat!(
+                "4_z8d625eb63be2775577c70e1a_f1187926dea44b322_d20211230_",
+                "m171512_c002_v0001110_t0055"
+            ))
+            .destination_bucket_id("0d625eb63be2775577c70e1a")
+            .destination_file_name("new-file.txt")?
+            .validate_destination_bucket()
+            .build()?;
+
+        match copy_file(&mut auth, file).await.unwrap_err() {
+            Error::Validation(ValidationError::Incompatible(msg)) =>
+                assert!(msg.contains("0d625eb63be2775577c70e1a")),
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn copy_file_part_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::WriteFiles, Capability::ReadFiles]
+        ).await;
+
+        let file = StartLargeFile::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name("Test-large-file2.txt")?
+            .content_type("text/plain")
+            .build()?;
+
+        let file = start_large_file(&mut auth, file).await?;
+
+        let part1 = CopyFilePart::builder()
+            .source_file_id(concat!(
+                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
+                "m151810_c002_v0001168_t0010"
+            ))
+            .destination_large_file(&file)
+            .part_number(1)?
+            .build()?;
+
+        let part2 = CopyFilePart::builder()
+            .source_file_id(concat!(
+                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
+                "m151810_c002_v0001168_t0010"
+            ))
+            .destination_large_file(&file)
+            .part_number(2)?
+            .range(ByteRange::new(0, 3)?)
+            .build()?;
+
+        let part1 = copy_file_part(&mut auth, part1).await?;
+        let part2 = copy_file_part(&mut auth, part2).await?;
+
+        assert_eq!(part1.part_number, 1);
+        assert_eq!(part2.part_number, 2);
+
+        let _file = cancel_large_file(&mut auth, file).await?;
+        Ok(())
+    }
+
+    // TODO: File header tests.
+
+    #[async_std::test]
+    async fn download_file_by_id_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = DownloadFile::with_id(concat!("4_z8d625eb63be2775577c70e1a_f",
+            "111954e3108ff3f6_d20211118_m151810_c002_v0001168_t0010"));
+
+        let (file, _headers) = download_file(&mut auth, req).await?;
+        assert_eq!(file, b"Some text\n");
+        // The client's read buffer is not kept.
+        assert_eq!(file.capacity(), file.len());
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_file_by_name_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+
+        let (file, _headers) = download_file(&mut auth, req).await?;
+        assert_eq!(file, b"Some text\n");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_file_by_name_via_download_authorization_success()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::ReadFiles, Capability::ShareFiles]
+        ).await;
+
+        let req = DownloadAuthorizationRequest::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name_prefix("test")?
+            .duration(chrono::Duration::seconds(30))?
+            .build()?;
+
+        let mut download_auth = get_download_authorization(
+            &mut auth,
+            req
+        ).await?;
+
+        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+
+        let (file, _headers) = download_file(&mut download_auth, req).await?;
+        assert_eq!(file, b"Some text\n");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_header_value_prefers_download_authorization()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+        let dl = signed_url_test_auth(DownloadOverrides::default());
+
+        assert_eq!(auth.download_header_value(None),
+            auth.authorization_token());
+        assert_eq!(auth.download_header_value(Some(&dl)), "some-token");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn renew_download_authorization_keeps_scope() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ShareFiles])
+            .await;
+
+        let mut existing = signed_url_test_auth(DownloadOverrides::default());
+        existing.obtained_at = chrono::Utc::now() - chrono::Duration::hours(1);
+        assert!(existing.is_probably_expired());
+
+        let renewed = renew_download_authorization(
+            &mut auth,
+            &existing,
+            std::time::Duration::from_secs(30)
+        ).await?;
+
+        assert_eq!(renewed.bucket_id(), existing.bucket_id());
+        assert_eq!(renewed.file_name_prefix(), existing.file_name_prefix());
+        assert!(! renewed.is_probably_expired());
+        assert!(renewed.expiration() <= chrono::Utc::now()
+            + chrono::Duration::seconds(30));
+
+        let res = renew_download_authorization(
+            &mut auth,
+            &existing,
+            chrono::Duration::weeks(2)
+        ).await;
+
+        assert!(matches!(res,
+            Err(Error::Validation(ValidationError::OutOfBounds(_)))
+        ));
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn head_file_by_id_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let file = head_file_by_id(&mut auth, concat!(
+            "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
+            "m151810_c002_v0001168_t0010"
+        )).await?;
+
+        assert_eq!(file.file_name(), "test-file.txt");
+        assert_eq!(file.content_length(), 10);
+        assert_eq!(file.content_type(), Some("text/plain"));
+        assert_eq!(
+            file.content_sha1(),
+            Some("5a08924b0ef1cccfb5de1d94e3d75c38dc0d3c79")
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn head_file_by_name_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let file = head_file_by_name(
+            &mut auth,
+            "testing-b2-client",
+            "test-file.txt"
+        ).await?;
+
+        assert_eq!(file.file_id(), concat!(
+            "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
+            "m151810_c002_v0001168_t0010"
+        ));
+        assert_eq!(
+            file.file_info().get("src_last_modified_millis")
+                .map(String::as_str),
+            Some("1637248673875")
+        );
+
+        Ok(())
+    }
+
+    /* TODO: Setup, write these tests.
+    #[async_std::test]
+    async fn download_file_not_authorized() -> anyhow::Result<()> {
+        todo!()
+    }
+
+    #[async_std::test]
+    async fn download_public_file_without_read_cap() -> anyhow::Result<()> {
+        todo!()
+    }
+    */
+
+    #[async_std::test]
+    async fn download_file_range_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .range(ByteRange::new(5, 8)?)
+            .build()?;
+
+        let (file, _headers) = download_file(&mut auth, req).await?;
+        assert_eq!(file, b"text");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "futures")]
+    #[async_std::test]
+    async fn download_file_accelerated_reassembles_ranges()
+    -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/accelerated_download.yaml",
+            None, None
+        ).await?;
+
+        let auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = ||
+            DownloadFile::with_name("test-file.txt", "testing-b2-client");
+
+        let file = download_file_accelerated(&auth, req(), 10, 3).await?;
+        assert_eq!(file, b"Some text\n");
+
+        // The last range extends past the end of the file.
+        let file = download_file_accelerated(&auth, req(), 12, 2).await?;
+        assert_eq!(file, b"Some text\n");
+
+        // Only the first range is within the file; the buffer is no larger
+        // than the file.
+        let file = download_file_accelerated(&auth, req(), 100, 3).await?;
+        assert_eq!(file, b"Some text\n");
+        assert_eq!(file.capacity(), 10);
+
+        let req =
+            DownloadFile::with_name("bad-checksum.txt", "testing-b2-client");
+
+        match download_file_accelerated(&auth, req, 10, 2).await.unwrap_err() {
+            Error::ChecksumMismatch { actual, .. } =>
+                assert_eq!(actual, compute_sha1(b"Some text\n")),
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_file_larger_than_limit() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .max_bytes(4)
+            .build()?;
+
+        match download_file(&mut auth, req).await.unwrap_err() {
+            Error::ResponseTooLarge(4) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_file_within_limit() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .max_bytes(10)
+            .build()?;
+
+        let (file, _headers) = download_file(&mut auth, req).await?;
+        assert_eq!(file, b"Some text\n");
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn download_file_into_reuses_buffer() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let mut buf = vec![];
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .build()?;
+
+        let headers = download_file_into(&mut auth, req, &mut buf).await?;
+        assert_eq!(buf, b"Some text\n");
+        assert_eq!(headers.file_name(), "test-file.txt");
+
+        let allocation = buf.as_ptr();
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .range(ByteRange::new(5, 8)?)
+            .build()?;
+
+        download_file_into(&mut auth, req, &mut buf).await?;
+        assert_eq!(buf, b"text");
+        assert_eq!(buf.as_ptr(), allocation);
+
+        Ok(())
+    }
+
+    // TODO: Test download with custom headers.
+
+    #[async_std::test]
+    async fn delete_file_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/delete_file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::DeleteFiles, Capability::WriteFiles]
+        ).await;
+
+        let mut upload_auth = get_upload_authorization_by_id(
+            &mut auth,
+            "8d625eb63be2775577c70e1a"
+        ).await?;
+
+        let file = UploadFile::builder()
+            .file_name("test-file-upload.txt")?
+            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
+            .build()?;
+
+        let file = upload_file(&mut upload_auth, file, b"abcd").await?;
+
+
+        let _ = delete_file_version(&mut auth, file, BypassGovernance::No)
+            .await?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn delete_all_file_versions_relists_after_deleting()
+    -> anyhow::Result<()> {
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/delete_file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(
-            client,
-            vec![Capability::WriteFiles, Capability::ReadFiles]
-        ).await;
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new(|path, res| {
+                if ! path.ends_with("b2_list_file_versions") {
+                    return;
+                }
+
+                // Replace the recorded version in the second listing with a
+                // version uploaded during the deletion, and list no versions
+                // after that.
+                match res.call {
+                    0 => {},
+                    1 => res.body = res.body.to_string()
+                        .replace("m150118", "m150120")
+                        .parse().unwrap(),
+                    _ => res.body = serde_json::json!({
+                        "files": [],
+                        "nextFileId": null,
+                        "nextFileName": null,
+                    }),
+                }
+            }))
+            .with_middleware(vcr);
+
+        let mut auth = create_test_auth(
+            client,
+            vec![Capability::ListFiles, Capability::DeleteFiles]
+        ).await;
+
+        let files = delete_all_file_versions(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            "test-file-upload.txt",
+            BypassGovernance::No,
+            false
+        ).await?;
+
+        assert_eq!(files.len(), 2);
+        assert_ne!(files[0].file_id, files[1].file_id);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_large_file_full_process() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            Some(Box::new(|req| {
+                use surf_vcr::Body;
+
+                if let Body::Str(body) = &mut req.body {
+                    if body.starts_with("aaaaa") {
+                        // We don't need to store 5 MB of nothing for our test.
+                        req.body = Body::Str("aaaaa for 5 MB of data".into());
+                    }
+                }
+            })),
+            None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let file = StartLargeFile::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name("Test-large-file.txt")?
+            .content_type("text/plain")
+            .build()?;
+
+        let file = start_large_file(&mut auth, file).await?;
+        let mut upload_auth = get_upload_part_authorization(&mut auth, &file)
+            .await?;
+
+        // All but the last part must be at least 5MB.
+        let data1: Vec<u8> = [b'a'].iter().cycle().take(5*1024*1024)
+            .cloned().collect();
+
+        let upload = UploadFilePart::builder()
+            .part_number(1)
+            .part_sha1_checksum("61b8d6600ac94d912874f569a9341120f680c9f8")
+            .build();
+
+
+        let _part1 = upload_file_part(&mut upload_auth, &upload, &data1).await?;
+
+        let upload = upload.create_next_part(
+            Some("924f61661a3472da74307a35f2c8d22e07e84a4d")
+        )?;
+
+        let _part2 = upload_file_part(&mut upload_auth, &upload, b"bcd").await?;
+
+        let file = finish_large_file_upload(
+            &mut auth,
+            &file,
+            &[
+                "61b8d6600ac94d912874f569a9341120f680c9f8".into(),
+                "924f61661a3472da74307a35f2c8d22e07e84a4d".into(),
+            ]
+        ).await?;
+
+        assert_eq!(file.action, FileAction::Upload);
 
-        let file = CopyFile::builder()
-            .source_file_id(concat!(
-                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
-                "m151810_c002_v0001168_t0010"
-            ))
-            .destination_file_name("new-file.txt")?
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_path_above_threshold() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            Some(Box::new(|req| {
+                use surf_vcr::Body;
+
+                if let Body::Str(body) = &mut req.body {
+                    if body.starts_with("aaaaa") {
+                        req.body = Body::Str("aaaaa for 5 MB of data".into());
+                    }
+                }
+            })),
+            None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-path-large");
+        let mut data = vec![b'a'; 5 * 1024 * 1024];
+        data.extend_from_slice(b"bcd");
+        std::fs::write(&path, &data)?;
+
+        let file = UploadFile::builder()
+            .file_name("Test-large-file.txt")?
+            .content_type("text/plain")
             .build()?;
 
-        let new_file = copy_file(&mut auth, file).await?;
-        assert_eq!(new_file.file_name, "new-file.txt");
-        assert_eq!(new_file.action, FileAction::Copy);
+        let options = UploadOptions::default()
+            .large_file_threshold(5 * 1024 * 1024)
+            .part_size(5 * 1024 * 1024);
+
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            options
+        ).await;
+        std::fs::remove_file(&path)?;
+
+        let file = res?;
+        assert_eq!(file.action, FileAction::Upload);
+        assert_eq!(file.content_length, 5242883);
 
         Ok(())
     }
 
-    // TODO: test copy_file with a byte range.
-
     #[async_std::test]
-    async fn copy_file_part_success() -> anyhow::Result<()> {
+    async fn upload_path_reports_progress_per_part() -> anyhow::Result<()> {
+        use std::sync::{Arc, Mutex};
+
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/large_file.yaml",
-            None, None
+            Some(Box::new(|req| {
+                use surf_vcr::Body;
+
+                if let Body::Str(body) = &mut req.body {
+                    if body.starts_with("aaaaa") {
+                        req.body = Body::Str("aaaaa for 5 MB of data".into());
+                    }
+                }
+            })),
+            None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-progress-large");
+        let mut data = vec![b'a'; 5 * 1024 * 1024];
+        data.extend_from_slice(b"bcd");
+        std::fs::write(&path, &data)?;
+
+        let file = UploadFile::builder()
+            .file_name("Test-large-file.txt")?
+            .content_type("text/plain")
+            .build()?;
+
+        let reports = Arc::new(Mutex::new(vec![]));
+
+        let options = UploadOptions::default()
+            .large_file_threshold(5 * 1024 * 1024)
+            .part_size(5 * 1024 * 1024)
+            .progress({
+                let reports = reports.clone();
+                move |sent, _| reports.lock().unwrap().push(sent)
+            });
+
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            options
+        ).await;
+        std::fs::remove_file(&path)?;
+
+        res?;
+        assert_eq!(*reports.lock().unwrap(), [0, 5242880, 5242883]);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_path_accepts_already_finished_large_file()
+    -> anyhow::Result<()> {
+        use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            Some(Box::new(|req| {
+                use surf_vcr::Body;
+
+                if let Body::Str(body) = &mut req.body {
+                    if body.starts_with("aaaaa") {
+                        req.body = Body::Str("aaaaa for 5 MB of data".into());
+                    }
+                }
+            })),
+            None
         ).await?;
 
+        let finishes = Arc::new(AtomicUsize::new(0));
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new({
+                let finishes = finishes.clone();
+
+                // Replace B2's response to finishing the large file with a
+                // transient error, as if the file was finished but the
+                // response was lost; the retry then finds it already finished.
+                move |path, res| if path.ends_with("b2_finish_large_file") {
+                    finishes.fetch_add(1, Ordering::SeqCst);
+
+                    match res.call {
+                        0 => res.error(503, "service_unavailable",
+                            "Service temporarily unavailable"),
+                        _ => res.error(400, "bad_request",
+                            "No active upload for this file"),
+                    }
+                }
+            }))
+            .with_middleware(vcr);
+
         let mut auth = create_test_auth(
             client,
             vec![Capability::WriteFiles, Capability::ReadFiles]
         ).await;
 
-        let file = StartLargeFile::builder()
-            .bucket_id("8d625eb63be2775577c70e1a")
-            .file_name("Test-large-file2.txt")?
+        let path = temp_path("upload-path-finished");
+        let mut data = vec![b'a'; 5 * 1024 * 1024];
+        data.extend_from_slice(b"bcd");
+        std::fs::write(&path, &data)?;
+
+        let file = UploadFile::builder()
+            .file_name("Test-large-file.txt")?
             .content_type("text/plain")
             .build()?;
 
-        let file = start_large_file(&mut auth, file).await?;
+        let options = UploadOptions::default()
+            .large_file_threshold(5 * 1024 * 1024)
+            .part_size(5 * 1024 * 1024);
 
-        let part1 = CopyFilePart::builder()
-            .source_file_id(concat!(
-                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
-                "m151810_c002_v0001168_t0010"
-            ))
-            .destination_large_file(&file)
-            .part_number(1)?
-            .build()?;
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            options
+        ).await;
+        std::fs::remove_file(&path)?;
 
-        let part2 = CopyFilePart::builder()
-            .source_file_id(concat!(
-                "4_z8d625eb63be2775577c70e1a_f111954e3108ff3f6_d20211118_",
-                "m151810_c002_v0001168_t0010"
-            ))
-            .destination_large_file(&file)
-            .part_number(2)?
-            .range(ByteRange::new(0, 3)?)
+        let file = res?;
+        assert_eq!(file.action, FileAction::Upload);
+        assert_eq!(file.content_length, 5242883);
+        assert_eq!(finishes.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn upload_path_cancels_large_file() -> anyhow::Result<()> {
+        use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+        use crate::{
+            client::SurfClient,
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        let cancel = CancellationToken::new();
+        let cancelled = Arc::new(AtomicUsize::new(0));
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/large_file.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new({
+                let (cancel, cancelled) = (cancel.clone(), cancelled.clone());
+
+                // Cancel the token once the large file is started, and count
+                // the requests to cancel it.
+                move |path, _| if path.ends_with("b2_start_large_file") {
+                    cancel.cancel();
+                } else if path.ends_with("b2_cancel_large_file") {
+                    cancelled.fetch_add(1, Ordering::SeqCst);
+                }
+            }))
+            .with_middleware(vcr);
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let path = temp_path("upload-path-cancel");
+        std::fs::write(&path, vec![b'a'; 5 * 1024 * 1024 + 3])?;
+
+        let file = UploadFile::builder()
+            .file_name("Test-large-file2.txt")?
+            .content_type("text/plain")
             .build()?;
 
-        let part1 = copy_file_part(&mut auth, part1).await?;
-        let part2 = copy_file_part(&mut auth, part2).await?;
+        let options = UploadOptions::default()
+            .large_file_threshold(5 * 1024 * 1024)
+            .part_size(5 * 1024 * 1024)
+            .cancellation_token(cancel);
+
+        let res = upload_path(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            file,
+            &path,
+            options
+        ).await;
+        std::fs::remove_file(&path)?;
+
+        match res.unwrap_err() {
+            Error::Cancelled => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+        assert_eq!(cancelled.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_get_file_info() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+            .await;
+
+        let file_info = get_file_info(
+            &mut auth,
+            concat!("4_z8d625eb63be2775577c70e1a_f1187926dea44b322_d20211230",
+                "_m171512_c002_v0001110_t0055")
+        ).await?;
+
+        assert_eq!(
+            file_info.content_sha1,
+            Some(String::from("81fe8bfe87576c3ecb22426f8e57847382917acf"))
+        );
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_hide_file() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+            .await;
+
+        let file = hide_file_by_name(
+            &mut auth,
+            "8d625eb63be2775577c70e1a",
+            "test-file.txt"
+        ).await?;
+
+        assert_eq!(file.action, FileAction::Hide);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn test_list_file_names() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None,
+            Some(std::boxed::Box::new(move |res| {
+                use surf_vcr::Body;
+
+                if let Body::Str(body) = &mut res.body {
+                    let body_json: Result<serde_json::Value, _> =
+                        serde_json::from_str(body);
+
+                    if let Ok(mut body) = body_json {
+                        if let Some(files) = body.get_mut("files") {
+                            let files = files.as_array_mut().unwrap();
+
+                            for file in files.iter_mut() {
+                                file["accountId"] = serde_json::Value::String(
+                                    "hidden account id".into()
+                                );
+                            }
+                        }
+
+                        res.body = Body::Str(body.to_string());
+                    }
+                }
+            }))
+        ).await?;
+
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
+
+        let req = ListFileNames::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .max_file_count(5)
+            .build().unwrap();
 
-        assert_eq!(part1.part_number, 1);
-        assert_eq!(part2.part_number, 2);
+        let (files, next_req) = list_file_names(&mut auth, req).await?;
+
+        assert_eq!(files.len(), 2);
+        assert!(next_req.is_none());
 
-        let _file = cancel_large_file(&mut auth, file).await?;
         Ok(())
     }
 
-    // TODO: File header tests.
-
     #[async_std::test]
-    async fn download_file_by_id_success() -> anyhow::Result<()> {
+    async fn test_list_all_file_names() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let req = DownloadFile::with_id(concat!("4_z8d625eb63be2775577c70e1a_f",
-            "111954e3108ff3f6_d20211118_m151810_c002_v0001168_t0010"));
+        let req = ListFileNames::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .max_file_count(5)
+            .build().unwrap();
 
-        let (file, _headers) = download_file(&mut auth, req).await?;
-        assert_eq!(file, b"Some text\n");
+        let files = list_all_file_names(&mut auth, req, None).await?;
+        assert_eq!(files.len(), 2);
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn download_file_by_name_success() -> anyhow::Result<()> {
+    async fn list_all_file_names_stops_at_limit() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+        let req = ListFileNames::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .max_file_count(5)
+            .build().unwrap();
 
-        let (file, _headers) = download_file(&mut auth, req).await?;
-        assert_eq!(file, b"Some text\n");
+        let files = list_all_file_names(&mut auth, req, Some(1)).await?;
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].file_name(), "new-file.txt");
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn download_file_by_name_via_download_authorization_success()
-    -> anyhow::Result<()> {
+    async fn test_list_bucket_files() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(
-            client,
-            vec![Capability::ReadFiles, Capability::ShareFiles]
-        ).await;
-
-        let req = DownloadAuthorizationRequest::builder()
-            .bucket_id("8d625eb63be2775577c70e1a")
-            .file_name_prefix("test")?
-            .duration(chrono::Duration::seconds(30))?
-            .build()?;
-
-        let mut download_auth = get_download_authorization(
-            &mut auth,
-            req
-        ).await?;
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
 
-        let req = DownloadFile::with_name("test-file.txt", "testing-b2-client");
+        let bucket: Bucket = serde_json::from_value(serde_json::json!({
+            "accountId": "hidden-account-id",
+            "bucketId": "8d625eb63be2775577c70e1a",
+            "bucketName": "testing-b2-client",
+            "bucketType": "allPrivate",
+            "bucketInfo": {},
+            "corsRules": [],
+            "fileLockConfiguration": {
+                "isClientAuthorizedToRead": false,
+                "value": null,
+            },
+            "defaultServerSideEncryption": {
+                "isClientAuthorizedToRead": false,
+                "value": null,
+            },
+            "lifecycleRules": [],
+            "revision": 2,
+            "options": ["s3"],
+        }))?;
+
+        let (files, next_req) = list_bucket_files(&mut auth, &bucket, None)
+            .await?;
 
-        let (file, _headers) = download_file(&mut download_auth, req).await?;
-        assert_eq!(file, b"Some text\n");
+        assert_eq!(files.len(), 2);
+        assert!(next_req.is_none());
 
         Ok(())
     }
 
-    /* TODO: Setup, write these tests.
-    #[async_std::test]
-    async fn download_file_not_authorized() -> anyhow::Result<()> {
-        todo!()
-    }
-
+    #[cfg(feature = "futures")]
     #[async_std::test]
-    async fn download_public_file_without_read_cap() -> anyhow::Result<()> {
-        todo!()
-    }
-    */
+    async fn list_file_summaries_streams_files() -> anyhow::Result<()> {
+        use futures_util::StreamExt as _;
 
-    #[async_std::test]
-    async fn download_file_range_success() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let req = DownloadFile::builder()
-            .file_name("test-file.txt", "testing-b2-client")
-            .range(ByteRange::new(5, 8)?)
-            .build()?;
+        let files: Vec<_> = list_file_summaries(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            None
+        )?.collect().await;
 
-        let (file, _headers) = download_file(&mut auth, req).await?;
-        assert_eq!(file, b"text");
+        let files = files.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].file_name(), "new-file.txt");
+        assert_eq!(files[0].content_length(), 10);
+        assert_eq!(files[0].sha1_checksum(),
+            Some("5a08924b0ef1cccfb5de1d94e3d75c38dc0d3c79"));
+        assert!(files[0].upload_time().is_some());
 
         Ok(())
     }
 
-    // TODO: Test download with custom headers.
-
     #[async_std::test]
-    async fn delete_file_success() -> anyhow::Result<()> {
+    async fn delete_all_file_versions_dry_run() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
-            "test_sessions/delete_file.yaml",
+            "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(
-            client,
-            vec![Capability::DeleteFiles, Capability::WriteFiles]
-        ).await;
+        // A dry run does not need the DeleteFiles capability, and the session
+        // has no recorded deletions.
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
 
-        let mut upload_auth = get_upload_authorization_by_id(
+        let files = delete_all_file_versions(
             &mut auth,
-            "8d625eb63be2775577c70e1a"
+            Some("8d625eb63be2775577c70e1a"),
+            "test-file.txt",
+            BypassGovernance::No,
+            true
         ).await?;
 
-        let file = UploadFile::builder()
-            .file_name("test-file-upload.txt")?
-            .sha1_checksum("81fe8bfe87576c3ecb22426f8e57847382917acf")
-            .build()?;
-
-        let file = upload_file(&mut upload_auth, file, b"abcd").await?;
-
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|f| f.file_name == "test-file.txt"));
 
-        let _ = delete_file_version(&mut auth, file, BypassGovernance::No)
-            .await?;
+        match delete_all_file_versions(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            "test-file.txt",
+            BypassGovernance::No,
+            false
+        ).await.unwrap_err() {
+            Error::Unauthorized(Capability::DeleteFiles) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn upload_large_file_full_process() -> anyhow::Result<()> {
+    async fn test_list_file_versions() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
-            "test_sessions/large_file.yaml",
-            Some(Box::new(|req| {
+            "test_sessions/file.yaml",
+            None,
+            Some(std::boxed::Box::new(move |res| {
                 use surf_vcr::Body;
 
-                if let Body::Str(body) = &mut req.body {
-                    if body.starts_with("aaaaa") {
-                        // We don't need to store 5 MB of nothing for our test.
-                        req.body = Body::Str("aaaaa for 5 MB of data".into());
+                if let Body::Str(body) = &mut res.body {
+                    let body_json: Result<serde_json::Value, _> =
+                        serde_json::from_str(body);
+
+                    if let Ok(mut body) = body_json {
+                        if let Some(files) = body.get_mut("files") {
+                            let files = files.as_array_mut().unwrap();
+
+                            for file in files.iter_mut() {
+                                file["accountId"] = serde_json::Value::String(
+                                    "hidden account id".into()
+                                );
+                            }
+                        }
+
+                        res.body = Body::Str(body.to_string());
                     }
                 }
-            })),
-            None
+            }))
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let file = StartLargeFile::builder()
+        let req = ListFileVersions::builder()
             .bucket_id("8d625eb63be2775577c70e1a")
-            .file_name("Test-large-file.txt")?
-            .content_type("text/plain")
-            .build()?;
+            .max_file_count(5)
+            .build().unwrap();
 
-        let file = start_large_file(&mut auth, file).await?;
-        let mut upload_auth = get_upload_part_authorization(&mut auth, &file)
-            .await?;
+        let (files, next_req) = list_file_versions(&mut auth, req).await?;
 
-        // All but the last part must be at least 5MB.
-        let data1: Vec<u8> = [b'a'].iter().cycle().take(5*1024*1024)
-            .cloned().collect();
+        assert_eq!(files.len(), 4);
+        assert!(next_req.is_none());
 
-        let upload = UploadFilePart::builder()
-            .part_number(1)
-            .part_sha1_checksum("61b8d6600ac94d912874f569a9341120f680c9f8")
-            .build();
+        Ok(())
+    }
 
+    #[async_std::test]
+    async fn list_file_versions_for_single_file() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
+        ).await?;
 
-        let _part1 = upload_file_part(&mut upload_auth, &upload, &data1).await?;
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
+            .await;
 
-        let upload = upload.create_next_part(
-            Some("924f61661a3472da74307a35f2c8d22e07e84a4d")
-        )?;
+        // "notes.txt.bak" follows the two versions of "notes.txt".
+        let req = ListFileVersions::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .for_single_file("notes.txt")?
+            .max_file_count(3)
+            .build()?;
 
-        let _part2 = upload_file_part(&mut upload_auth, &upload, b"bcd").await?;
+        let (files, next_req) = list_file_versions(&mut auth, req).await?;
 
-        let file = finish_large_file_upload(
-            &mut auth,
-            &file,
-            &[
-                "61b8d6600ac94d912874f569a9341120f680c9f8".into(),
-                "924f61661a3472da74307a35f2c8d22e07e84a4d".into(),
-            ]
-        ).await?;
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|f| f.file_name() == "notes.txt"));
+        assert!(next_req.is_none());
 
-        assert_eq!(file.action, FileAction::Upload);
+        // The page ends with the last version of "notes.txt".
+        let req = ListFileVersions::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .for_single_file("notes.txt")?
+            .max_file_count(2)
+            .build()?;
+
+        let (files, next_req) = list_file_versions(&mut auth, req).await?;
+
+        assert_eq!(files.len(), 2);
+        assert!(next_req.is_none());
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn test_get_file_info() -> anyhow::Result<()> {
+    async fn list_file_versions_excluding_hidden_files() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::ReadFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let file_info = get_file_info(
-            &mut auth,
-            concat!("4_z8d625eb63be2775577c70e1a_f1187926dea44b322_d20211230",
-                "_m171512_c002_v0001110_t0055")
-        ).await?;
+        let list = |exclude_hidden| {
+            let req = ListFileVersions::builder()
+                .bucket_id("8d625eb63be2775577c70e1a")
+                .prefix("reuploaded/").unwrap()
+                .max_file_count(4);
 
-        assert_eq!(
-            file_info.content_sha1,
-            Some(String::from("81fe8bfe87576c3ecb22426f8e57847382917acf"))
-        );
+            if exclude_hidden { req.exclude_hidden() } else { req }
+                .build().unwrap()
+        };
+
+        let (files, next_req) = list_file_versions(&mut auth, list(false))
+            .await?;
+        assert_eq!(files.len(), 4);
+
+        let (files, _) = list_file_versions(&mut auth, next_req.unwrap())
+            .await?;
+        assert_eq!(files.len(), 2);
+
+        // a.txt was hidden, then uploaded again; b.txt is hidden, and its
+        // versions span both pages.
+        let mut names = vec![];
+        let mut request = Some(list(true));
+
+        while let Some(req) = request {
+            let (files, next_req) = list_file_versions(&mut auth, req).await?;
+            names.extend(files.into_iter().map(|f| f.file_name));
+            request = next_req;
+        }
+
+        assert_eq!(names, [
+            "reuploaded/a.txt",
+            "reuploaded/a.txt",
+            "reuploaded/a.txt",
+            "reuploaded/c.txt",
+        ]);
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn test_hide_file() -> anyhow::Result<()> {
+    async fn get_latest_file_version_of_hidden_file() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
             "test_sessions/file.yaml",
             None, None
         ).await?;
 
-        let mut auth = create_test_auth(client, vec![Capability::WriteFiles])
+        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let file = hide_file_by_name(
+        let file = get_latest_file_version(
             &mut auth,
-            "8d625eb63be2775577c70e1a",
+            Some("8d625eb63be2775577c70e1a"),
             "test-file.txt"
         ).await?;
 
-        assert_eq!(file.action, FileAction::Hide);
+        assert!(file.is_none());
 
         Ok(())
     }
 
-    #[async_std::test]
-    async fn test_list_file_names() -> anyhow::Result<()> {
-        let client = create_test_client(
-            VcrMode::Replay,
-            "test_sessions/file.yaml",
-            None,
-            Some(std::boxed::Box::new(move |res| {
-                use surf_vcr::Body;
-
-                if let Body::Str(body) = &mut res.body {
-                    let body_json: Result<serde_json::Value, _> =
-                        serde_json::from_str(body);
-
-                    if let Ok(mut body) = body_json {
-                        if let Some(files) = body.get_mut("files") {
-                            let files = files.as_array_mut().unwrap();
-
-                            for file in files.iter_mut() {
-                                file["accountId"] = serde_json::Value::String(
-                                    "hidden account id".into()
-                                );
-                            }
-                        }
-
-                        res.body = Body::Str(body.to_string());
-                    }
-                }
-            }))
+    #[async_std::test]
+    async fn get_latest_file_version_success() -> anyhow::Result<()> {
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/file.yaml",
+            None, None
         ).await?;
 
         let mut auth = create_test_auth(client, vec![Capability::ListFiles])
             .await;
 
-        let req = ListFileNames::builder()
-            .bucket_id("8d625eb63be2775577c70e1a")
-            .max_file_count(5)
-            .build().unwrap();
-
-        let (files, next_req) = list_file_names(&mut auth, req).await?;
+        let file = get_latest_file_version(
+            &mut auth,
+            Some("8d625eb63be2775577c70e1a"),
+            "new-file.txt"
+        ).await?.unwrap();
 
-        assert_eq!(files.len(), 2);
-        assert!(next_req.is_none());
+        assert_eq!(file.file_name(), "new-file.txt");
+        assert_eq!(file.action(), FileAction::Upload);
 
         Ok(())
     }
 
     #[async_std::test]
-    async fn test_list_file_versions() -> anyhow::Result<()> {
+    async fn rename_file_copies_then_deletes() -> anyhow::Result<()> {
         let client = create_test_client(
             VcrMode::Replay,
-            "test_sessions/file.yaml",
-            None,
-            Some(std::boxed::Box::new(move |res| {
-                use surf_vcr::Body;
-
-                if let Body::Str(body) = &mut res.body {
-                    let body_json: Result<serde_json::Value, _> =
-                        serde_json::from_str(body);
-
-                    if let Ok(mut body) = body_json {
-                        if let Some(files) = body.get_mut("files") {
-                            let files = files.as_array_mut().unwrap();
+            "test_sessions/rename_file.yaml",
+            None, None
+        ).await?;
 
-                            for file in files.iter_mut() {
-                                file["accountId"] = serde_json::Value::String(
-                                    "hidden account id".into()
-                                );
-                            }
-                        }
+        let mut auth = create_test_auth(client, vec![
+            Capability::ListFiles,
+            Capability::WriteFiles,
+            Capability::DeleteFiles,
+        ]).await;
 
-                        res.body = Body::Str(body.to_string());
-                    }
-                }
-            }))
-        ).await?;
+        let bucket_id = Some("8d625eb63be2775577c70e1a");
 
-        let mut auth = create_test_auth(client, vec![Capability::ListFiles])
-            .await;
+        let file = rename_file(&mut auth, bucket_id, "old-name.txt",
+            "new-name.txt").await?;
 
-        let req = ListFileVersions::builder()
-            .bucket_id("8d625eb63be2775577c70e1a")
-            .max_file_count(5)
-            .build().unwrap();
+        assert_eq!(file.file_name(), "new-name.txt");
+        assert_eq!(file.action(), FileAction::Copy);
 
-        let (files, next_req) = list_file_versions(&mut auth, req).await?;
+        match rename_file(&mut auth, bucket_id, "locked.txt", "unlocked.txt")
+            .await.unwrap_err()
+        {
+            Error::RenameIncomplete { file, error } => {
+                assert_eq!(file.file_name(), "unlocked.txt");
 
-        assert_eq!(files.len(), 4);
-        assert!(next_req.is_none());
+                match *error {
+                    Error::B2(e) =>
+                        assert_eq!(e.code(), ErrorCode::AccessDenied),
+                    e => panic!("Unexpected error type: {:?}", e),
+                }
+            },
+            e => panic!("Unexpected error type: {:?}", e),
+        }
 
         Ok(())
     }
@@ -4787,6 +9754,451 @@ mod tests_mocked {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::NoClient;
+
+    fn download_authorization_request(cache_control: CacheControl)
+    -> DownloadAuthorizationRequestBuilder<'static> {
+        DownloadAuthorizationRequest::builder()
+            .bucket_id("8d625eb63be2775577c70e1a")
+            .file_name_prefix("files/").unwrap()
+            .duration(chrono::Duration::seconds(30)).unwrap()
+            .cache_control(cache_control)
+    }
+
+    #[test]
+    fn download_authorization_rejects_contradictory_caching() {
+        use http_types::{cache::CacheDirective, Trailers};
+
+        let cache_control = |directives: &[CacheDirective]| {
+            let mut cache_control = CacheControl::new();
+
+            for directive in directives {
+                cache_control.push(directive.clone());
+            }
+
+            cache_control
+        };
+
+        let mut header = Trailers::new();
+        header.insert("Expires", "Fri, 21 Jan 2022 14:10:49 GMT");
+        let expires = Expires::from_headers(header.as_ref()).unwrap().unwrap();
+
+        let no_store = cache_control(&[CacheDirective::NoStore]);
+
+        match download_authorization_request(no_store).expiration(expires)
+            .build().unwrap_err()
+        {
+            ValidationError::Incompatible(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let max_age = CacheDirective::MaxAge(
+            std::time::Duration::from_secs(60)
+        );
+
+        match download_authorization_request(
+            cache_control(&[CacheDirective::NoStore, max_age.clone()])
+        ).build().unwrap_err() {
+            ValidationError::Incompatible(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        assert!(
+            download_authorization_request(cache_control(&[max_age]))
+                .build().is_ok()
+        );
+    }
+
+    #[test]
+    fn download_authorization_rejects_expiration_after_duration() {
+        use crate::clock::FixedClock;
+        use chrono::TimeZone as _;
+        use http_types::Trailers;
+
+        let expires = || {
+            let mut header = Trailers::new();
+            header.insert("Expires", "Fri, 21 Jan 2022 14:10:49 GMT");
+            Expires::from_headers(header.as_ref()).unwrap().unwrap()
+        };
+
+        // Fri, 21 Jan 2022 14:10:00 GMT
+        let now = chrono::Utc.timestamp_opt(1642774200, 0).unwrap();
+
+        // The authorization ends 30 seconds from now; the expiration is 49
+        // seconds away.
+        match download_authorization_request(CacheControl::new())
+            .expiration(expires())
+            .with_clock(FixedClock::new(now))
+            .build().unwrap_err()
+        {
+            ValidationError::Incompatible(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let now = now + chrono::Duration::seconds(20);
+
+        assert!(
+            download_authorization_request(CacheControl::new())
+                .expiration(expires())
+                .with_clock(FixedClock::new(now))
+                .build().is_ok()
+        );
+    }
+
+    #[test]
+    fn parts_at_minimum_size_are_accepted() {
+        let mut sizes = PartSizes { minimum: 100, ..Default::default() };
+
+        assert!(sizes.check(1, 100).is_ok());
+        sizes.record(1, 100);
+        assert!(sizes.check(2, 100).is_ok());
+        sizes.record(2, 100);
+
+        // The last part may be smaller than the minimum.
+        assert!(sizes.check(3, 1).is_ok());
+        sizes.record(3, 1);
+    }
+
+    #[test]
+    fn part_below_minimum_before_last_part_is_rejected() {
+        let mut sizes = PartSizes { minimum: 100, ..Default::default() };
+
+        sizes.record(1, 99);
+
+        match sizes.check(2, 100) {
+            Err(ValidationError::PartTooSmall { part_number, size, minimum })
+                => assert_eq!((part_number, size, minimum), (1, 99, 100)),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // Replacing the small part with a large enough part is fine.
+        assert!(sizes.check(1, 100).is_ok());
+        sizes.record(1, 100);
+        assert!(sizes.check(2, 100).is_ok());
+    }
+
+    #[test]
+    fn part_below_minimum_after_later_part_is_rejected() {
+        let mut sizes = PartSizes { minimum: 100, ..Default::default() };
+
+        sizes.record(2, 100);
+
+        assert!(matches!(
+            sizes.check(1, 99),
+            Err(ValidationError::PartTooSmall { part_number: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn skip_if_sha1_matches_requires_checksum() {
+        let res = UploadFile::builder()
+            .file_name("some-file.txt").unwrap()
+            .skip_if_sha1_matches()
+            .build();
+
+        assert!(matches!(res, Err(ValidationError::MissingData(_))));
+    }
+
+    #[test]
+    fn upload_file_sha1_checksum_is_validated() {
+        let build = |sha1| UploadFile::builder()
+            .file_name("some-file.txt").unwrap()
+            .sha1_checksum(sha1)
+            .build();
+
+        assert!(build("81FE8BFE87576C3ECB22426F8E57847382917ACF").is_ok());
+        assert!(build("do_not_verify").is_ok());
+
+        for sha1 in [
+            "81fe8bfe87576c3ecb22426f8e57847382917ac",
+            "81fe8bfe87576c3ecb22426f8e57847382917acg",
+            "",
+        ] {
+            assert!(matches!(build(sha1), Err(ValidationError::BadFormat(_))));
+        }
+    }
+
+    #[test]
+    fn compute_sha1_is_lowercase_hex() {
+        assert_eq!(
+            compute_sha1(b"abcd"),
+            "81fe8bfe87576c3ecb22426f8e57847382917acf"
+        );
+        assert_eq!(
+            compute_sha1(b""),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+        );
+    }
+
+    #[test]
+    fn sha1_reader_checksums_data_read() -> anyhow::Result<()> {
+        use std::io::Read as _;
+
+        let mut reader = Sha1Reader::new(&b"abcd"[..]);
+        let mut data = [0; 3];
+
+        reader.read_exact(&mut data)?;
+        assert_eq!(&data, b"abc");
+        assert_eq!(reader.read(&mut data)?, 1);
+        assert_eq!(reader.read(&mut data)?, 0);
+
+        assert_eq!(reader.finalize(), compute_sha1(b"abcd"));
+        assert_eq!(
+            Sha1Reader::new(std::io::empty()).finalize(),
+            compute_sha1(b"")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_download_not_modified() {
+        use chrono::{TimeZone as _, Utc};
+
+        let since = chrono::DateTime::parse_from_rfc3339(
+            "2022-01-01T00:00:00Z"
+        ).unwrap().with_timezone(&Utc);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-bz-upload-timestamp".into(), "1637248690000".into());
+
+        let res = DownloadResult::from_response(
+            Some(304), vec![], headers.clone(), since
+        );
+
+        assert!(matches!(res, DownloadResult::NotModified(_)));
+        assert_eq!(
+            res.last_modified(),
+            Utc.timestamp_millis_opt(1637248690000).single()
+        );
+
+        // Without a status code, the timestamp is used.
+        let res = DownloadResult::from_response(None, vec![], headers, since);
+        assert!(matches!(res, DownloadResult::NotModified(_)));
+
+        let res = DownloadResult::from_response(
+            Some(304), vec![], HeaderMap::new(), since
+        );
+        assert!(matches!(res, DownloadResult::NotModified(_)));
+    }
+
+    #[test]
+    fn conditional_download_modified_empty_file() {
+        use chrono::Utc;
+
+        let since = chrono::DateTime::parse_from_rfc3339(
+            "2021-01-01T00:00:00Z"
+        ).unwrap().with_timezone(&Utc);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Last-Modified".into(),
+            "Thu, 18 Nov 2021 15:18:10 GMT".into()
+        );
+
+        let res = DownloadResult::from_response(
+            Some(200), vec![], headers.clone(), since
+        );
+        assert!(matches!(res, DownloadResult::Modified(_, _)));
+
+        let res = DownloadResult::from_response(None, vec![], headers, since);
+        assert!(matches!(res, DownloadResult::Modified(_, _)));
+
+        // An empty file is not assumed to be unmodified if B2 reports nothing
+        // else about it.
+        let res = DownloadResult::from_response(
+            None, vec![], HeaderMap::new(), since
+        );
+        assert!(matches!(res, DownloadResult::Modified(_, _)));
+    }
+
+    #[test]
+    fn download_response_parses_errors_only_on_failure() {
+        let body = serde_json::to_vec(&serde_json::json!({
+            "status": 404,
+            "code": "not_found",
+            "message": "File not present",
+        })).unwrap();
+
+        // A downloaded file may look like a B2 error.
+        let res = download_response::<&str>(
+            Some(200), body.clone(), HeaderMap::new()
+        );
+        assert_eq!(res.unwrap().1, body);
+
+        let res = download_response::<&str>(
+            Some(404), body.clone(), HeaderMap::new()
+        );
+        assert!(matches!(res, Err(Error::B2(e))
+            if e.code() == ErrorCode::NotFound
+        ));
+
+        let res = download_response::<&str>(None, body, HeaderMap::new());
+        assert!(matches!(res, Err(Error::B2(_))));
+
+        let res = download_response::<&str>(
+            Some(304), vec![], HeaderMap::new()
+        );
+        assert!(res.is_ok());
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn accelerated_ranges_are_bounded() {
+        let ranges = accelerated_ranges(10, 3);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!((ranges[2].start, ranges[2].end), (8, 9));
+
+        assert_eq!(accelerated_ranges(10, 0).len(), 1);
+        assert_eq!(accelerated_ranges(10, usize::MAX).len(), 10);
+
+        let ranges = accelerated_ranges(4_000_000_000, usize::MAX);
+        assert_eq!(ranges.len(), 64);
+        assert_eq!(ranges[63].end, 3_999_999_999);
+    }
+
+    #[test]
+    fn part_size_defaults_to_recommended() {
+        let size = choose_part_size(1_000_000_000, None, 100_000_000, 5_000_000)
+            .unwrap();
+        assert_eq!(size, 100_000_000);
+
+        // 2 TB does not fit in 10,000 recommended-size parts.
+        let size = choose_part_size(
+            2_000_000_000_000, None, 100_000_000, 5_000_000
+        ).unwrap();
+        assert_eq!(size, 200_000_000);
+    }
+
+    #[test]
+    fn part_size_override_is_validated() {
+        let size = choose_part_size(
+            1_000_000_000, Some(5_000_000), 100_000_000, 5_000_000
+        ).unwrap();
+        assert_eq!(size, 5_000_000);
+
+        match choose_part_size(
+            1_000_000_000, Some(4_999_999), 100_000_000, 5_000_000
+        ).unwrap_err() {
+            ValidationError::OutOfBounds(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        match choose_part_size(
+            100_000_000_000, Some(5_000_000), 100_000_000, 5_000_000
+        ).unwrap_err() {
+            ValidationError::OutOfBounds(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn file_id_and_name_from_file() {
+        let file: File = serde_json::from_str(r#"{
+            "accountId": "abcdefg",
+            "action": "upload",
+            "bucketId": "bucket-id",
+            "contentLength": 4,
+            "contentSha1": "81fe8bfe87576c3ecb22426f8e57847382917acf",
+            "contentMd5": null,
+            "contentType": "text/plain",
+            "fileId": "file-id",
+            "fileInfo": {},
+            "fileName": "file-name.txt",
+            "fileRetention": null,
+            "legalHold": null,
+            "serverSideEncryption": null,
+            "uploadTimestamp": 1637248690000
+        }"#).unwrap();
+
+        assert_eq!(FileId::from(&file).as_str(), "file-id");
+        assert_eq!(FileName::from(&file).as_str(), "file-name.txt");
+
+        assert_eq!(
+            serde_json::to_value(FileId::from("some-id")).unwrap(),
+            serde_json::json!("some-id")
+        );
+    }
+
+    #[test]
+    fn file_headers_are_decoded() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Bz-File-Id".into(), "some-id".into());
+        headers.insert("X-Bz-File-Name".into(), "my%20file.txt".into());
+        headers.insert("X-Bz-Content-Sha1".into(), "none".into());
+        headers.insert("X-Bz-Info-author".into(), "J%C3%BCrgen".into());
+
+        let file = FileHeaders::from_headers(&headers).unwrap();
+
+        assert_eq!(file.file_name(), "my file.txt");
+        assert_eq!(file.content_length(), 0);
+        assert_eq!(file.content_sha1(), None);
+        assert_eq!(
+            file.file_info().get("author").map(String::as_str),
+            Some("J\u{fc}rgen")
+        );
+    }
+
+    #[test]
+    fn file_headers_parse_content_range() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Bz-File-Id".into(), "some-id".into());
+        headers.insert("X-Bz-File-Name".into(), "file.txt".into());
+        headers.insert("Content-Length".into(), "100".into());
+        headers.insert("Content-Range".into(), "bytes 100-199/200".into());
+
+        let file = FileHeaders::from_headers(&headers).unwrap();
+        let range = file.content_range().unwrap();
+
+        assert_eq!(file.content_length(), 100);
+        assert_eq!((range.start(), range.end()), (100, 199));
+        assert_eq!(range.total(), Some(200));
+        assert_eq!(range.len(), 100);
+        assert!(range.is_final());
+    }
+
+    #[test]
+    fn content_range_with_unknown_total() {
+        let range = ContentRange::from_header("bytes 0-49/*").unwrap();
+
+        assert_eq!((range.start(), range.end()), (0, 49));
+        assert_eq!(range.total(), None);
+        assert!(! range.is_final());
+    }
+
+    #[test]
+    fn content_range_rejects_bad_values() {
+        for value in [
+            "bytes */200", "bytes 10-5/200", "bytes 0-200/200", "0-9/10",
+            "bytes 0-9", "bytes a-9/10",
+        ] {
+            match ContentRange::from_header(value).unwrap_err() {
+                ValidationError::BadFormat(_) => {},
+                e => panic!("Unexpected error type: {:?}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn file_headers_require_file_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-bz-file-name".into(), "file.txt".into());
+
+        match FileHeaders::from_headers(&headers).unwrap_err() {
+            ValidationError::MissingData(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn if_modified_since_is_http_date() {
+        use chrono::Utc;
+
+        let time = chrono::DateTime::parse_from_rfc3339(
+            "1994-11-06T08:49:37Z"
+        ).unwrap().with_timezone(&Utc);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
 
 
     #[async_std::test]
@@ -4806,4 +10218,138 @@ mod tests {
 
         Ok(())
     }
+
+    pub(super) fn signed_url_test_auth<C>(overrides: DownloadOverrides)
+    -> DownloadAuthorization<C>
+        where C: HttpClient,
+    {
+        DownloadAuthorization {
+            client: C::default(),
+            api_url: "https://api002.backblazeb2.com".into(),
+            download_url: "https://f002.backblazeb2.com".into(),
+            bucket_id: "8d625eb63be2775577c70e1a".into(),
+            file_name_prefix: "test".into(),
+            authorization_token: "some-token".into(),
+            overrides,
+            obtained_at: chrono::Utc::now(),
+            valid_duration: Duration(chrono::Duration::seconds(30)),
+            clock: crate::clock::system(),
+            log_redaction: LogRedaction::default(),
+        }
+    }
+
+    #[test]
+    fn redacted_download_authorization_debug_hides_prefix() {
+        let auth = signed_url_test_auth::<NoClient>(Default::default());
+        assert!(format!("{:?}", auth).contains("\"test\""));
+
+        let auth = auth.with_log_redaction(LogRedaction { file_names: true });
+        assert!(! format!("{:?}", auth).contains("\"test\""));
+    }
+
+    #[test]
+    fn signed_url_includes_overrides() -> anyhow::Result<()> {
+        let auth = signed_url_test_auth::<NoClient>(DownloadOverrides {
+            content_language: Some("en-US".into()),
+            ..Default::default()
+        });
+
+        let url = SignedUrlBuilder::new(&auth, "testing-b2-client", "test file")
+            .content_type(Mime::from("text/plain"))
+            .build()?;
+
+        assert_eq!(url, concat!(
+            "https://f002.backblazeb2.com/file/testing-b2-client/test%20file",
+            "?Authorization=some-token&b2ContentLanguage=en-US",
+            "&b2ContentType=text/plain"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_url_rejects_unauthorized_overrides() {
+        let auth = signed_url_test_auth::<NoClient>(DownloadOverrides {
+            content_language: Some("en-US".into()),
+            ..Default::default()
+        });
+
+        let res = SignedUrlBuilder::new(&auth, "testing-b2-client", "test.txt")
+            .content_language("de-DE")
+            .build();
+
+        match res.unwrap_err() {
+            ValidationError::Incompatible(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        let res = SignedUrlBuilder::new(&auth, "testing-b2-client", "file.txt")
+            .build();
+
+        match res.unwrap_err() {
+            ValidationError::OutOfBounds(_) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+    }
+
+    #[async_std::test]
+    async fn download_rejects_unauthorized_overrides() -> anyhow::Result<()> {
+        let disposition = ContentDisposition::new("inline")?;
+
+        let mut auth = signed_url_test_auth::<NoClient>(DownloadOverrides {
+            content_disposition: Some("attachment".into()),
+            ..Default::default()
+        });
+
+        let req = DownloadFile::builder()
+            .file_name("test-file.txt", "testing-b2-client")
+            .content_disposition(&disposition)?
+            .build()?;
+
+        match download_file(&mut auth, req).await.unwrap_err() {
+            Error::Validation(ValidationError::Incompatible(_)) => {},
+            e => panic!("Unexpected error type: {:?}", e),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn download_authorization_round_trips() -> anyhow::Result<()> {
+        let auth = signed_url_test_auth::<NoClient>(DownloadOverrides {
+            content_language: Some("en-US".into()),
+            ..Default::default()
+        });
+
+        assert!(! format!("{:?}", auth).contains("some-token"));
+        let expiration = auth.expiration();
+
+        let json = serde_json::to_string(&auth)?;
+        let auth: DownloadAuthorization<NoClient> =
+            serde_json::from_str(&json)?;
+
+        assert_eq!(auth.authorization_token(), "some-token");
+        assert_eq!(auth.expiration(), expiration);
+        assert_eq!(auth.bucket_id(), "8d625eb63be2775577c70e1a");
+        assert_eq!(auth.file_name_prefix(), "test");
+        assert_eq!(auth.download_url, "https://f002.backblazeb2.com");
+        assert_eq!(auth.overrides.content_language.as_deref(), Some("en-US"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn download_authorization_expires_by_clock() {
+        use crate::clock::FixedClock;
+
+        let auth = signed_url_test_auth::<NoClient>(DownloadOverrides::default());
+        let clock = FixedClock::new(auth.obtained_at);
+        let auth = auth.with_clock(clock.clone());
+
+        clock.advance(chrono::Duration::seconds(29));
+        assert!(! auth.is_probably_expired());
+
+        clock.advance(chrono::Duration::seconds(1));
+        assert!(auth.is_probably_expired());
+    }
 }
@@ -11,7 +11,6 @@
 //! # Examples
 //!
 //! ```no_run
-//! # fn calculate_sha1(data: &[u8]) -> String { String::default() }
 //! # use anyhow;
 //! use std::env;
 //! use b2_client as b2;
@@ -29,19 +28,27 @@
 //!     "my-bucket-id"
 //! ).await?;
 //!
+//! let data = b"very important information";
+//! let checksum = b2::compute_sha1(data);
+//!
 //! let file = b2::UploadFile::builder()
 //!     .file_name("my-file.txt")?
-//!     .sha1_checksum("61b8d6600ac94d912874f569a9341120f680c9f8")
+//!     .sha1_checksum(&checksum)
 //!     .build()?;
 //!
-//! let data = b"very important information";
-//!
 //! let file_info = b2::upload_file(&mut upload_auth, file, data).await?;
 //!
 //! # Ok(())
 //! # }
 //! ```
 //!
+//! # Builders
+//!
+//! Requests with more than a few parameters are created via builders. Builder
+//! methods that validate their input return a `Result`; methods that cannot
+//! fail return the builder directly, so `?` is only needed where an error is
+//! possible. The final `build` call checks that all required data is present.
+//!
 //! # Differences from the B2 Service API
 //!
 //! * The B2 endpoint `b2_get_upload_part_url` is
@@ -59,16 +66,60 @@ pub mod bucket;
 pub mod file;
 
 pub mod client;
+pub mod clock;
+pub mod content_type;
 pub mod error;
+pub mod metrics;
+pub mod retry;
+
+#[cfg(any(
+    feature = "with_surf",
+    feature = "with_hyper",
+    feature = "with_isahc",
+    all(feature = "with_fetch", target_arch = "wasm32"),
+))]
+pub mod default_backend;
 
 mod types;
 mod validate;
 
+/// The most commonly used types and functions.
+///
+/// ```
+/// use b2_client::prelude::*;
+/// ```
+///
+/// Less common items, and the HTTP client implementations in [client], must
+/// still be imported from their modules.
 pub mod prelude {
     #![allow(unused_imports)]
 
+    pub use super::{
+        account::{
+            authorize_account,
+            authorize_from_env,
+            Authorization,
+            Capability,
+        },
+        bucket::{list_buckets, Bucket, ListBuckets},
+        client::HttpClient,
+        error::Error,
+        file::{
+            compute_sha1,
+            delete_file_version,
+            download_file,
+            get_upload_authorization,
+            list_file_names,
+            upload_file,
+            BypassGovernance,
+            DownloadFile,
+            File,
+            ListFileNames,
+            UploadFile,
+        },
+    };
+
     pub(crate) use super::{
-        account::{Authorization, Capability},
         types::{B2Result, Duration},
         require_capability,
     };
@@ -90,6 +141,27 @@ pub use file::*;
 
 pub use client::HttpClient;
 pub use error::Error;
+pub use types::{ContentDisposition, IntoDuration};
+
+/// A `Result` whose error is a b2-client [Error].
+///
+/// `E` is the error type of the [HttpClient] implementation, so functions
+/// wrapping b2-client calls can return `b2_client::Result<T, E>` rather than
+/// `Result<T, Error<E>>`:
+///
+/// ```no_run
+/// use b2_client as b2;
+///
+/// # #[cfg(feature = "with_surf")]
+/// async fn bucket_names(auth: &mut b2::Authorization<b2::client::SurfClient>)
+/// -> b2::Result<Vec<String>, surf::Error> {
+///     let buckets = b2::list_buckets(auth, b2::ListBuckets::builder().build())
+///         .await?;
+///
+///     Ok(buckets.iter().map(|b| b.name().to_owned()).collect())
+/// }
+/// ```
+pub type Result<T, E> = std::result::Result<T, Error<E>>;
 
 #[cfg(all(test, feature = "with_surf"))]
 pub(crate) mod test_utils {
@@ -102,6 +174,20 @@ pub(crate) mod test_utils {
     use surf::http::Method;
 
 
+    /// A path in the temporary directory that is unique to this test run.
+    pub fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("b2-client-{}-{}", std::process::id(), name))
+    }
+
+    /// A function to modify a recorded request; see [create_test_client].
+    pub type RequestModifier =
+        Box<dyn Fn(&mut VcrRequest) + Send + Sync + 'static>;
+
+    /// A function to modify a recorded response; see [create_test_client].
+    pub type ResponseModifier =
+        Box<dyn Fn(&mut VcrResponse) + Send + Sync + 'static>;
+
     /// Create a SurfClient with the surf-vcr middleware.
     ///
     /// We remove the following data from the recorded sessions:
@@ -126,9 +212,23 @@ pub(crate) mod test_utils {
     /// modified.
     pub async fn create_test_client(
         mode: VcrMode, cassette: &'static str,
-        req_mod: Option<Box<dyn Fn(&mut VcrRequest) + Send + Sync + 'static>>,
-        res_mod: Option<Box<dyn Fn(&mut VcrResponse) + Send + Sync + 'static>>,
+        req_mod: Option<RequestModifier>,
+        res_mod: Option<ResponseModifier>,
     ) -> std::result::Result<SurfClient, VcrError> {
+        let vcr = create_test_vcr(mode, cassette, req_mod, res_mod).await?;
+
+        Ok(SurfClient::default().with_middleware(vcr))
+    }
+
+    /// Create the surf-vcr middleware used by [create_test_client].
+    ///
+    /// Tests that need to alter replayed responses can add their own
+    /// middleware to a [SurfClient] before this one.
+    pub async fn create_test_vcr(
+        mode: VcrMode, cassette: &'static str,
+        req_mod: Option<RequestModifier>,
+        res_mod: Option<ResponseModifier>,
+    ) -> std::result::Result<VcrMiddleware, VcrError> {
         #![allow(clippy::option_map_unit_fn)]
 
         let vcr = VcrMiddleware::new(mode, cassette).await.unwrap()
@@ -222,13 +322,7 @@ pub(crate) mod test_utils {
                 }
             });
 
-        let surf = surf::Client::new()
-            .with(vcr);
-
-        let client = SurfClient::default()
-            .with_client(surf);
-
-        Ok(client)
+        Ok(vcr)
     }
 
     fn hide_response_account_id(mut json: serde_json::Value)
@@ -250,11 +344,146 @@ pub(crate) mod test_utils {
         json
     }
 
+    /// A replayed response that a [RewriteResponses] function may change.
+    pub struct Reply {
+        /// The number of earlier requests to the same path.
+        pub call: usize,
+        pub status: u16,
+        /// Headers to add to the response.
+        pub headers: Vec<(&'static str, String)>,
+        pub body: serde_json::Value,
+    }
+
+    impl Reply {
+        /// Replace the response with a B2 error.
+        pub fn error(&mut self, status: u16, code: &str, message: &str) {
+            self.status = status;
+            self.body = serde_json::json!({
+                "status": status,
+                "code": code,
+                "message": message,
+            });
+        }
+    }
+
+    /// surf middleware that lets a test change the replayed responses to its
+    /// requests.
+    ///
+    /// The function is called with the path of each request and its response;
+    /// responses whose body is not JSON are passed through unchanged. Add this
+    /// to a [SurfClient] before the middleware from [create_test_vcr].
+    pub struct RewriteResponses<F> {
+        rewrite: F,
+        calls: std::sync::Mutex<std::collections::HashMap<String, usize>>,
+    }
+
+    impl<F> RewriteResponses<F>
+        where F: Fn(&str, &mut Reply) + Send + Sync + 'static,
+    {
+        pub fn new(rewrite: F) -> Self {
+            Self { rewrite, calls: Default::default() }
+        }
+    }
+
+    #[surf::utils::async_trait]
+    impl<F> surf::middleware::Middleware for RewriteResponses<F>
+        where F: Fn(&str, &mut Reply) + Send + Sync + 'static,
+    {
+        async fn handle(
+            &self,
+            req: surf::Request,
+            client: surf::Client,
+            next: surf::middleware::Next<'_>,
+        ) -> surf::Result<surf::Response> {
+            let path = req.url().path().to_owned();
+            let mut res = next.run(req, client).await?;
+
+            let body = res.body_bytes().await?;
+
+            let json = match serde_json::from_slice(&body) {
+                Ok(json) => json,
+                Err(_) => {
+                    res.set_body(body);
+                    return Ok(res);
+                },
+            };
+
+            let call = {
+                let mut calls = self.calls.lock().unwrap();
+                let call = calls.entry(path.clone()).or_default();
+                *call += 1;
+                *call - 1
+            };
+
+            let mut reply = Reply {
+                call,
+                status: res.status().into(),
+                headers: vec![],
+                body: json,
+            };
+
+            (self.rewrite)(&path, &mut reply);
+
+            let status = surf::StatusCode::try_from(reply.status)?;
+            AsMut::<surf::http::Response>::as_mut(&mut res).set_status(status);
+
+            for (name, value) in reply.headers {
+                res.insert_header(name, value);
+            }
+
+            res.set_body(reply.body);
+
+            Ok(res)
+        }
+    }
+
+    /// surf middleware that waits before sending requests.
+    pub struct Delay {
+        delay: std::time::Duration,
+        only_first: bool,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Delay {
+        /// Wait before sending each request.
+        pub fn all(delay: std::time::Duration) -> Self {
+            Self { delay, only_first: false, calls: Default::default() }
+        }
+
+        /// Wait before sending the first request only.
+        pub fn first(delay: std::time::Duration) -> Self {
+            Self { delay, only_first: true, calls: Default::default() }
+        }
+    }
+
+    #[surf::utils::async_trait]
+    impl surf::middleware::Middleware for Delay {
+        async fn handle(
+            &self,
+            req: surf::Request,
+            client: surf::Client,
+            next: surf::middleware::Next<'_>,
+        ) -> surf::Result<surf::Response> {
+            use std::sync::atomic::Ordering;
+
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+
+            if call == 0 || ! self.only_first {
+                async_std::task::sleep(self.delay).await;
+            }
+
+            next.run(req, client).await
+        }
+    }
+
     /// Create an [Authorization] with the specified capabilities.
     ///
     /// If the `B2_CLIENT_TEST_KEY` and `B2_CLIENT_TEST_KEY_ID` environment
     /// variables are set, their values are used to make an authorization
-    /// request against the B2 API.
+    /// request against the B2 API. The key must grant each of the specified
+    /// capabilities; any others it grants are removed from the
+    /// [Authorization], so that tests exercise the minimal set of capabilities
+    /// they specify.
     ///
     /// Otherwise, a fake authorization is created with values usable for
     /// pre-recorded sessions in unit tests.
@@ -278,23 +507,36 @@ pub(crate) mod test_utils {
             let auth = authorize_account(client, &key, &key_id.unwrap())
                 .await.unwrap();
 
-            for cap in capabilities {
+            for &cap in capabilities.iter() {
                 assert!(auth.capabilities().has_capability(cap));
             }
 
-            auth
+            auth.with_only_capabilities(capabilities)
         } else {
-            Authorization::new(
+            create_auth_with(
                 client,
-                "some-account-id".into(),
-                "some-key-id".into(),
-                Capabilities::new(capabilities, None, None, None),
-                "https://api002.backblazeb2.com".into(),
-                "https://f002.backblazeb2.com".into(),
-                100000000,
-                5000000,
-                "https://s3.us-west-002.backblazeb2.com".into(),
+                Capabilities::new(capabilities, None, None, None)
             )
         }
     }
+
+    /// Create a fake [Authorization] with the specified capabilities, usable
+    /// for pre-recorded sessions.
+    ///
+    /// Unlike [create_test_auth], this never authorizes against the B2 API, so
+    /// tests can describe keys with restrictions.
+    pub fn create_auth_with(client: SurfClient, capabilities: Capabilities)
+    -> Authorization<SurfClient> {
+        Authorization::new(
+            client,
+            "some-account-id".into(),
+            "some-key-id".into(),
+            capabilities,
+            "https://api002.backblazeb2.com".into(),
+            "https://f002.backblazeb2.com".into(),
+            100000000,
+            5000000,
+            "https://s3.us-west-002.backblazeb2.com".into(),
+        )
+    }
 }
@@ -0,0 +1,616 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+   License, v. 2.0. If a copy of the MPL was not distributed with this
+   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Numeric telemetry for the requests sent to B2.
+//!
+//! Implement [Metrics] to receive a callback before and after every request,
+//! and before each retry of a failed request, then wrap the HTTP client in a
+//! [MeteredClient] before authorizing:
+//!
+//! ```no_run
+//! # #[cfg(feature = "with_surf")]
+//! # async fn f() -> anyhow::Result<()> {
+//! use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+//! use b2_client::{
+//!     client::SurfClient,
+//!     metrics::{MeteredClient, Metrics, RequestEnd},
+//! };
+//!
+//! #[derive(Default)]
+//! struct ByteCounter(AtomicU64);
+//!
+//! impl Metrics for ByteCounter {
+//!     fn on_request_end(&self, req: &RequestEnd<'_>) {
+//!         self.0.fetch_add(req.bytes_received, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! let counter = Arc::new(ByteCounter::default());
+//! let client = MeteredClient::new(SurfClient::default(), counter.clone());
+//!
+//! let auth = b2_client::authorize_from_env(client).await?;
+//! # Ok(()) }
+//! ```
+//!
+//! The [UploadAuthorization](crate::file::UploadAuthorization)s and
+//! [DownloadAuthorization](crate::file::DownloadAuthorization)s obtained
+//! via the [Authorization](crate::account::Authorization) use clones of its
+//! client, so their requests are reported to the same [Metrics].
+//!
+//! Clients that are not wrapped in a `MeteredClient` do no extra work.
+//!
+//! The reported events contain no authorization tokens or request and
+//! response bodies.
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{
+    client::{HeaderMap, HttpClient, RequestKind},
+    error::{B2Error, Error, ValidationError},
+};
+
+
+/// Callbacks for the requests sent by a [MeteredClient].
+///
+/// Every method does nothing by default. The callbacks are called from the
+/// task sending the request, so they should return quickly.
+pub trait Metrics: Send + Sync {
+    /// Called before a request is sent.
+    fn on_request_start(&self, req: &RequestStart<'_>) {
+        let _ = req;
+    }
+
+    /// Called after a request's response has been received, or the request
+    /// failed.
+    fn on_request_end(&self, req: &RequestEnd<'_>) {
+        let _ = req;
+    }
+
+    /// Called when a failed request will be retried, before waiting for the
+    /// [delay](RetryAttempt::delay).
+    fn on_retry(&self, retry: &RetryAttempt<'_>) {
+        let _ = retry;
+    }
+}
+
+/// A request that is about to be sent.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestStart<'a> {
+    /// The HTTP method.
+    pub method: &'static str,
+    /// The URL of the request.
+    ///
+    /// The URL of a download by name includes the file name.
+    pub url: &'a str,
+    /// Whether the request is an API call or a file transfer.
+    pub kind: RequestKind,
+    /// The size of the request body, if known.
+    pub bytes_sent: Option<u64>,
+}
+
+/// A request that has completed.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestEnd<'a> {
+    /// The HTTP method.
+    pub method: &'static str,
+    /// The URL of the request.
+    ///
+    /// The URL of a download by name includes the file name.
+    pub url: &'a str,
+    /// Whether the request is an API call or a file transfer.
+    pub kind: RequestKind,
+    /// The size of the request body, if known.
+    pub bytes_sent: Option<u64>,
+    /// The size of the response body.
+    pub bytes_received: u64,
+    /// The time from sending the request to receiving the full response.
+    pub duration: Duration,
+    /// The result of the request.
+    pub outcome: RequestOutcome,
+}
+
+/// A failed request that will be retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryAttempt<'a> {
+    /// The B2 API endpoint of the request, e.g. `b2_authorize_account`.
+    pub endpoint: &'static str,
+    /// The number of the attempt that failed, counting from one.
+    pub attempt: u32,
+    /// The result of the failed attempt.
+    pub outcome: RequestOutcome,
+    /// The error code returned by B2, if any; e.g. `service_unavailable`.
+    pub code: Option<&'a str>,
+    /// The delay before the next attempt.
+    pub delay: Duration,
+}
+
+impl<'a> RetryAttempt<'a> {
+    /// Describe the failed `attempt` of a request to `endpoint`.
+    pub(crate) fn new<E>(
+        endpoint: &'static str,
+        attempt: u32,
+        error: &'a Error<E>,
+        delay: Duration,
+    ) -> Self
+        where E: std::fmt::Debug + std::fmt::Display,
+    {
+        let (outcome, code) = match error {
+            Error::B2(e) =>
+                (RequestOutcome::B2Error(e.http_status()), Some(e.code_str())),
+            _ => (RequestOutcome::ClientError, None),
+        };
+
+        Self { endpoint, attempt, outcome, code, delay }
+    }
+}
+
+/// The result of a request reported to [Metrics].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RequestOutcome {
+    /// B2 returned a successful response.
+    Success,
+    /// B2 returned an error response with the given HTTP status.
+    B2Error(u16),
+    /// The HTTP client failed to send the request or receive the response.
+    ClientError,
+}
+
+impl RequestOutcome {
+    /// Determine the outcome from the HTTP status if the [HttpClient] reported
+    /// it, or else from the response body.
+    fn of<E>(res: &Result<&[u8], E>, status: Option<u16>) -> Self {
+        match (res, status) {
+            (Err(_), _) => Self::ClientError,
+            (Ok(_), Some(status)) if status >= 400 => Self::B2Error(status),
+            (Ok(_), Some(_)) => Self::Success,
+            (Ok(body), None) if looks_like_b2_error(body) => {
+                match serde_json::from_slice::<B2Error>(body) {
+                    Ok(e) => Self::B2Error(e.http_status()),
+                    Err(_) => Self::Success,
+                }
+            },
+            (Ok(_), None) => Self::Success,
+        }
+    }
+}
+
+/// Check whether a response body begins like a B2 error object, so that only
+/// likely errors are parsed a second time.
+///
+/// This is only used when the [HttpClient] does not report the HTTP status,
+/// as [send](HttpClient::send) and
+/// [send_keep_headers](HttpClient::send_keep_headers) do not; B2 error
+/// responses are JSON objects whose first field is `code`, which no
+/// successful API response begins with.
+fn looks_like_b2_error(body: &[u8]) -> bool {
+    match body.split_first() {
+        Some((b'{', rest)) => rest.iter()
+            .position(|b| ! b.is_ascii_whitespace())
+            .map(|i| rest[i..].starts_with(b"\"code\""))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// Measures the duration of a request.
+///
+/// [std::time::Instant] is monotonic but panics on `wasm32-unknown-unknown`,
+/// so the system time is used there instead.
+struct Stopwatch(
+    #[cfg(not(target_arch = "wasm32"))]
+    std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    chrono::DateTime<chrono::Utc>,
+);
+
+impl Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start() -> Self {
+        Self(std::time::Instant::now())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn start() -> Self {
+        Self(chrono::Utc::now())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn elapsed(&self) -> Duration {
+        (chrono::Utc::now() - self.0).to_std().unwrap_or_default()
+    }
+}
+
+/// An [HttpClient] that reports each request to a [Metrics] implementation.
+///
+/// A default `MeteredClient` has no `Metrics` and reports nothing.
+#[derive(Clone, Default)]
+pub struct MeteredClient<C>
+    where C: HttpClient,
+{
+    client: C,
+    metrics: Option<Arc<dyn Metrics>>,
+    method: &'static str,
+    url: String,
+    kind: RequestKind,
+    bytes_sent: Option<u64>,
+}
+
+impl<C> std::fmt::Debug for MeteredClient<C>
+    where C: HttpClient + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MeteredClient")
+            .field("client", &self.client)
+            .field("metrics", &self.metrics.as_ref().map(|_| "<metrics>"))
+            .finish()
+    }
+}
+
+impl<C> MeteredClient<C>
+    where C: HttpClient,
+{
+    /// Report the requests sent by `client` to `metrics`.
+    pub fn new(client: C, metrics: Arc<dyn Metrics>) -> Self {
+        Self {
+            client,
+            metrics: Some(metrics),
+            method: "",
+            url: String::new(),
+            kind: RequestKind::default(),
+            bytes_sent: None,
+        }
+    }
+
+    /// The wrapped HTTP client.
+    pub fn client(&self) -> &C { &self.client }
+
+    fn start(&mut self, method: &'static str, url: &str) {
+        if self.metrics.is_some() {
+            self.method = method;
+            self.url = url.to_owned();
+            self.kind = RequestKind::default();
+            self.bytes_sent = Some(0);
+        }
+    }
+
+    fn request_start(&self) -> RequestStart<'_> {
+        RequestStart {
+            method: self.method,
+            url: &self.url,
+            kind: self.kind,
+            bytes_sent: self.bytes_sent,
+        }
+    }
+
+    fn request_end(
+        &self,
+        duration: Duration,
+        res: Result<&[u8], ()>,
+        status: Option<u16>,
+    ) -> RequestEnd<'_> {
+        RequestEnd {
+            method: self.method,
+            url: &self.url,
+            kind: self.kind,
+            bytes_sent: self.bytes_sent,
+            bytes_received: res.map(|b| b.len() as u64).unwrap_or(0),
+            duration,
+            outcome: RequestOutcome::of(&res, status),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl<C> HttpClient for MeteredClient<C>
+    where C: HttpClient + Send,
+{
+    type Error = C::Error;
+
+    fn get(&mut self, url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> {
+        self.start("GET", url.as_ref());
+        self.client.get(url)?;
+        Ok(self)
+    }
+
+    fn head(&mut self, url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> {
+        self.start("HEAD", url.as_ref());
+        self.client.head(url)?;
+        Ok(self)
+    }
+
+    fn post(&mut self, url: impl AsRef<str>)
+    -> Result<&mut Self, ValidationError> {
+        self.start("POST", url.as_ref());
+        self.client.post(url)?;
+        Ok(self)
+    }
+
+    fn with_header<S: AsRef<str>>(&mut self, name: S, value: S)
+    -> Result<&mut Self, ValidationError> {
+        self.client.with_header(name, value)?;
+        Ok(self)
+    }
+
+    fn with_body(&mut self, data: impl Into<Vec<u8>>) -> &mut Self {
+        let data = data.into();
+
+        if self.metrics.is_some() {
+            self.bytes_sent = Some(data.len() as u64);
+        }
+
+        self.client.with_body(data);
+        self
+    }
+
+    fn with_body_json(&mut self, body: serde_json::Value) -> &mut Self {
+        if self.metrics.is_some() {
+            self.bytes_sent = Some(body.to_string().len() as u64);
+        }
+
+        self.client.with_body_json(body);
+        self
+    }
+
+    fn read_body_from_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        let path = path.into();
+
+        if self.metrics.is_some() {
+            self.bytes_sent = std::fs::metadata(&path).ok().map(|m| m.len());
+        }
+
+        self.client.read_body_from_file(path);
+        self
+    }
+
+    fn user_agent(&mut self, user_agent_string: impl Into<String>)
+    -> Result<&mut Self, ValidationError> {
+        self.client.user_agent(user_agent_string)?;
+        Ok(self)
+    }
+
+    fn user_agent_string(&self) -> Option<&str> {
+        self.client.user_agent_string()
+    }
+
+    fn with_response_limit(&mut self, limit: u64) -> &mut Self {
+        self.client.with_response_limit(limit);
+        self
+    }
+
+    fn with_response_buffer(&mut self, buf: Vec<u8>) -> &mut Self {
+        self.client.with_response_buffer(buf);
+        self
+    }
+
+    fn with_request_kind(&mut self, kind: RequestKind) -> &mut Self {
+        self.kind = kind;
+        self.client.with_request_kind(kind);
+        self
+    }
+
+    fn with_expect_continue(&mut self) -> &mut Self {
+        self.client.with_expect_continue();
+        self
+    }
+
+    fn on_retry(&self, retry: &RetryAttempt<'_>) {
+        if let Some(metrics) = &self.metrics {
+            metrics.on_retry(retry);
+        }
+
+        self.client.on_retry(retry);
+    }
+
+    async fn send(&mut self) -> Result<Vec<u8>, Self::Error> {
+        let metrics = match self.metrics.clone() {
+            Some(metrics) => metrics,
+            None => return self.client.send().await,
+        };
+
+        metrics.on_request_start(&self.request_start());
+        let start = Stopwatch::start();
+
+        let res = self.client.send().await;
+
+        let duration = start.elapsed();
+        let body = res.as_ref().map(|b| b.as_slice()).map_err(|_| ());
+        metrics.on_request_end(&self.request_end(duration, body, None));
+
+        res
+    }
+
+    async fn send_keep_headers(&mut self)
+    -> Result<(Vec<u8>, HeaderMap), Self::Error> {
+        let metrics = match self.metrics.clone() {
+            Some(metrics) => metrics,
+            None => return self.client.send_keep_headers().await,
+        };
+
+        metrics.on_request_start(&self.request_start());
+        let start = Stopwatch::start();
+
+        let res = self.client.send_keep_headers().await;
+
+        let duration = start.elapsed();
+        let body = res.as_ref().map(|(b, _)| b.as_slice()).map_err(|_| ());
+        metrics.on_request_end(&self.request_end(duration, body, None));
+
+        res
+    }
+
+    async fn send_keep_status(&mut self)
+    -> Result<(Option<u16>, Vec<u8>, HeaderMap), Self::Error> {
+        let metrics = match self.metrics.clone() {
+            Some(metrics) => metrics,
+            None => return self.client.send_keep_status().await,
+        };
+
+        metrics.on_request_start(&self.request_start());
+        let start = Stopwatch::start();
+
+        let res = self.client.send_keep_status().await;
+
+        let duration = start.elapsed();
+        let status = res.as_ref().ok().and_then(|(status, _, _)| *status);
+        let body = res.as_ref().map(|(_, b, _)| b.as_slice()).map_err(|_| ());
+        metrics.on_request_end(&self.request_end(duration, body, status));
+
+        res
+    }
+}
+
+#[cfg(all(test, feature = "with_surf"))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::{account::authorize_account, test_utils::create_test_client};
+    use surf_vcr::VcrMode;
+
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(&'static str, u64, RequestOutcome)>>);
+
+    impl Metrics for Recorder {
+        fn on_request_end(&self, req: &RequestEnd<'_>) {
+            self.0.lock().unwrap()
+                .push((req.method, req.bytes_received, req.outcome));
+        }
+    }
+
+    #[async_std::test]
+    async fn metered_client_reports_requests() -> anyhow::Result<()> {
+        let recorder = Arc::new(Recorder::default());
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = MeteredClient::new(client, recorder.clone());
+        authorize_account(client, "B2_KEY_ID", "B2_AUTH_KEY").await?;
+
+        let client = create_test_client(
+            VcrMode::Replay,
+            "test_sessions/bad_auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = MeteredClient::new(client, recorder.clone());
+        assert!(authorize_account(client, "B2_KEY_ID", "wrong-key").await
+            .is_err());
+
+        let requests = recorder.0.lock().unwrap();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].0, "GET");
+        assert!(requests[0].1 > 0);
+        assert_eq!(requests[0].2, RequestOutcome::Success);
+        assert_eq!(requests[1].2, RequestOutcome::B2Error(401));
+
+        Ok(())
+    }
+
+    #[test]
+    fn outcome_prefers_reported_status() {
+        let body = br#"{"code": "not_found", "status": 404, "message": ""}"#;
+        let res = Ok::<_, ()>(&body[..]);
+
+        // A downloaded file may look like a B2 error.
+        assert_eq!(
+            RequestOutcome::of(&res, Some(200)),
+            RequestOutcome::Success
+        );
+        assert_eq!(
+            RequestOutcome::of(&res, Some(503)),
+            RequestOutcome::B2Error(503)
+        );
+        assert_eq!(
+            RequestOutcome::of(&res, None),
+            RequestOutcome::B2Error(404)
+        );
+        assert_eq!(
+            RequestOutcome::of(&Err::<&[u8], _>(()), Some(200)),
+            RequestOutcome::ClientError
+        );
+    }
+
+    #[async_std::test]
+    async fn metered_client_reports_retries() -> anyhow::Result<()> {
+        use crate::{
+            account::authorize_account_with_retry,
+            client::SurfClient,
+            retry::{JitterMode, RetryPolicy},
+            test_utils::{create_test_vcr, RewriteResponses},
+        };
+
+        /// The fields of a [RetryAttempt], with the code owned.
+        #[derive(Default)]
+        #[allow(clippy::type_complexity)]
+        struct Retries(Mutex<Vec<(
+            &'static str, u32, RequestOutcome, Option<String>, Duration
+        )>>);
+
+        impl Metrics for Retries {
+            fn on_retry(&self, retry: &RetryAttempt<'_>) {
+                self.0.lock().unwrap().push((
+                    retry.endpoint,
+                    retry.attempt,
+                    retry.outcome,
+                    retry.code.map(String::from),
+                    retry.delay,
+                ));
+            }
+        }
+
+        let policy = RetryPolicy {
+            jitter: JitterMode::None,
+            ..Default::default()
+        };
+
+        let vcr = create_test_vcr(
+            VcrMode::Replay,
+            "test_sessions/auth_account.yaml",
+            None, None
+        ).await?;
+
+        let client = SurfClient::default()
+            .with_middleware(RewriteResponses::new(|_, res| {
+                if res.call == 0 {
+                    res.error(503, "service_unavailable",
+                        "Service temporarily unavailable");
+                }
+            }))
+            .with_middleware(vcr);
+
+        let retries = Arc::new(Retries::default());
+        let client = MeteredClient::new(client, retries.clone());
+
+        authorize_account_with_retry(
+            client, "B2_KEY_ID", "B2_AUTH_KEY", &policy, |_| async {}
+        ).await?;
+
+        let retries = retries.0.lock().unwrap();
+
+        assert_eq!(retries.len(), 1);
+
+        let (endpoint, attempt, outcome, code, delay) = &retries[0];
+        assert_eq!(*endpoint, "b2_authorize_account");
+        assert_eq!(*attempt, 1);
+        assert_eq!(*outcome, RequestOutcome::B2Error(503));
+        assert_eq!(code.as_deref(), Some("service_unavailable"));
+        assert_eq!(*delay, Duration::from_secs(1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+   License, v. 2.0. If a copy of the MPL was not distributed with this
+   file, You can obtain one at http://mozilla.org/MPL/2.0/.
+*/
+
+//! Exponential backoff for retrying failed requests.
+//!
+//! b2-client does not retry requests itself; a [RetryPolicy] calculates how
+//! long to wait between attempts so that you can retry with the async runtime
+//! of your choice. Use
+//! [B2Error::is_retriable](crate::error::B2Error::is_retriable) to decide
+//! whether a failed request should be retried at all.
+//!
+//! See <https://www.backblaze.com/b2/docs/integration_checklist.html> for the
+//! B2 recommendations on retrying requests, and
+//! <https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/>
+//! for a description of the jitter modes.
+//!
+//! # Sleeping
+//!
+//! b2-client does not depend on an async runtime, so functions that wait
+//! between requests, such as
+//! [authorize_account_with_retry](crate::account::authorize_account_with_retry)
+//! and [wait_for_bucket](crate::bucket::wait_for_bucket), take a `sleep`
+//! function instead. It must return a future that completes after the given
+//! duration; e.g., `async_std::task::sleep` or `tokio::time::sleep`.
+
+use std::time::Duration;
+
+
+/// The randomization applied to each backoff delay.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JitterMode {
+    /// Use the exponential delay without randomization.
+    ///
+    /// The delays are deterministic, which is useful for tests.
+    None,
+    /// Choose a random delay between zero and the exponential delay.
+    Full,
+    /// Choose a random delay between half of the exponential delay and the
+    /// full exponential delay.
+    Equal,
+}
+
+/// A policy for retrying failed requests with exponential backoff.
+///
+/// The delay before retry `n` (counting from zero) is
+/// `min(max_delay, base_delay * 2^n)`, randomized according to the
+/// [JitterMode].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first request.
+    pub max_attempts: u32,
+    /// The delay before the first retry, prior to applying jitter.
+    pub base_delay: Duration,
+    /// The maximum delay between attempts, prior to applying jitter.
+    pub max_delay: Duration,
+    /// The randomization to apply to each delay.
+    pub jitter: JitterMode,
+}
+
+impl Default for RetryPolicy {
+    /// Create a policy of five attempts with full jitter, starting at one
+    /// second and delaying no more than 64 seconds.
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(64),
+            jitter: JitterMode::Full,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Calculate the delay before the given retry.
+    ///
+    /// `retry` counts from zero for the first retry. `random` must be in the
+    /// range `[0, 1)`; it is ignored if the policy uses [JitterMode::None].
+    /// Values outside the range are clamped to it, and NaN and infinite values
+    /// are treated as zero.
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let delay = 2u32.checked_pow(retry)
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map(|delay| delay.min(self.max_delay))
+            .unwrap_or(self.max_delay);
+
+        let random = if random.is_finite() {
+            random.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        match self.jitter {
+            JitterMode::None => delay,
+            JitterMode::Full => delay.mul_f64(random),
+            JitterMode::Equal => delay / 2 + (delay / 2).mul_f64(random),
+        }
+    }
+
+    /// Calculate the delay before each retry, using a random number generator
+    /// seeded with `seed`.
+    ///
+    /// The iterator yields `max_attempts - 1` delays. The same seed always
+    /// produces the same delays.
+    pub fn delays(&self, seed: u64) -> impl Iterator<Item = Duration> + '_ {
+        let mut rng = SplitMix64(seed);
+
+        (0..self.max_attempts.saturating_sub(1))
+            .map(move |retry| self.delay(retry, rng.next_f64()))
+    }
+}
+
+/// The SplitMix64 generator; see <https://prng.di.unimi.it/splitmix64.c>.
+///
+/// This is not suitable for cryptography, but is more than sufficient to
+/// spread out retries.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Generate a number in the range `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn policy(jitter: JitterMode) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 6,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter,
+        }
+    }
+
+    fn millis(delays: impl Iterator<Item = Duration>) -> Vec<u128> {
+        delays.map(|d| d.as_millis()).collect()
+    }
+
+    #[test]
+    fn no_jitter_delays_are_deterministic() {
+        let policy = policy(JitterMode::None);
+
+        assert_eq!(millis(policy.delays(0)), [100, 200, 400, 800, 1000]);
+        assert_eq!(millis(policy.delays(1)), [100, 200, 400, 800, 1000]);
+    }
+
+    #[test]
+    fn full_jitter_delays() {
+        let policy = policy(JitterMode::Full);
+
+        assert_eq!(policy.delay(2, 0.0), Duration::ZERO);
+        assert_eq!(policy.delay(2, 0.5), Duration::from_millis(200));
+
+        assert_eq!(millis(policy.delays(42)), [74, 31, 111, 275, 38]);
+        assert_eq!(millis(policy.delays(42)), millis(policy.delays(42)));
+    }
+
+    #[test]
+    fn equal_jitter_delays() {
+        let policy = policy(JitterMode::Equal);
+
+        assert_eq!(policy.delay(2, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.delay(2, 0.5), Duration::from_millis(300));
+
+        assert_eq!(millis(policy.delays(42)), [87, 115, 255, 537, 519]);
+    }
+
+    #[test]
+    fn non_finite_random_values_are_zero() {
+        for jitter in [JitterMode::Full, JitterMode::Equal] {
+            let policy = policy(jitter);
+
+            for random in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+                assert_eq!(policy.delay(2, random), policy.delay(2, 0.0));
+            }
+        }
+
+        assert_eq!(policy(JitterMode::Full).delay(2, 7.0),
+            Duration::from_millis(400));
+    }
+
+    #[test]
+    fn delay_does_not_overflow() {
+        let policy = policy(JitterMode::None);
+
+        assert_eq!(policy.delay(64, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(u32::MAX, 0.0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn single_attempt_has_no_delays() {
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            ..policy(JitterMode::Full)
+        };
+
+        assert_eq!(policy.delays(7).count(), 0);
+    }
+}
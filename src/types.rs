@@ -6,7 +6,10 @@
 //! Collection of internal, general-purpose types used throughout the crate.
 
 use std::fmt;
-use super::error::{B2Error, Error};
+use super::{
+    client::HeaderMap,
+    error::{B2Error, Error, ValidationError},
+};
 
 use percent_encoding::{AsciiSet, CONTROLS};
 use serde::{Serialize, Deserialize};
@@ -39,6 +42,25 @@ impl<T, E> From<B2Result<T>> for std::result::Result<T, Error<E>>
     }
 }
 
+impl<'de, T> B2Result<T>
+    where T: Deserialize<'de>,
+{
+    /// Deserialize a response from the B2 API.
+    ///
+    /// An error response's `Retry-After` header is kept with its [B2Error].
+    pub(crate) fn from_response(res: &'de (Vec<u8>, HeaderMap))
+    -> Result<Self, serde_json::Error> {
+        let (body, headers) = res;
+        let mut res: Self = serde_json::from_slice(body)?;
+
+        if let Self::Err(e) = &mut res {
+            e.set_retry_after(headers);
+        }
+
+        Ok(res)
+    }
+}
+
 impl<T> B2Result<T> {
     pub fn map<U, F>(self, op: F) -> B2Result<U>
         where F: FnOnce(T) -> U,
@@ -54,9 +76,24 @@ impl<T> B2Result<T> {
 ///
 /// The grammar is specified in RFC 6266, except parameter names that contain an
 /// '*' are not allowed.
-// TODO: Implement; parse/validate.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ContentDisposition(pub(crate) String);
 
+impl ContentDisposition {
+    /// Create a Content-Disposition value, such as
+    /// `attachment; filename="report.pdf"`.
+    ///
+    /// Returns [ValidationError::BadFormat] if the value is not valid.
+    pub fn new(value: impl Into<String>) -> Result<Self, ValidationError> {
+        let value = value.into();
+        crate::validate::validate_content_disposition(&value, false)?;
+        Ok(Self(value))
+    }
+
+    /// The Content-Disposition value.
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) struct Duration(pub(crate) chrono::Duration);
 
@@ -76,6 +113,34 @@ impl From<Duration> for chrono::Duration {
     fn from(d: Duration) -> Self { d.0 }
 }
 
+/// A duration that can be converted to a [chrono::Duration].
+///
+/// This is implemented for [chrono::Duration] and [std::time::Duration], so
+/// functions that take an `impl IntoDuration` accept either.
+pub trait IntoDuration {
+    /// Convert the value to a [chrono::Duration].
+    ///
+    /// Returns [ValidationError::OutOfBounds] if the value cannot be
+    /// represented as a `chrono::Duration`.
+    fn into_duration(self) -> Result<chrono::Duration, ValidationError>;
+}
+
+impl IntoDuration for chrono::Duration {
+    fn into_duration(self) -> Result<chrono::Duration, ValidationError> {
+        Ok(self)
+    }
+}
+
+impl IntoDuration for std::time::Duration {
+    fn into_duration(self) -> Result<chrono::Duration, ValidationError> {
+        chrono::Duration::from_std(self).map_err(|_|
+            ValidationError::OutOfBounds(format!(
+                "Duration is too large: {} seconds", self.as_secs()
+            ))
+        )
+    }
+}
+
 impl Serialize for Duration {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where S: serde::Serializer,
@@ -84,6 +149,32 @@ impl Serialize for Duration {
     }
 }
 
+/// Serialize a [Duration] as whole seconds, for B2's `validDurationInSeconds`
+/// fields.
+///
+/// [Duration] itself serializes as milliseconds; B2 would read that as a
+/// duration a thousand times too long. Fractional seconds are truncated.
+pub(crate) fn serialize_seconds<S>(d: &Duration, serializer: S)
+-> Result<S::Ok, S::Error>
+    where S: serde::Serializer,
+{
+    serializer.serialize_i64(d.num_seconds())
+}
+
+/// Serialize an optional [Duration] as whole seconds; see
+/// [serialize_seconds].
+pub(crate) fn serialize_optional_seconds<S>(
+    d: &Option<Duration>,
+    serializer: S
+) -> Result<S::Ok, S::Error>
+    where S: serde::Serializer,
+{
+    match d {
+        Some(d) => serialize_seconds(d, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
 struct DurationVisitor;
 
 impl<'de> serde::de::Visitor<'de> for DurationVisitor {
@@ -101,6 +192,26 @@ impl<'de> serde::de::Visitor<'de> for DurationVisitor {
     {
         Ok(s)
     }
+
+    fn visit_u64<E>(self, s: u64) -> Result<Self::Value, E>
+        where E: serde::de::Error,
+    {
+        i64::try_from(s).map_err(|_| E::invalid_value(
+            serde::de::Unexpected::Unsigned(s),
+            &self
+        ))
+    }
+
+    // Fractional milliseconds are truncated.
+    fn visit_f64<E>(self, s: f64) -> Result<Self::Value, E>
+        where E: serde::de::Error,
+    {
+        if s.is_finite() && s >= i64::MIN as f64 && s < i64::MAX as f64 {
+            Ok(s.trunc() as i64)
+        } else {
+            Err(E::invalid_value(serde::de::Unexpected::Float(s), &self))
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Duration {
@@ -135,6 +246,65 @@ pub(crate) const QUERY_ENCODE_SET: AsciiSet = CONTROLS
 
 #[cfg(test)]
 mod tests {
+    use super::{ContentDisposition, Duration, IntoDuration as _};
+    use crate::error::ValidationError;
+
+    #[test]
+    fn content_disposition_is_validated() {
+        let disposition = ContentDisposition::new(
+            "attachment; filename=\"report.pdf\""
+        ).unwrap();
+        assert_eq!(disposition.as_str(), "attachment; filename=\"report.pdf\"");
+
+        assert!(ContentDisposition::new("attachment; filename*=report.pdf")
+            .is_err());
+    }
+
+    #[test]
+    fn std_duration_into_duration() {
+        let d = std::time::Duration::from_millis(3_600_500).into_duration()
+            .unwrap();
+        assert_eq!(d, chrono::Duration::milliseconds(3_600_500));
+
+        let d = std::time::Duration::from_secs(u64::MAX).into_duration();
+        assert!(matches!(d, Err(ValidationError::OutOfBounds(_))));
+    }
+
+    #[test]
+    fn serialize_duration_as_seconds() {
+        let mut json = serde_json::Serializer::new(vec![]);
+        super::serialize_seconds(
+            &Duration(chrono::Duration::milliseconds(3_600_900)),
+            &mut json
+        ).unwrap();
+
+        assert_eq!(json.into_inner(), b"3600");
+    }
+
+    #[test]
+    fn deserialize_duration_from_i64() {
+        let d: Duration = serde_json::from_str("-5000").unwrap();
+        assert_eq!(*d, chrono::Duration::milliseconds(-5000));
+    }
+
+    #[test]
+    fn deserialize_duration_from_u64() {
+        let d: Duration = serde_json::from_value(serde_json::json!(86400000u64))
+            .unwrap();
+        assert_eq!(*d, chrono::Duration::days(1));
+
+        let d: Result<Duration, _> = serde_json::from_value(
+            serde_json::json!(u64::MAX)
+        );
+        assert!(d.is_err());
+    }
+
+    #[test]
+    fn deserialize_duration_from_f64_truncates() {
+        let d: Duration = serde_json::from_str("1500.9").unwrap();
+        assert_eq!(*d, chrono::Duration::milliseconds(1500));
+    }
+
     #[test]
     fn b2_filename_encoding_tests() {
         use crate::types::QUERY_ENCODE_SET;
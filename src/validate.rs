@@ -85,6 +85,25 @@ pub(crate) fn validated_file_name(name: &str)
     }
 }
 
+/// Ensure a SHA1 checksum to send in an `X-Bz-Content-Sha1` header is valid.
+///
+/// The checksum must be 40 hexadecimal digits, or one of the special values
+/// `do_not_verify` and `hex_digits_at_end`.
+pub(crate) fn validated_sha1_checksum(checksum: &str)
+-> Result<&str, ValidationError> {
+    let is_hex = checksum.len() == 40
+        && checksum.bytes().all(|b| b.is_ascii_hexdigit());
+
+    if is_hex || checksum == "do_not_verify" || checksum == "hex_digits_at_end"
+    {
+        Ok(checksum)
+    } else {
+        Err(ValidationError::BadFormat(
+            format!("Invalid SHA1 checksum: {}", checksum)
+        ))
+    }
+}
+
 pub(crate) fn validated_cors_rule_name(name: impl Into<String>)
 -> Result<String, CorsRuleValidationError> {
     // The rules are the same as for bucket names.